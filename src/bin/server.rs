@@ -0,0 +1,37 @@
+use std::{process, time::Duration};
+
+use bevy::{app::ScheduleRunnerPlugin, log::LogPlugin, prelude::*};
+use voxel_game::{
+    game::{save::resources::SaveDirectory, GamePlugin},
+    server::{resources::ServerArgs, ServerPlugin},
+    AppState,
+};
+
+fn main() {
+    let args = ServerArgs::parse(std::env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{error}\n{}", ServerArgs::USAGE);
+        process::exit(2);
+    });
+
+    let mut app = App::new();
+    app.add_plugins((
+        // wake up about once a tick, the fixed timestep catches up on any ticks missed
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / args.tick_rate,
+        ))),
+        LogPlugin::default(),
+        GamePlugin,
+        ServerPlugin,
+    ))
+    .add_state::<AppState>()
+    .insert_resource(Time::<Fixed>::from_hz(args.tick_rate))
+    .insert_resource(args.settings.clone());
+    if let Some(world) = &args.world {
+        app.insert_resource(SaveDirectory(world.clone()));
+    }
+    println!(
+        "Server running world at {} ticks per second on port {}",
+        args.tick_rate, args.settings.port
+    );
+    app.run();
+}
@@ -0,0 +1,149 @@
+use std::{fmt::Write, io};
+
+use bevy::prelude::*;
+
+pub fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads little endian values out of a byte slice, for the binary file and network formats
+pub struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        ByteReader { bytes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Everything not read yet
+    pub fn rest(&mut self) -> &'a [u8] {
+        std::mem::take(&mut self.bytes)
+    }
+
+    pub fn take(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if self.bytes.len() < count {
+            return Err(invalid_data("data ends early"));
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    pub fn i32(&mut self) -> io::Result<i32> {
+        Ok(self.u32()? as i32)
+    }
+
+    /// A finite float, NaN and infinities are refused so they can not spread into positions
+    pub fn f32(&mut self) -> io::Result<f32> {
+        let value = f32::from_bits(self.u32()?);
+        if !value.is_finite() {
+            return Err(invalid_data("number is not finite"));
+        }
+        Ok(value)
+    }
+
+    pub fn ivec3(&mut self) -> io::Result<IVec3> {
+        Ok(IVec3::new(self.i32()?, self.i32()?, self.i32()?))
+    }
+
+    pub fn vec3(&mut self) -> io::Result<Vec3> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    /// A utf-8 string behind its u16 byte length, as written by `write_string`
+    pub fn string(&mut self) -> io::Result<String> {
+        let length = self.u16()? as usize;
+        String::from_utf8(self.take(length)?.to_vec())
+            .map_err(|_| invalid_data("text is not utf-8"))
+    }
+}
+
+pub fn write_ivec3(bytes: &mut Vec<u8>, value: IVec3) {
+    for axis in value.to_array() {
+        bytes.extend_from_slice(&axis.to_le_bytes());
+    }
+}
+
+pub fn write_vec3(bytes: &mut Vec<u8>, value: Vec3) {
+    for axis in value.to_array() {
+        bytes.extend_from_slice(&axis.to_le_bytes());
+    }
+}
+
+/// Cuts text down to at most `max_length` bytes without splitting a character
+pub fn truncate(text: &str, max_length: usize) -> &str {
+    let mut length = text.len().min(max_length);
+    while !text.is_char_boundary(length) {
+        length -= 1;
+    }
+    &text[..length]
+}
+
+/// Writes a string behind its byte length, strings longer than a u16 are cut short
+pub fn write_string(bytes: &mut Vec<u8>, value: &str) {
+    let value = truncate(value, u16::MAX as usize);
+    bytes.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(value.as_bytes());
+}
+
+/// Writes runs of equal values as a u16 length and the value, runs longer than a u16 are split
+pub fn write_runs(bytes: &mut Vec<u8>, values: &[u16]) {
+    let mut start = 0;
+    while start < values.len() {
+        let length = values[start..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|value| **value == values[start])
+            .count();
+        bytes.extend_from_slice(&(length as u16).to_le_bytes());
+        bytes.extend_from_slice(&values[start].to_le_bytes());
+        start += length;
+    }
+}
+
+/// Bytes as lowercase hex digits, for binary data inside the text file formats
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(text, "{byte:02x}");
+    }
+    text
+}
+
+/// Bytes written by `to_hex`
+pub fn from_hex(text: &str) -> io::Result<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return Err(invalid_data("hex has a digit missing"));
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|index| {
+            u8::from_str_radix(&text[index..index + 2], 16)
+                .map_err(|_| invalid_data("not a hex digit"))
+        })
+        .collect()
+}
@@ -0,0 +1,57 @@
+mod systems;
+
+pub mod resources;
+
+use bevy::prelude::*;
+
+use crate::{
+    game::world::{
+        resources::VoxelWorld,
+        systems::{set_blocks, update_chunk},
+        FACE_MASK_BACK, FACE_MASK_BOTTOM, FACE_MASK_FRONT, FACE_MASK_LEFT, FACE_MASK_RIGHT,
+        FACE_MASK_TOP,
+    },
+    AppState,
+};
+
+use self::{resources::*, systems::*};
+
+pub const CHUNK_BORDERS_KEY: KeyCode = KeyCode::F4;
+pub const FACE_MASKS_KEY: KeyCode = KeyCode::F5;
+pub const DIRTY_CHUNKS_KEY: KeyCode = KeyCode::F6;
+
+/// Colour each face mask bit is drawn in
+pub const FACE_MASK_COLORS: [(u8, Color); 6] = [
+    (FACE_MASK_TOP, Color::GREEN),
+    (FACE_MASK_BOTTOM, Color::YELLOW),
+    (FACE_MASK_LEFT, Color::RED),
+    (FACE_MASK_RIGHT, Color::CYAN),
+    (FACE_MASK_FRONT, Color::BLUE),
+    (FACE_MASK_BACK, Color::FUCHSIA),
+];
+pub const CHUNK_BORDER_COLOR: Color = Color::WHITE;
+pub const DIRTY_CHUNK_COLOR: Color = Color::ORANGE_RED;
+/// Chunks are usually remeshed in the frame they are flagged, so they stay highlighted a little longer
+pub const DIRTY_CHUNK_HIGHLIGHT_SECONDS: f32 = 0.5;
+
+/// Gizmos for debugging chunk masks and meshing, only built with the `chunk_debug` feature
+pub struct ChunkDebugPlugin;
+
+impl Plugin for ChunkDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ChunkDebugSettings>()
+            .init_resource::<DirtyChunks>()
+            .add_systems(Update, toggle_chunk_debug)
+            .add_systems(
+                Update,
+                (
+                    // before the flags are cleared by meshing
+                    track_dirty_chunks.after(set_blocks).before(update_chunk),
+                    draw_chunk_borders,
+                    draw_face_masks,
+                    draw_dirty_chunks,
+                )
+                    .run_if(in_state(AppState::Game).and_then(resource_exists::<VoxelWorld>())),
+            );
+    }
+}
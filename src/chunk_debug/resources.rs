@@ -0,0 +1,16 @@
+use bevy::{prelude::*, utils::HashMap};
+
+/// Which chunk debug gizmos are drawn
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Default)]
+pub struct ChunkDebugSettings {
+    pub chunk_borders: bool,
+    /// of the voxels in the chunk the camera is in, every voxel in the world would be too many lines
+    pub face_masks: bool,
+    pub dirty_chunks: bool,
+}
+
+/// Chunks that were flagged for remeshing lately, with how many seconds they stay highlighted
+#[derive(Resource, Debug, Default)]
+pub struct DirtyChunks {
+    pub chunks: HashMap<IVec3, f32>,
+}
@@ -0,0 +1,129 @@
+use bevy::prelude::*;
+
+use crate::game::world::{
+    components::{Chunk, Voxel},
+    raycast::voxel_at_point,
+    resources::VoxelWorld,
+    to_chunk_space, CHUNK_SIZE, FACE_DIRECTIONS, VOXEL_SIZE,
+};
+
+use super::{
+    resources::{ChunkDebugSettings, DirtyChunks},
+    CHUNK_BORDERS_KEY, CHUNK_BORDER_COLOR, DIRTY_CHUNKS_KEY, DIRTY_CHUNK_COLOR,
+    DIRTY_CHUNK_HIGHLIGHT_SECONDS, FACE_MASKS_KEY, FACE_MASK_COLORS,
+};
+
+pub fn toggle_chunk_debug(keys: Res<Input<KeyCode>>, mut settings: ResMut<ChunkDebugSettings>) {
+    if keys.just_pressed(CHUNK_BORDERS_KEY) {
+        settings.chunk_borders = !settings.chunk_borders;
+    }
+    if keys.just_pressed(FACE_MASKS_KEY) {
+        settings.face_masks = !settings.face_masks;
+    }
+    if keys.just_pressed(DIRTY_CHUNKS_KEY) {
+        settings.dirty_chunks = !settings.dirty_chunks;
+    }
+}
+
+/// The box around a chunk in world units, voxels are centered on their positions so the edges are half a voxel out
+fn chunk_bounds(chunk_position: IVec3) -> Transform {
+    let size = CHUNK_SIZE.as_vec3() * VOXEL_SIZE;
+    let min = ((chunk_position * CHUNK_SIZE).as_vec3() - Vec3::splat(0.5)) * VOXEL_SIZE;
+    Transform::from_translation(min + size / 2.0).with_scale(size)
+}
+
+/// Remembers chunks flagged for remeshing, and forgets them once their highlight runs out
+pub fn track_dirty_chunks(
+    voxel_world: Res<VoxelWorld>,
+    chunk_query: Query<&Chunk>,
+    time: Res<Time>,
+    mut dirty_chunks: ResMut<DirtyChunks>,
+) {
+    dirty_chunks.chunks.retain(|_, seconds_left| {
+        *seconds_left -= time.delta_seconds();
+        *seconds_left > 0.0
+    });
+    for (chunk_position, chunk) in &voxel_world.chunks {
+        if chunk_query
+            .get(chunk.entity_id)
+            .is_ok_and(|chunk| chunk.updated)
+        {
+            dirty_chunks
+                .chunks
+                .insert(*chunk_position, DIRTY_CHUNK_HIGHLIGHT_SECONDS);
+        }
+    }
+}
+
+pub fn draw_chunk_borders(
+    settings: Res<ChunkDebugSettings>,
+    voxel_world: Res<VoxelWorld>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.chunk_borders {
+        return;
+    }
+    for chunk_position in voxel_world.chunks.keys() {
+        gizmos.cuboid(chunk_bounds(*chunk_position), CHUNK_BORDER_COLOR);
+    }
+}
+
+pub fn draw_dirty_chunks(
+    settings: Res<ChunkDebugSettings>,
+    dirty_chunks: Res<DirtyChunks>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.dirty_chunks {
+        return;
+    }
+    for chunk_position in dirty_chunks.chunks.keys() {
+        // drawn a little inside the border so both can be seen at once
+        let bounds = chunk_bounds(*chunk_position);
+        let bounds = bounds.with_scale(bounds.scale - Vec3::splat(VOXEL_SIZE * 0.5));
+        gizmos.cuboid(bounds, DIRTY_CHUNK_COLOR);
+    }
+}
+
+/// Outlines every face flagged in the masks of the voxels in the chunk the camera is in
+pub fn draw_face_masks(
+    settings: Res<ChunkDebugSettings>,
+    voxel_world: Res<VoxelWorld>,
+    voxel_query: Query<&Voxel>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut gizmos: Gizmos,
+) {
+    if !settings.face_masks {
+        return;
+    }
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    let (chunk_position, _) = to_chunk_space(voxel_at_point(camera_transform.translation()));
+    let Some(chunk) = voxel_world.chunks.get(&chunk_position) else {
+        return;
+    };
+    let chunk_origin = chunk_position * CHUNK_SIZE;
+    for (local_position, entity) in &chunk.blocks {
+        let Ok(voxel) = voxel_query.get(*entity) else {
+            continue;
+        };
+        let center = (chunk_origin + *local_position).as_vec3() * VOXEL_SIZE;
+        for (face, direction) in FACE_DIRECTIONS {
+            if voxel.mask & face == 0 {
+                continue;
+            }
+            let color = FACE_MASK_COLORS
+                .iter()
+                .find(|(mask, _)| *mask == face)
+                .map_or(Color::WHITE, |(_, color)| *color);
+            let normal = direction.as_vec3();
+            // a bit inside the voxel so faces of neighbours do not overlap
+            gizmos.rect(
+                center + normal * VOXEL_SIZE * 0.45,
+                Quat::from_rotation_arc(Vec3::Z, normal),
+                Vec2::splat(VOXEL_SIZE * 0.8),
+                color,
+            );
+        }
+    }
+}
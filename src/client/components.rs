@@ -0,0 +1,12 @@
+use bevy::prelude::*;
+
+use crate::network::prediction::SnapshotBuffer;
+
+/// Another player on the server, moved between the positions the server relays
+#[derive(Component, Debug, Clone, Default, Reflect)]
+#[reflect(Component)]
+pub struct RemotePlayer {
+    pub player_id: u32,
+    #[reflect(ignore)]
+    pub snapshots: SnapshotBuffer,
+}
@@ -0,0 +1,388 @@
+mod systems;
+
+pub mod components;
+pub mod resources;
+
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+
+use crate::{
+    game::{
+        save::SaveSet,
+        sky::systems::advance_world_time,
+        tick::WorldTickSet,
+        world::{
+            resources::{CubeMesh, VoxelWorld},
+            systems::set_blocks,
+        },
+        SimulationState,
+    },
+    AppState,
+};
+
+use self::{components::*, resources::*, systems::*};
+
+/// File the key this player proves who they are to servers with is kept in, next to the game
+pub const PLAYER_KEY_FILE_NAME: &str = "player_key.txt";
+
+/// Lines of chat kept in the chat window
+pub const MAX_CHAT_LINES: usize = 100;
+/// Height of the chat window's message list, in egui points
+pub const CHAT_WINDOW_HEIGHT: f32 = 150.0;
+
+/// Size of the box drawn for other players, in voxels
+pub const REMOTE_PLAYER_SCALE: Vec3 = Vec3::new(1.0, 2.0, 1.0);
+
+/// Plays on a server when `ClientSettings` has an address.
+/// The server runs the simulation then, the local world only shows what it is sent.
+pub struct ClientPlugin;
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        let connected = resource_exists::<NetworkClient>();
+        app.init_resource::<ClientSettings>()
+            .init_resource::<ChatLog>()
+            .init_resource::<PlayerKeyPath>()
+            .register_type::<RemotePlayer>()
+            .configure_sets(
+                FixedUpdate,
+                (
+                    WorldTickSet::Begin,
+                    WorldTickSet::Blocks,
+                    WorldTickSet::Fluids,
+                    WorldTickSet::Entities,
+                )
+                    .run_if(not(connected.clone())),
+            )
+            .add_systems(OnEnter(AppState::Game), connect_to_server)
+            .add_systems(
+                Update,
+                (
+                    receive_from_server
+                        .before(set_blocks)
+                        .run_if(resource_exists::<VoxelWorld>()),
+                    send_block_changes.after(set_blocks),
+                    disconnect_from_server,
+                    flush_to_server,
+                )
+                    .chain()
+                    // receiving can drop the connection, the later systems check again
+                    .distributive_run_if(connected.clone()),
+            )
+            .add_systems(
+                Update,
+                (
+                    stop_fly_camera_movement.run_if(resource_added::<NetworkClient>()),
+                    (restore_fly_camera_movement, restore_world_tick_rate)
+                        .distributive_run_if(resource_removed::<NetworkClient>()),
+                    (show_predicted_player, interpolate_remote_players)
+                        .after(receive_from_server)
+                        .distributive_run_if(connected.clone()),
+                    add_remote_player_meshes.run_if(resource_exists::<CubeMesh>()),
+                    (chat_ui, show_command_feedback).run_if(in_state(AppState::Game)),
+                ),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    predict_player_movement,
+                    // the clock keeps going between the times the server sends
+                    advance_world_time,
+                )
+                    .distributive_run_if(connected),
+            );
+        leave_local_save_alone(app);
+    }
+}
+
+/// The world shown on a server is the server's, it is neither loaded from nor saved over the local save
+fn leave_local_save_alone(app: &mut App) {
+    let local = not(resource_exists::<NetworkClient>());
+    app.configure_sets(Update, SaveSet.run_if(local.clone()))
+        .configure_sets(
+            OnEnter(SimulationState::Paused),
+            SaveSet.run_if(local.clone()),
+        )
+        .configure_sets(Last, SaveSet.run_if(local));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        net::{Ipv4Addr, SocketAddr},
+        thread,
+        time::Duration,
+    };
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::{
+        events::{SaveWorldEvent, SetBlockEvent},
+        game::{
+            blocks::resources::BlockUpdateQueue,
+            commands::parser::PermissionLevel,
+            falling::components::DroppedItem,
+            save::{
+                chunk::chunk_local_position,
+                resources::{SaveDirectory, WorldMetadata},
+                SavePlugin, CHUNK_DIRECTORY_NAME, METADATA_FILE_NAME,
+            },
+            sky::resources::WorldTime,
+            tick::resources::WorldTick,
+            world::{
+                access::{BlockChange, VoxelAccess, WorldVoxels},
+                components::{BlockType, CROP_MAX_AGE},
+                generation::TerrainShape,
+                resources::{NeighbourUpdates, WorldSeed, WorldTerrain},
+                CHUNK_SIZE, VOXEL_SIZE,
+            },
+        },
+        network::{protocol::Message, PLAYER_SPEED},
+        server::{
+            accounts::ServerAccounts,
+            resources::{NetworkServer, ReplicatedChunks},
+            ServerPlugin,
+        },
+    };
+
+    /// A client that only shows what the server sends, in a world of the chunk at the origin
+    fn client_app(server_address: SocketAddr) -> App {
+        let client =
+            NetworkClient::connect(server_address, "Tester", "testkey", Duration::ZERO).unwrap();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<SetBlockEvent>()
+            .init_resource::<ChatLog>()
+            .init_resource::<WorldTime>()
+            .init_resource::<NeighbourUpdates>()
+            .insert_resource(client)
+            .add_systems(
+                Update,
+                (receive_from_server, send_block_changes, flush_to_server).chain(),
+            );
+        VoxelWorld::spawn_test_world(&mut app.world, CHUNK_SIZE);
+        app
+    }
+
+    fn block_at(app: &mut App, position: IVec3) -> BlockChange {
+        app.world.run_system_once(move |voxels: WorldVoxels| {
+            voxels.voxel(position).unwrap().block_change()
+        })
+    }
+
+    /// Updates the server and the client in turn until `done` is true for the client
+    fn exchange(server: &mut App, client: &mut App, mut done: impl FnMut(&mut App) -> bool) {
+        for _ in 0..1000 {
+            server.update();
+            client.update();
+            if done(client) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        panic!("the client never caught up with the server");
+    }
+
+    /// A server on a world of its own and a client connected to it
+    fn session(test_name: &str) -> (SaveDirectory, App, App) {
+        let directory = SaveDirectory(
+            std::env::temp_dir().join(format!("voxel_game_{test_name}_{}", std::process::id())),
+        );
+        let mut server = ServerPlugin::test_app(&directory, None);
+        server.update();
+        let port = server
+            .world
+            .resource::<NetworkServer>()
+            .socket
+            .local_addr()
+            .unwrap()
+            .port();
+        let client = client_app(SocketAddr::from((Ipv4Addr::LOCALHOST, port)));
+        (directory, server, client)
+    }
+
+    #[test]
+    fn chunks_and_block_edits_reach_the_client() {
+        let (directory, mut server, mut client) = session("loopback");
+
+        // the chunks around the spawn point are streamed once the client has logged in
+        exchange(&mut server, &mut client, |client| {
+            client
+                .world
+                .resource::<NetworkClient>()
+                .loaded_chunks
+                .contains(&IVec3::ZERO)
+        });
+        // let the client read everything the server has sent so far
+        for _ in 0..3 {
+            thread::sleep(Duration::from_millis(5));
+            client.update();
+        }
+        let replicated = server.world.resource::<ReplicatedChunks>().blocks[&IVec3::ZERO].clone();
+        for (index, block) in replicated.into_iter().enumerate() {
+            let position = chunk_local_position(index);
+            assert_eq!(block_at(&mut client, position), block, "{position}");
+        }
+
+        // an edit in reach is applied by the server and comes back as a delta,
+        // one out of reach and one by a player next to the spawn are refused
+        let edited = IVec3::new(0, CHUNK_SIZE.y - 1, CHUNK_SIZE.z - 1);
+        let out_of_reach = IVec3::new(60, 0, 60);
+        let protected = IVec3::new(0, CHUNK_SIZE.y - 1, 40);
+        let before = block_at(&mut server, out_of_reach);
+        let before_protected = block_at(&mut server, protected);
+        client.world.send_event(SetBlockEvent {
+            position: protected,
+            block: Some(BlockType::Glass),
+            state: 0,
+        });
+        client.world.send_event(SetBlockEvent {
+            position: edited,
+            block: Some(BlockType::Crop),
+            state: CROP_MAX_AGE,
+        });
+        client.world.send_event(SetBlockEvent {
+            position: out_of_reach,
+            block: Some(BlockType::Glass),
+            state: 0,
+        });
+        let crop = Some((BlockType::Crop, CROP_MAX_AGE));
+        exchange(&mut server, &mut client, |client| {
+            block_at(client, edited) == crop
+        });
+        let _ = fs::remove_dir_all(&directory.0);
+        assert_eq!(block_at(&mut server, edited), crop);
+        assert_eq!(block_at(&mut server, out_of_reach), before);
+        assert_eq!(block_at(&mut server, protected), before_protected);
+    }
+
+    #[test]
+    fn operators_complete_their_commands_and_see_what_they_give() {
+        let (directory, mut server, mut client) = session("give");
+        let mut accounts = server.world.resource_mut::<ServerAccounts>();
+        let account = accounts.players.find_or_create("Tester");
+        account.permission = PermissionLevel::Operator;
+        account.key = Some("testkey".to_string());
+        exchange(&mut server, &mut client, |client| {
+            client.world.resource::<NetworkClient>().player_id.is_some()
+        });
+        assert_eq!(
+            client.world.resource::<NetworkClient>().permission,
+            PermissionLevel::Operator
+        );
+
+        let message = Message::Chat {
+            text: "/give sand 2".to_string(),
+        };
+        client
+            .world
+            .resource_mut::<NetworkClient>()
+            .send(&message, Duration::ZERO);
+        exchange(&mut server, &mut client, |client| {
+            client
+                .world
+                .query::<&DroppedItem>()
+                .iter(&client.world)
+                .count()
+                == 2
+        });
+        let _ = fs::remove_dir_all(&directory.0);
+        for dropped_item in client.world.query::<&DroppedItem>().iter(&client.world) {
+            assert_eq!(dropped_item.block, BlockType::Sand);
+        }
+    }
+
+    #[test]
+    fn playing_on_a_server_leaves_the_local_save_alone() {
+        let (directory, mut server, mut client) = session("remote_save");
+        let local = SaveDirectory(
+            std::env::temp_dir().join(format!("voxel_game_local_save_{}", std::process::id())),
+        );
+        let metadata = WorldMetadata {
+            seed: 1234,
+            terrain: TerrainShape::default(),
+            world_time: WorldTime::default(),
+        };
+        metadata.save(&local).unwrap();
+        let saved_metadata = fs::read_to_string(local.0.join(METADATA_FILE_NAME)).unwrap();
+        client
+            .insert_resource(local.clone())
+            .add_state::<AppState>()
+            .add_state::<SimulationState>()
+            .init_resource::<WorldSeed>()
+            .init_resource::<WorldTerrain>()
+            .init_resource::<WorldTick>()
+            .init_resource::<BlockUpdateQueue>()
+            .add_plugins(SavePlugin);
+        leave_local_save_alone(&mut client);
+
+        exchange(&mut server, &mut client, |client| {
+            client
+                .world
+                .resource::<NetworkClient>()
+                .loaded_chunks
+                .contains(&IVec3::ZERO)
+        });
+        // asked to save, then paused
+        client.world.send_event(SaveWorldEvent);
+        client.update();
+        client
+            .world
+            .resource_mut::<NextState<SimulationState>>()
+            .set(SimulationState::Paused);
+        client.update();
+        client.update();
+        let metadata_after = fs::read_to_string(local.0.join(METADATA_FILE_NAME)).unwrap();
+        let chunks_saved = local.0.join(CHUNK_DIRECTORY_NAME).exists();
+        let _ = fs::remove_dir_all(&directory.0);
+        fs::remove_dir_all(&local.0).unwrap();
+        assert_eq!(metadata_after, saved_metadata);
+        assert!(!chunks_saved);
+    }
+
+    #[test]
+    fn clients_follow_the_tick_rate_of_their_server() {
+        let (directory, mut server, mut client) = session("tick_rate");
+        server
+            .world
+            .resource_mut::<Time<Fixed>>()
+            .set_timestep_hz(10.0);
+        exchange(&mut server, &mut client, |client| {
+            client.world.resource::<NetworkClient>().player_id.is_some()
+        });
+        let _ = fs::remove_dir_all(&directory.0);
+        assert_eq!(client.world.resource::<NetworkClient>().tick_rate, 10.0);
+        assert_eq!(
+            client.world.resource::<Time<Fixed>>().timestep(),
+            Duration::from_millis(100)
+        );
+
+        // an input moves the player a tenth of a second's worth, the same on both sides
+        let mut player = server
+            .world
+            .resource::<NetworkServer>()
+            .clients
+            .values()
+            .find_map(|client| client.player.clone())
+            .unwrap();
+        let mut prediction = client
+            .world
+            .resource::<NetworkClient>()
+            .prediction
+            .clone()
+            .unwrap();
+        let spawn = player.position;
+        assert_eq!(prediction.predicted_position(), spawn);
+        let input = prediction.apply_input(Vec3::X);
+        player.receive_inputs(&[input]);
+        player.apply_next_input();
+        assert_eq!(player.position, prediction.predicted_position());
+        let step = PLAYER_SPEED * VOXEL_SIZE / 10.0;
+        assert!((player.position.x - spawn.x - step).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    bytes::{invalid_data, to_hex},
+    game::{commands::parser::PermissionLevel, tick::WORLD_TICKS_PER_SECOND},
+    network::{
+        connection::Connection, prediction::Prediction, protocol::Message, MAX_KEY_LENGTH,
+        PLAYER_KEY_LENGTH, PROTOCOL_VERSION,
+    },
+};
+
+use super::{MAX_CHAT_LINES, PLAYER_KEY_FILE_NAME};
+
+/// Where to play, a local world when no server address is set
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ClientSettings {
+    pub server_address: Option<SocketAddr>,
+    pub name: String,
+    /// start on the multiplayer screen instead of in a local world
+    pub open_server_browser: bool,
+    /// start on the create world screen instead of in the default world
+    pub open_create_world: bool,
+}
+
+impl Default for ClientSettings {
+    fn default() -> Self {
+        ClientSettings {
+            server_address: None,
+            name: "Player".to_string(),
+            open_server_browser: false,
+            open_create_world: false,
+        }
+    }
+}
+
+impl ClientSettings {
+    pub const USAGE: &'static str =
+        "usage: Voxel_Game [--connect <address:port>] [--name <name>] [--servers] [--new-world]";
+
+    /// Parses the arguments after the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut settings = ClientSettings::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {flag}"))
+            };
+            match flag.as_str() {
+                "--connect" => {
+                    let address = value()?;
+                    settings.server_address = Some(
+                        address
+                            .to_socket_addrs()
+                            .ok()
+                            .and_then(|mut addresses| addresses.next())
+                            .ok_or_else(|| format!("invalid server address {address}"))?,
+                    );
+                }
+                "--name" => settings.name = value()?,
+                "--servers" => settings.open_server_browser = true,
+                "--new-world" => settings.open_create_world = true,
+                _ => return Err(format!("unknown argument {flag}")),
+            }
+        }
+        Ok(settings)
+    }
+}
+
+/// Where the player key is kept
+#[derive(Resource, Debug, Clone)]
+pub struct PlayerKeyPath(pub PathBuf);
+
+impl Default for PlayerKeyPath {
+    fn default() -> Self {
+        PlayerKeyPath(PathBuf::from(PLAYER_KEY_FILE_NAME))
+    }
+}
+
+/// A new random player key, as hex
+pub fn new_player_key() -> String {
+    to_hex(&rand::random::<[u8; PLAYER_KEY_LENGTH]>())
+}
+
+/// The key saved at `path`, a new one is made and saved the first time.
+/// Servers tie a name to the key it first logged in with, losing the file loses those names.
+pub fn load_or_create_player_key(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            let key = text.trim();
+            if key.is_empty()
+                || key.len() > MAX_KEY_LENGTH
+                || !key
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric())
+            {
+                return Err(invalid_data("the player key is not a key"));
+            }
+            Ok(key.to_string())
+        }
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            let key = new_player_key();
+            fs::write(path, &key)?;
+            Ok(key)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// The connection to a server, only present while connected
+#[derive(Resource, Debug)]
+pub struct NetworkClient {
+    pub socket: UdpSocket,
+    pub connection: Connection,
+    /// handed out by the server once it accepts the login
+    pub player_id: Option<u32>,
+    /// what the server lets this player run, commands above it are not offered
+    pub permission: PermissionLevel,
+    /// payloads of chunks still arriving, by chunk position
+    pub chunk_parts: HashMap<IVec3, Vec<u8>>,
+    /// chunks the server keeps up to date, the rest of the world may be stale
+    pub loaded_chunks: HashSet<IVec3>,
+    /// the local player, predicted from its inputs, once logged in
+    pub prediction: Option<Prediction>,
+    /// estimate of the server's current world tick, other players are shown a little behind it
+    pub server_tick: f64,
+    /// world ticks a second of the server, sent on login
+    pub tick_rate: f32,
+}
+
+impl NetworkClient {
+    /// Opens a socket towards the server and queues the handshake and login
+    pub fn connect(
+        server_address: SocketAddr,
+        name: &str,
+        key: &str,
+        now: Duration,
+    ) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.connect(server_address)?;
+        socket.set_nonblocking(true)?;
+        let mut connection = Connection::new(now);
+        connection.send(
+            &Message::Handshake {
+                protocol_version: PROTOCOL_VERSION,
+            },
+            now,
+        );
+        connection.send(
+            &Message::Login {
+                name: name.to_string(),
+                key: key.to_string(),
+            },
+            now,
+        );
+        Ok(NetworkClient {
+            socket,
+            connection,
+            player_id: None,
+            permission: PermissionLevel::default(),
+            chunk_parts: HashMap::new(),
+            loaded_chunks: HashSet::new(),
+            prediction: None,
+            server_tick: 0.0,
+            tick_rate: WORLD_TICKS_PER_SECOND as f32,
+        })
+    }
+
+    pub fn send(&mut self, message: &Message, now: Duration) {
+        self.connection.send(message, now);
+    }
+}
+
+/// Chat and command feedback shown in the chat window, with what is being typed
+#[derive(Resource, Debug, Default)]
+pub struct ChatLog {
+    pub lines: VecDeque<String>,
+    pub input: String,
+}
+
+impl ChatLog {
+    /// Adds a message, keeping the last `MAX_CHAT_LINES` lines
+    pub fn push(&mut self, text: &str) {
+        self.lines.extend(text.lines().map(str::to_string));
+        while self.lines.len() > MAX_CHAT_LINES {
+            self.lines.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn player_keys_are_made_once_and_kept() {
+        let path =
+            std::env::temp_dir().join(format!("voxel_game_player_key_{}", std::process::id()));
+        let key = load_or_create_player_key(&path).unwrap();
+        assert_eq!(key.len(), PLAYER_KEY_LENGTH * 2);
+        assert_eq!(load_or_create_player_key(&path).unwrap(), key);
+        assert_ne!(new_player_key(), key);
+
+        fs::write(&path, "not a key\n").unwrap();
+        let broken = load_or_create_player_key(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(broken.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
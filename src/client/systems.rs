@@ -0,0 +1,477 @@
+use std::{io, time::Duration};
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+use bevy_egui::{egui, EguiContexts};
+use bevy_flycam::prelude::*;
+
+use crate::{
+    events::{CommandEvent, CommandFeedbackEvent, CommandSender, SetBlockEvent},
+    game::{
+        commands::parser::{complete, parse_command, PermissionLevel},
+        falling::{components::DroppedItem, DROPPED_ITEM_SCALE},
+        save::chunk::{chunk_local_position, decode_chunk},
+        sky::resources::WorldTime,
+        tick::WORLD_TICKS_PER_SECOND,
+        world::{
+            access::{BlockChange, VoxelAccess, WorldVoxels},
+            resources::CubeMesh,
+            CHUNK_SIZE,
+        },
+    },
+    network::{
+        prediction::{Prediction, SnapshotBuffer},
+        protocol::Message,
+        INTERPOLATION_DELAY_TICKS, MAX_PACKET_SIZE, MAX_SENT_INPUTS,
+    },
+};
+
+use super::{
+    components::RemotePlayer,
+    resources::{
+        load_or_create_player_key, new_player_key, ChatLog, ClientSettings, NetworkClient,
+        PlayerKeyPath,
+    },
+    CHAT_WINDOW_HEIGHT, REMOTE_PLAYER_SCALE,
+};
+
+/// Connects to the configured server when a game starts, if there is one
+pub fn connect_to_server(
+    mut commands: Commands,
+    settings: Res<ClientSettings>,
+    key_path: Res<PlayerKeyPath>,
+    time: Res<Time<Real>>,
+) {
+    let Some(server_address) = settings.server_address else {
+        return;
+    };
+    let key = load_or_create_player_key(&key_path.0).unwrap_or_else(|error| {
+        // the name can only be played under again with the same key, this one is not kept
+        warn!(
+            "Failed to keep the player key in {}: {error}",
+            key_path.0.display()
+        );
+        new_player_key()
+    });
+    match NetworkClient::connect(server_address, &settings.name, &key, time.elapsed()) {
+        Ok(client) => {
+            info!("Connecting to {server_address} as {}", settings.name);
+            commands.insert_resource(client);
+        }
+        Err(error) => error!("Failed to connect to {server_address}: {error}"),
+    }
+}
+
+/// Reads every packet from the server and applies what it says to the local world
+#[allow(clippy::too_many_arguments)]
+pub fn receive_from_server(
+    mut commands: Commands,
+    mut client: ResMut<NetworkClient>,
+    mut chat_log: ResMut<ChatLog>,
+    time: Res<Time<Real>>,
+    mut fixed_time: ResMut<Time<Fixed>>,
+    mut world_time: ResMut<WorldTime>,
+    mut voxels: WorldVoxels,
+    mut remote_player_query: Query<&mut RemotePlayer>,
+) {
+    let now = time.elapsed();
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    loop {
+        let length = match client.socket.recv(&mut buffer) {
+            Ok(length) => length,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+            // the server not listening shows up here, the connection times out if it stays away
+            Err(_) => continue,
+        };
+        let Ok(messages) = client.connection.receive(&buffer[..length], now) else {
+            continue;
+        };
+        for message in messages {
+            match message {
+                Message::LoginAccepted {
+                    player_id,
+                    spawn,
+                    permission,
+                    tick_rate,
+                } => {
+                    info!("Logged in as player {player_id}");
+                    client.player_id = Some(player_id);
+                    client.permission = permission;
+                    client.prediction = Some(Prediction::new(spawn, tick_rate));
+                    // inputs are made once a tick, as often as the server applies them
+                    client.tick_rate = tick_rate;
+                    fixed_time.set_timestep_hz(tick_rate as f64);
+                }
+                Message::PermissionChanged { permission } => client.permission = permission,
+                Message::DroppedItem { block, position } => {
+                    commands.spawn((
+                        SpatialBundle::from_transform(
+                            Transform::from_translation(position)
+                                .with_scale(Vec3::splat(DROPPED_ITEM_SCALE)),
+                        ),
+                        DroppedItem { block },
+                        Name::new("Dropped Item"),
+                    ));
+                }
+                Message::PlayerState {
+                    input_sequence,
+                    position,
+                } => {
+                    if let Some(prediction) = &mut client.prediction {
+                        prediction.reconcile(position, input_sequence);
+                    }
+                }
+                Message::ChunkData {
+                    position,
+                    part,
+                    part_count,
+                    payload,
+                } => {
+                    let parts = client.chunk_parts.entry(position).or_default();
+                    if part == 0 {
+                        parts.clear();
+                    }
+                    parts.extend_from_slice(&payload);
+                    if part + 1 < part_count {
+                        continue;
+                    }
+                    let bytes = client.chunk_parts.remove(&position).unwrap_or_default();
+                    match decode_chunk(&bytes) {
+                        Ok(blocks) => {
+                            apply_chunk(&mut voxels, position, blocks);
+                            client.loaded_chunks.insert(position);
+                        }
+                        Err(error) => warn!("Bad chunk {position} from server: {error}"),
+                    }
+                }
+                Message::ChunkDelta { position, changes } => {
+                    for (index, block) in changes {
+                        let local_position = chunk_local_position(index as usize);
+                        voxels.apply_change(position * CHUNK_SIZE + local_position, block);
+                    }
+                }
+                Message::UnloadChunk { position } => {
+                    // the blocks stay as they were last sent, they are just not kept up to date
+                    client.loaded_chunks.remove(&position);
+                }
+                Message::BlockChange { position, block } => {
+                    voxels.apply_change(position, block);
+                }
+                Message::EntityPosition {
+                    entity_id,
+                    tick,
+                    position,
+                } => {
+                    client.server_tick = client.server_tick.max(tick as f64);
+                    let remote_player = remote_player_query
+                        .iter_mut()
+                        .find(|remote_player| remote_player.player_id == entity_id);
+                    match remote_player {
+                        Some(mut remote_player) => remote_player.snapshots.push(tick, position),
+                        None => {
+                            let mut snapshots = SnapshotBuffer::default();
+                            snapshots.push(tick, position);
+                            commands.spawn((
+                                SpatialBundle::from_transform(
+                                    Transform::from_translation(position)
+                                        .with_scale(REMOTE_PLAYER_SCALE),
+                                ),
+                                RemotePlayer {
+                                    player_id: entity_id,
+                                    snapshots,
+                                },
+                                Name::new(format!("Player {entity_id}")),
+                            ));
+                        }
+                    }
+                }
+                Message::WorldTime { day, time_of_day } => {
+                    *world_time = WorldTime { day, time_of_day };
+                }
+                Message::Chat { text } => chat_log.push(&text),
+                Message::Disconnect { reason } => {
+                    chat_log.push(&format!("Disconnected: {reason}"));
+                    commands.remove_resource::<NetworkClient>();
+                    break;
+                }
+                // only servers are sent these
+                Message::Handshake { .. } | Message::Login { .. } | Message::PlayerInput { .. } => {
+                }
+            }
+        }
+    }
+    voxels.update_touched_chunks();
+}
+
+/// Turns a chunk into the one the server sent, only touching the blocks that differ
+fn apply_chunk(voxels: &mut WorldVoxels, chunk_position: IVec3, blocks: Vec<BlockChange>) {
+    for (index, block) in blocks.into_iter().enumerate() {
+        let position = chunk_position * CHUNK_SIZE + chunk_local_position(index);
+        let current = voxels.voxel(position).map(|voxel| voxel.block_change());
+        if current.is_some_and(|current| current != block) {
+            voxels.apply_change(position, block);
+        }
+    }
+}
+
+/// Sends blocks set on this client to the server, which applies them and tells everyone else
+pub fn send_block_changes(
+    mut client: ResMut<NetworkClient>,
+    time: Res<Time<Real>>,
+    mut set_block_events: EventReader<SetBlockEvent>,
+) {
+    for event in set_block_events.read() {
+        let message = Message::BlockChange {
+            position: event.position,
+            block: event.block.map(|block| (block, event.state)),
+        };
+        client.send(&message, time.elapsed());
+    }
+}
+
+/// Turns the movement keys into a tick of input for the local player, moves the prediction by it
+/// and sends it to the server along with the inputs the server has not acknowledged yet.
+/// Moves the same way the fly camera does offline.
+pub fn predict_player_movement(
+    mut client: ResMut<NetworkClient>,
+    time: Res<Time<Real>>,
+    keys: Res<Input<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<&Transform, With<FlyCam>>,
+) {
+    let Some(prediction) = &mut client.prediction else {
+        return;
+    };
+    let mut movement = Vec3::ZERO;
+    let cursor_grabbed = window_query
+        .get_single()
+        .is_ok_and(|window| window.cursor.grab_mode != CursorGrabMode::None);
+    if let (true, Ok(camera_transform)) = (cursor_grabbed, camera_query.get_single()) {
+        let local_z = camera_transform.local_z();
+        let forward = -Vec3::new(local_z.x, 0.0, local_z.z);
+        let right = Vec3::new(local_z.z, 0.0, -local_z.x);
+        for (key, direction) in [
+            (key_bindings.move_forward, forward),
+            (key_bindings.move_backward, -forward),
+            (key_bindings.move_left, -right),
+            (key_bindings.move_right, right),
+            (key_bindings.move_ascend, Vec3::Y),
+            (key_bindings.move_descend, Vec3::NEG_Y),
+        ] {
+            if keys.pressed(key) {
+                movement += direction;
+            }
+        }
+    }
+    prediction.apply_input(movement.normalize_or_zero());
+    let inputs = prediction.pending_inputs(MAX_SENT_INPUTS);
+    client.send(&Message::PlayerInput { inputs }, time.elapsed());
+}
+
+/// Moves the camera to the predicted player, smoothing away corrections from the server
+pub fn show_predicted_player(
+    mut client: ResMut<NetworkClient>,
+    time: Res<Time>,
+    mut camera_query: Query<&mut Transform, With<FlyCam>>,
+) {
+    let Some(prediction) = &mut client.prediction else {
+        return;
+    };
+    prediction.decay_correction(time.delta_seconds());
+    for mut camera_transform in camera_query.iter_mut() {
+        camera_transform.translation = prediction.display_position();
+    }
+}
+
+/// Shows other players where they were a couple of server ticks ago, between the positions around then
+pub fn interpolate_remote_players(
+    mut client: ResMut<NetworkClient>,
+    time: Res<Time>,
+    mut remote_player_query: Query<(&RemotePlayer, &mut Transform)>,
+) {
+    let newest_tick = remote_player_query
+        .iter()
+        .filter_map(|(remote_player, _)| remote_player.snapshots.newest_tick())
+        .max();
+    client.server_tick += time.delta_seconds_f64() * client.tick_rate as f64;
+    // without new positions the clock would run off ahead of the server
+    if let Some(newest_tick) = newest_tick {
+        client.server_tick = client.server_tick.min(newest_tick as f64 + 1.0);
+    }
+    let shown_tick = client.server_tick - INTERPOLATION_DELAY_TICKS;
+    for (remote_player, mut transform) in remote_player_query.iter_mut() {
+        if let Some(position) = remote_player.snapshots.sample(shown_tick) {
+            transform.translation = position;
+        }
+    }
+}
+
+/// The fly camera moves itself offline, while connected the prediction moves it instead
+pub fn stop_fly_camera_movement(mut movement_settings: ResMut<MovementSettings>) {
+    movement_settings.speed = 0.0;
+}
+
+pub fn restore_fly_camera_movement(mut movement_settings: ResMut<MovementSettings>) {
+    movement_settings.speed = MovementSettings::default().speed;
+}
+
+/// The local world ticks at its own rate again once the server's is no longer followed
+pub fn restore_world_tick_rate(mut fixed_time: ResMut<Time<Fixed>>) {
+    fixed_time.set_timestep_hz(WORLD_TICKS_PER_SECOND);
+}
+
+/// Gives newly seen players a box so they can be seen
+pub fn add_remote_player_meshes(
+    mut commands: Commands,
+    remote_player_query: Query<Entity, Added<RemotePlayer>>,
+    cube_mesh: Res<CubeMesh>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for entity in remote_player_query.iter() {
+        commands.entity(entity).insert((
+            cube_mesh.mesh_handle.clone(),
+            materials.add(Color::WHITE.into()),
+        ));
+    }
+}
+
+/// Tells the server this client is leaving when the game closes
+pub fn disconnect_from_server(
+    mut client: ResMut<NetworkClient>,
+    time: Res<Time<Real>>,
+    mut app_exit_events: EventReader<AppExit>,
+) {
+    if app_exit_events.read().next().is_some() {
+        let message = Message::Disconnect {
+            reason: "client closed".to_string(),
+        };
+        client.send(&message, time.elapsed());
+    }
+}
+
+/// Puts queued, resent and ack packets on the socket, and gives up on a server that went quiet
+pub fn flush_to_server(
+    mut commands: Commands,
+    mut client: ResMut<NetworkClient>,
+    time: Res<Time<Real>>,
+) {
+    let now = time.elapsed();
+    for packet in client.connection.flush(now) {
+        // lost packets are resent, unreliable ones are replaced by newer ones
+        let _ = client.socket.send(&packet);
+    }
+    if client.connection.timed_out(now) {
+        warn!("Connection to server timed out");
+        commands.remove_resource::<NetworkClient>();
+    }
+}
+
+/// Sends what was typed in the chat window. On a server everything goes to the server,
+/// which runs the commands. In single player commands run locally with every permission.
+fn submit_chat(
+    text: String,
+    client: Option<&mut NetworkClient>,
+    chat_log: &mut ChatLog,
+    settings: &ClientSettings,
+    origin: Option<Vec3>,
+    now: Duration,
+    command_events: &mut EventWriter<CommandEvent>,
+) {
+    if let Some(client) = client {
+        client.send(&Message::Chat { text }, now);
+    } else if text.starts_with('/') {
+        match parse_command(&text, PermissionLevel::Admin) {
+            Ok(command) => command_events.send(CommandEvent {
+                sender: CommandSender::LocalPlayer,
+                permission: PermissionLevel::Admin,
+                origin,
+                command,
+            }),
+            Err(error) => chat_log.push(&error.to_string()),
+        }
+    } else {
+        chat_log.push(&format!("<{}> {text}", settings.name));
+    }
+}
+
+/// The chat window, Enter sends what was typed and Tab completes commands
+pub fn chat_ui(
+    mut contexts: EguiContexts,
+    mut chat_log: ResMut<ChatLog>,
+    mut client: Option<ResMut<NetworkClient>>,
+    settings: Res<ClientSettings>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut command_events: EventWriter<CommandEvent>,
+    time: Res<Time<Real>>,
+) {
+    // single player runs everything, on a server only what the account may run is completed
+    let permission = client
+        .as_ref()
+        .map_or(PermissionLevel::Admin, |client| client.permission);
+    let mut submitted = None;
+    egui::Window::new("Chat")
+        .anchor(egui::Align2::LEFT_BOTTOM, [8.0, -8.0])
+        .resizable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::ScrollArea::vertical()
+                .max_height(CHAT_WINDOW_HEIGHT)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for line in &chat_log.lines {
+                        ui.label(line);
+                    }
+                });
+            let response = ui.add(egui::TextEdit::singleline(&mut chat_log.input).lock_focus(true));
+            chat_log.input.retain(|character| character != '\t');
+            if response.has_focus() && ui.input(|input| input.key_pressed(egui::Key::Tab)) {
+                let completions = complete(&chat_log.input, permission);
+                match completions.as_slice() {
+                    [] => {}
+                    [completion] => chat_log.input = format!("{completion} "),
+                    _ => {
+                        let options = completions.join("  ");
+                        chat_log.push(&options);
+                    }
+                }
+            }
+            if response.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter)) {
+                let text = std::mem::take(&mut chat_log.input);
+                if !text.trim().is_empty() {
+                    submitted = Some(text.trim().to_string());
+                }
+                response.request_focus();
+            }
+        });
+
+    if let Some(text) = submitted {
+        let origin = camera_query
+            .get_single()
+            .ok()
+            .map(|camera_transform| camera_transform.translation());
+        submit_chat(
+            text,
+            client.as_deref_mut(),
+            &mut chat_log,
+            &settings,
+            origin,
+            time.elapsed(),
+            &mut command_events,
+        );
+    }
+}
+
+/// Shows what commands run in single player had to say
+pub fn show_command_feedback(
+    mut chat_log: ResMut<ChatLog>,
+    mut feedback_events: EventReader<CommandFeedbackEvent>,
+) {
+    for event in feedback_events.read() {
+        if event.recipient == CommandSender::LocalPlayer {
+            chat_log.push(&event.text);
+        }
+    }
+}
@@ -1 +1,25 @@
+mod systems;
+
+pub mod resources;
+
 use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+
+use crate::AppState;
+
+use self::{resources::*, systems::*};
+
+/// The create world screen: a name, a seed and the terrain to generate a new world with
+pub struct CreateWorldPlugin;
+
+impl Plugin for CreateWorldPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.init_resource::<CreateWorldForm>().add_systems(
+            Update,
+            create_world_ui.run_if(in_state(AppState::CreateWorld)),
+        );
+    }
+}
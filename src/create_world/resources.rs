@@ -0,0 +1,94 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::game::{
+    save::{resources::SaveDirectory, METADATA_FILE_NAME},
+    world::{generation::TerrainShape, noise::hash},
+};
+
+/// What has been filled in on the create world screen
+#[derive(Resource, Debug, Clone)]
+pub struct CreateWorldForm {
+    pub name: String,
+    pub seed: String,
+    pub terrain: TerrainShape,
+    pub error: Option<String>,
+}
+
+impl Default for CreateWorldForm {
+    fn default() -> Self {
+        CreateWorldForm {
+            name: "world".to_string(),
+            seed: String::new(),
+            terrain: TerrainShape::default(),
+            error: None,
+        }
+    }
+}
+
+impl CreateWorldForm {
+    /// A number is used as the seed as is, other text is hashed into one and no text picks one at random
+    pub fn seed(&self) -> u64 {
+        let seed = self.seed.trim();
+        if seed.is_empty() {
+            return rand::random();
+        }
+        seed.parse().unwrap_or_else(|_| {
+            seed.chars().fold(0, |hashed, character| {
+                hash(hashed, IVec3::new(character as i32, 0, 0))
+            })
+        })
+    }
+
+    /// Where the new world is saved inside `saves`, or why it can not be
+    pub fn save_directory(&self, saves: &Path) -> Result<SaveDirectory, String> {
+        let name = self.name.trim();
+        if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+            return Err(format!("\"{name}\" can not be the name of a world"));
+        }
+        let directory = saves.join(name);
+        if directory.join(METADATA_FILE_NAME).exists() {
+            return Err(format!("There already is a world called {name}"));
+        }
+        Ok(SaveDirectory(directory))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn form(name: &str, seed: &str) -> CreateWorldForm {
+        CreateWorldForm {
+            name: name.to_string(),
+            seed: seed.to_string(),
+            ..default()
+        }
+    }
+
+    #[test]
+    fn seeds_are_numbers_or_hashed_text() {
+        assert_eq!(form("world", " 1234 ").seed(), 1234);
+        assert_eq!(form("world", "caves").seed(), form("world", "caves").seed());
+        assert_ne!(form("world", "caves").seed(), form("world", "hills").seed());
+    }
+
+    #[test]
+    fn worlds_need_a_free_directory_name() {
+        let saves = std::env::temp_dir().join(format!("voxel_game_saves_{}", std::process::id()));
+        fs::create_dir_all(saves.join("taken")).unwrap();
+        fs::write(saves.join("taken").join(METADATA_FILE_NAME), "seed=1\n").unwrap();
+        let new = form("new", "").save_directory(&saves);
+        let taken = form("taken", "").save_directory(&saves);
+        fs::remove_dir_all(&saves).unwrap();
+
+        assert_eq!(new.unwrap().0, saves.join("new"));
+        assert!(taken.is_err());
+        for name in ["", "  ", "..", "a/b", "a\\b"] {
+            assert!(form(name, "").save_directory(&saves).is_err(), "{name}");
+        }
+    }
+}
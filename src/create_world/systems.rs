@@ -0,0 +1,74 @@
+use std::path::Path;
+
+use bevy::{
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{
+    game::{
+        save::SAVES_DIRECTORY_NAME,
+        world::{
+            generation::TerrainShape,
+            resources::{WorldSeed, WorldTerrain},
+        },
+    },
+    AppState,
+};
+
+use super::resources::CreateWorldForm;
+
+/// Draws the create world screen, and starts the game in the new world once it is filled in
+pub fn create_world_ui(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut form: ResMut<CreateWorldForm>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    // the screen needs the mouse, the camera grabs it again once playing
+    if let Ok(mut window) = window_query.get_single_mut() {
+        if window.cursor.grab_mode != CursorGrabMode::None {
+            window.cursor.grab_mode = CursorGrabMode::None;
+            window.cursor.visible = true;
+        }
+    }
+
+    let mut create = false;
+    egui::Window::new("Create world").show(contexts.ctx_mut(), |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut form.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            ui.text_edit_singleline(&mut form.seed);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Terrain");
+            for terrain in TerrainShape::ALL {
+                ui.radio_value(&mut form.terrain, terrain, terrain.name());
+            }
+        });
+        create = ui.button("Create").clicked();
+        if let Some(error) = &form.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    });
+    if !create {
+        return;
+    }
+
+    match form.save_directory(Path::new(SAVES_DIRECTORY_NAME)) {
+        Ok(save_directory) => {
+            // a new world has no metadata to load, so these are kept
+            commands.insert_resource(save_directory);
+            commands.insert_resource(WorldSeed(form.seed()));
+            commands.insert_resource(WorldTerrain(form.terrain));
+            form.error = None;
+            next_state.set(AppState::Game);
+        }
+        Err(error) => form.error = Some(error),
+    }
+}
@@ -0,0 +1,155 @@
+mod systems;
+
+pub mod resources;
+
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+
+use crate::{game::world::resources::VoxelWorld, AppState};
+
+use self::{resources::*, systems::*};
+
+/// Toggles the debug overlay
+pub const DEBUG_OVERLAY_KEY: KeyCode = KeyCode::F3;
+/// How far the overlay looks for the block the camera is pointed at, in voxels
+pub const TARGET_REACH_IN_VOXELS: f32 = 64.0;
+/// How much of each new frame time goes into the smoothed one, lower is smoother
+pub const FRAME_TIME_SMOOTHING: f64 = 0.1;
+
+/// F3 overlay with frame times, where the camera is and what the world costs to draw
+pub struct DebugOverlayPlugin;
+
+impl Plugin for DebugOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.init_resource::<DebugOverlay>()
+            .init_resource::<DebugStats>()
+            .add_systems(Update, toggle_debug_overlay)
+            .add_systems(
+                Update,
+                (
+                    collect_frame_stats,
+                    collect_camera_stats,
+                    collect_world_stats.run_if(resource_exists::<VoxelWorld>()),
+                    debug_overlay_ui,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Game).and_then(overlay_visible)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        ecs::system::RunSystemOnce,
+        render::{mesh::Indices, render_resource::PrimitiveTopology},
+        time::TimeUpdateStrategy,
+    };
+
+    use super::*;
+    use crate::game::world::{
+        access::{VoxelAccess, WorldVoxels},
+        components::{BlockType, Chunk, TransparentChunkMesh},
+        resources::NeighbourUpdates,
+        VOXEL_SIZE,
+    };
+
+    /// A triangle of three positions, drawn with `indices`
+    fn triangle(indices: Indices) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+        mesh.set_indices(Some(indices));
+        mesh
+    }
+
+    /// The stat systems without the window, a 3x10x3 world with stone at 1 3 1 and a camera above it looking down
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                10,
+            )))
+            .insert_resource(DebugOverlay { visible: true })
+            .init_resource::<DebugStats>()
+            .init_resource::<NeighbourUpdates>()
+            .init_resource::<Assets<Mesh>>()
+            .add_systems(
+                Update,
+                (
+                    collect_frame_stats,
+                    collect_camera_stats,
+                    collect_world_stats.run_if(resource_exists::<VoxelWorld>()),
+                )
+                    .chain()
+                    .run_if(overlay_visible),
+            );
+        VoxelWorld::spawn_test_world(&mut app.world, IVec3::new(3, 10, 3));
+        app.world.run_system_once(|mut voxels: WorldVoxels| {
+            voxels.apply_change(IVec3::new(1, 3, 1), Some((BlockType::Stone, 0)));
+        });
+        let camera = Transform::from_translation(Vec3::new(1.0, 8.0, 1.0) * VOXEL_SIZE)
+            .looking_at(Vec3::new(1.0, 0.0, 1.0) * VOXEL_SIZE, Vec3::Z);
+        app.world
+            .spawn((Camera3d::default(), GlobalTransform::from(camera)));
+        app
+    }
+
+    #[test]
+    fn stats_are_collected_without_a_window() {
+        let mut app = app();
+        let mut meshes = app.world.resource_mut::<Assets<Mesh>>();
+        let opaque = meshes.add(triangle(Indices::U32(vec![0, 1, 2])));
+        let transparent = meshes.add(triangle(Indices::U16(vec![0, 1, 2])));
+        let chunk = app.world.resource::<VoxelWorld>().chunks[&IVec3::ZERO].entity_id;
+        app.world
+            .entity_mut(chunk)
+            .insert((Chunk { updated: true }, opaque))
+            .with_children(|parent| {
+                parent.spawn((TransparentChunkMesh, transparent));
+            });
+
+        // the first update has no time pass
+        app.update();
+        app.update();
+        app.update();
+        let stats = app.world.resource::<DebugStats>().clone();
+        assert!((stats.frame_time_seconds - 0.01).abs() < 1e-6);
+        assert!((stats.fps() - 100.0).abs() < 1e-3);
+        assert_eq!(stats.camera_position, Some(Vec3::new(0.1, 0.8, 0.1)));
+        assert_eq!(
+            stats.camera_voxel_coordinates(),
+            Some((IVec3::new(1, 8, 1), IVec3::ZERO, IVec3::new(1, 8, 1)))
+        );
+        let target = stats.targeted_block.unwrap();
+        assert_eq!(target.position, IVec3::new(1, 3, 1));
+        assert_eq!((target.block, target.state), (BlockType::Stone, 0));
+        assert_eq!(stats.loaded_chunks, 1);
+        assert_eq!(stats.voxel_entities, 3 * 10 * 3);
+        assert_eq!(stats.pending_mesh_jobs, 1);
+        assert_eq!(stats.vertex_count, 6);
+        // three positions of three floats each, then the indices
+        assert_eq!(stats.mesh_bytes, 36 + 12 + 36 + 6);
+        assert_eq!(stats.lines().len(), 8);
+    }
+
+    #[test]
+    fn stats_are_only_collected_while_the_overlay_is_visible() {
+        let mut app = app();
+        app.world.resource_mut::<DebugOverlay>().visible = false;
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<DebugStats>(), &DebugStats::default());
+        assert_eq!(
+            DebugStats::default().lines()[1..3],
+            ["Position: no camera", "Targeted: nothing"]
+        );
+    }
+}
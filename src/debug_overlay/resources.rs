@@ -0,0 +1,135 @@
+use std::mem::size_of;
+
+use bevy::prelude::*;
+
+use crate::game::world::{
+    components::{BlockType, ChunkCoordinate, Voxel, WorldCoordinate},
+    raycast::voxel_at_point,
+    to_chunk_space,
+};
+
+/// Whether the debug overlay is shown, stats are only collected while it is
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Default)]
+pub struct DebugOverlay {
+    pub visible: bool,
+}
+
+/// The block the camera is pointed at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetedBlock {
+    /// world voxel position
+    pub position: IVec3,
+    pub block: BlockType,
+    pub state: u8,
+    pub sky_light: u8,
+    pub block_light: u8,
+}
+
+/// Everything the debug overlay shows, kept up to date by systems so it can be read without a window
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct DebugStats {
+    /// smoothed over the last few frames
+    pub frame_time_seconds: f64,
+    /// in world units
+    pub camera_position: Option<Vec3>,
+    pub targeted_block: Option<TargetedBlock>,
+    pub loaded_chunks: usize,
+    /// chunks waiting to have their masks and meshes rebuilt
+    pub pending_mesh_jobs: usize,
+    pub voxel_entities: usize,
+    /// of every chunk mesh, opaque and transparent
+    pub vertex_count: usize,
+    pub mesh_bytes: usize,
+}
+
+impl DebugStats {
+    pub fn fps(&self) -> f64 {
+        if self.frame_time_seconds > 0.0 {
+            1.0 / self.frame_time_seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// Rough size of the voxel entities, their components and a bit of bookkeeping each
+    pub fn voxel_bytes(&self) -> usize {
+        let per_voxel = size_of::<Voxel>()
+            + size_of::<ChunkCoordinate>()
+            + size_of::<WorldCoordinate>()
+            + size_of::<Name>()
+            + size_of::<Entity>() * 2
+            + size_of::<IVec3>();
+        self.voxel_entities * per_voxel
+    }
+
+    /// The camera position in world voxels, then split into its chunk and the voxel inside that chunk
+    pub fn camera_voxel_coordinates(&self) -> Option<(IVec3, IVec3, IVec3)> {
+        let voxel = voxel_at_point(self.camera_position?);
+        let (chunk, local) = to_chunk_space(voxel);
+        Some((voxel, chunk, local))
+    }
+
+    /// The overlay text, one stat per line
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!(
+            "{:.0} fps ({:.2} ms)",
+            self.fps(),
+            self.frame_time_seconds * 1000.0
+        )];
+        match (self.camera_position, self.camera_voxel_coordinates()) {
+            (Some(position), Some((voxel, chunk, local))) => {
+                lines.push(format!(
+                    "Position: {:.2} {:.2} {:.2}",
+                    position.x, position.y, position.z
+                ));
+                lines.push(format!("Voxel: {} {} {}", voxel.x, voxel.y, voxel.z));
+                lines.push(format!(
+                    "Chunk: {} {} {} at {} {} {}",
+                    chunk.x, chunk.y, chunk.z, local.x, local.y, local.z
+                ));
+            }
+            _ => lines.push("Position: no camera".to_string()),
+        }
+        match &self.targeted_block {
+            Some(target) => lines.push(format!(
+                "Targeted: {} (state {}) at {} {} {}, sky light {}, block light {}",
+                target.block.name(),
+                target.state,
+                target.position.x,
+                target.position.y,
+                target.position.z,
+                target.sky_light,
+                target.block_light
+            )),
+            None => lines.push("Targeted: nothing".to_string()),
+        }
+        lines.push(format!(
+            "Chunks: {} loaded, {} waiting to mesh",
+            self.loaded_chunks, self.pending_mesh_jobs
+        ));
+        lines.push(format!("Vertices: {}", self.vertex_count));
+        lines.push(format!(
+            "Memory: {} for meshes, {} for {} voxels",
+            format_bytes(self.mesh_bytes),
+            format_bytes(self.voxel_bytes()),
+            self.voxel_entities
+        ));
+        lines
+    }
+}
+
+/// A byte count in the largest unit that keeps it above one
+pub fn format_bytes(bytes: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -0,0 +1,122 @@
+use bevy::{prelude::*, render::mesh::Indices};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::game::world::{
+    components::{Chunk, TransparentChunkMesh, Voxel},
+    raycast::raycast,
+    resources::VoxelWorld,
+};
+
+use super::{
+    resources::{DebugOverlay, DebugStats, TargetedBlock},
+    DEBUG_OVERLAY_KEY, FRAME_TIME_SMOOTHING, TARGET_REACH_IN_VOXELS,
+};
+
+type ChunkMeshes = Or<(With<Chunk>, With<TransparentChunkMesh>)>;
+
+pub fn overlay_visible(overlay: Res<DebugOverlay>) -> bool {
+    overlay.visible
+}
+
+pub fn toggle_debug_overlay(keys: Res<Input<KeyCode>>, mut overlay: ResMut<DebugOverlay>) {
+    if keys.just_pressed(DEBUG_OVERLAY_KEY) {
+        overlay.visible = !overlay.visible;
+    }
+}
+
+pub fn collect_frame_stats(time: Res<Time<Real>>, mut stats: ResMut<DebugStats>) {
+    let frame_time = time.delta_seconds_f64();
+    stats.frame_time_seconds = if stats.frame_time_seconds > 0.0 {
+        stats.frame_time_seconds + (frame_time - stats.frame_time_seconds) * FRAME_TIME_SMOOTHING
+    } else {
+        frame_time
+    };
+}
+
+/// Finds where the camera is and the block it is pointed at
+pub fn collect_camera_stats(
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    voxel_world: Option<Res<VoxelWorld>>,
+    voxel_query: Query<&Voxel>,
+    mut stats: ResMut<DebugStats>,
+) {
+    let Ok(camera_transform) = camera_query.get_single() else {
+        stats.camera_position = None;
+        stats.targeted_block = None;
+        return;
+    };
+    stats.camera_position = Some(camera_transform.translation());
+    let voxel_at = |position: IVec3| {
+        voxel_world
+            .as_ref()?
+            .voxel_entity(position)
+            .and_then(|entity| voxel_query.get(entity).ok())
+    };
+    stats.targeted_block = raycast(
+        camera_transform.translation(),
+        camera_transform.forward(),
+        TARGET_REACH_IN_VOXELS,
+        |position| voxel_at(position).is_some_and(|voxel| voxel.solid),
+    )
+    .and_then(|hit| {
+        let voxel = voxel_at(hit.position)?;
+        Some(TargetedBlock {
+            position: hit.position,
+            block: voxel.block,
+            state: voxel.state,
+            sky_light: voxel.sky_light,
+            block_light: voxel.block_light,
+        })
+    });
+}
+
+/// Counts the chunks, voxels and chunk mesh vertices, meshes are only there when rendering
+pub fn collect_world_stats(
+    voxel_world: Res<VoxelWorld>,
+    chunk_query: Query<&Chunk>,
+    chunk_mesh_query: Query<&Handle<Mesh>, ChunkMeshes>,
+    meshes: Option<Res<Assets<Mesh>>>,
+    mut stats: ResMut<DebugStats>,
+) {
+    stats.loaded_chunks = voxel_world.chunks.len();
+    stats.voxel_entities = voxel_world
+        .chunks
+        .values()
+        .map(|chunk| chunk.blocks.len())
+        .sum();
+    stats.pending_mesh_jobs = chunk_query.iter().filter(|chunk| chunk.updated).count();
+    stats.vertex_count = 0;
+    stats.mesh_bytes = 0;
+    let Some(meshes) = meshes else {
+        return;
+    };
+    for mesh in chunk_mesh_query
+        .iter()
+        .filter_map(|handle| meshes.get(handle))
+    {
+        let vertex_count = mesh.count_vertices();
+        let index_bytes = mesh.indices().map_or(0, |indices| match indices {
+            Indices::U16(indices) => indices.len() * 2,
+            Indices::U32(indices) => indices.len() * 4,
+        });
+        stats.vertex_count += vertex_count;
+        let vertex_bytes: usize = mesh
+            .attributes()
+            .map(|(_, values)| values.get_bytes().len())
+            .sum();
+        stats.mesh_bytes += vertex_bytes + index_bytes;
+    }
+}
+
+pub fn debug_overlay_ui(mut contexts: EguiContexts, stats: Res<DebugStats>) {
+    egui::Area::new("Debug Overlay")
+        .anchor(egui::Align2::LEFT_TOP, [8.0, 8.0])
+        .interactable(false)
+        .show(contexts.ctx_mut(), |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for line in stats.lines() {
+                    ui.monospace(line);
+                }
+            });
+        });
+}
@@ -1,2 +1,77 @@
-use bevy::prelude::Event;
-// TODO add global events here
\ No newline at end of file
+use std::path::PathBuf;
+
+use bevy::prelude::{Event, IVec3, Vec3};
+
+use crate::game::{
+    commands::parser::{Command, PermissionLevel},
+    structures::schematic::SchematicTransform,
+    world::components::BlockType,
+};
+
+/// Request to change a single voxel, `block: None` clears it to air.
+/// `position` is in world voxel space (chunk position * chunk size + local position).
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SetBlockEvent {
+    pub position: IVec3,
+    pub block: Option<BlockType>,
+    /// block specific state, like a crop's age
+    pub state: u8,
+}
+
+/// Request to save the voxels between `min` and `max` (both inclusive, world voxel space) as a schematic file,
+/// or as a MagicaVoxel model when `path` ends in `.vox`
+#[derive(Event, Debug, Clone)]
+pub struct CopySchematicEvent {
+    pub min: IVec3,
+    pub max: IVec3,
+    pub path: PathBuf,
+}
+
+/// Request to paste a schematic file or MagicaVoxel model with its lowest corner at `origin`.
+/// Air in the schematic only clears the world when `include_air` is set.
+#[derive(Event, Debug, Clone)]
+pub struct PasteSchematicEvent {
+    pub path: PathBuf,
+    pub origin: IVec3,
+    pub transform: SchematicTransform,
+    pub include_air: bool,
+}
+
+/// Request to export the meshes of every loaded chunk to an OBJ file at `path`,
+/// with its materials in an MTL file next to it
+#[derive(Event, Debug, Clone)]
+pub struct ExportMeshEvent {
+    pub path: PathBuf,
+}
+
+/// Who ran a command, and who hears back about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandSender {
+    /// the headless server's stdin
+    Console,
+    /// the player of a single player game
+    LocalPlayer,
+    /// a player on a server, by player id
+    Player(u32),
+}
+
+/// A parsed command to run, already checked against the sender's permission level.
+/// `origin` is where the sender is in world units, the console is nowhere.
+#[derive(Event, Debug, Clone)]
+pub struct CommandEvent {
+    pub sender: CommandSender,
+    pub permission: PermissionLevel,
+    pub origin: Option<Vec3>,
+    pub command: Command,
+}
+
+/// What a command has to say to whoever ran it
+#[derive(Event, Debug, Clone)]
+pub struct CommandFeedbackEvent {
+    pub recipient: CommandSender,
+    pub text: String,
+}
+
+/// Request to save the world now rather than when the game is paused or closed
+#[derive(Event, Debug, Clone, Copy)]
+pub struct SaveWorldEvent;
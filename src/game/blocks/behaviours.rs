@@ -0,0 +1,270 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng};
+
+use crate::game::world::{
+    access::{BlockChange, VoxelAccess},
+    components::{BlockType, CROP_MAX_AGE},
+};
+
+/// How a block type reacts to the world simulation.
+/// Behaviours only look at the world and return the changes they want made,
+/// so the same world and random numbers always give the same changes.
+pub trait BlockBehaviour: Send + Sync {
+    /// Called for a block picked by the random tick scheduler
+    fn random_tick(
+        &self,
+        _world: &dyn VoxelAccess,
+        _position: IVec3,
+        _rng: &mut StdRng,
+    ) -> Vec<(IVec3, BlockChange)> {
+        vec![]
+    }
+
+    /// Called when a voxel next to this block changed,
+    /// returns in how many world ticks the block wants a scheduled update
+    fn neighbour_changed(&self, _world: &dyn VoxelAccess, _position: IVec3) -> Option<u64> {
+        None
+    }
+
+    /// Called when an update scheduled for this block is due
+    fn scheduled_update(
+        &self,
+        _world: &dyn VoxelAccess,
+        _position: IVec3,
+        _rng: &mut StdRng,
+    ) -> Vec<(IVec3, BlockChange)> {
+        vec![]
+    }
+}
+
+/// Light a crop needs on the voxel above it to grow
+pub const CROP_MIN_GROWTH_LIGHT: u8 = 9;
+/// How far leaves look for a log holding them up before they decay
+pub const LEAVES_SUPPORT_DISTANCE: i32 = 4;
+
+fn is_covered(world: &dyn VoxelAccess, position: IVec3) -> bool {
+    world
+        .voxel(position + IVec3::Y)
+        .is_some_and(|voxel| !voxel.is_transparent())
+}
+
+/// Grass dies when covered and spreads onto nearby uncovered dirt
+pub struct GrassBehaviour;
+
+impl BlockBehaviour for GrassBehaviour {
+    fn random_tick(
+        &self,
+        world: &dyn VoxelAccess,
+        position: IVec3,
+        rng: &mut StdRng,
+    ) -> Vec<(IVec3, BlockChange)> {
+        if is_covered(world, position) {
+            return vec![(position, Some((BlockType::Dirt, 0)))];
+        }
+        let target = position
+            + IVec3::new(
+                rng.gen_range(-1..=1),
+                rng.gen_range(-3..=1),
+                rng.gen_range(-1..=1),
+            );
+        let is_dirt = world
+            .voxel(target)
+            .is_some_and(|voxel| voxel.solid && voxel.block == BlockType::Dirt);
+        if is_dirt && !is_covered(world, target) {
+            vec![(target, Some((BlockType::Grass, 0)))]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Crops age one step at a time while they get enough light
+pub struct CropBehaviour;
+
+impl BlockBehaviour for CropBehaviour {
+    fn random_tick(
+        &self,
+        world: &dyn VoxelAccess,
+        position: IVec3,
+        _rng: &mut StdRng,
+    ) -> Vec<(IVec3, BlockChange)> {
+        let Some(voxel) = world.voxel(position) else {
+            return vec![];
+        };
+        let light = world
+            .voxel(position + IVec3::Y)
+            .map_or(0, |above| above.light());
+        if voxel.state < CROP_MAX_AGE && light >= CROP_MIN_GROWTH_LIGHT {
+            vec![(position, Some((BlockType::Crop, voxel.state + 1)))]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// Leaves without a log nearby decay into air
+pub struct LeavesBehaviour;
+
+impl BlockBehaviour for LeavesBehaviour {
+    fn random_tick(
+        &self,
+        world: &dyn VoxelAccess,
+        position: IVec3,
+        _rng: &mut StdRng,
+    ) -> Vec<(IVec3, BlockChange)> {
+        let range = -LEAVES_SUPPORT_DISTANCE..=LEAVES_SUPPORT_DISTANCE;
+        for x in range.clone() {
+            for y in range.clone() {
+                for z in range.clone() {
+                    let offset = IVec3::new(x, y, z);
+                    if (x.abs() + y.abs() + z.abs()) > LEAVES_SUPPORT_DISTANCE {
+                        continue;
+                    }
+                    if world
+                        .voxel(position + offset)
+                        .is_some_and(|voxel| voxel.solid && voxel.block == BlockType::Log)
+                    {
+                        return vec![];
+                    }
+                }
+            }
+        }
+        vec![(position, None)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::game::{
+        blocks::resources::BlockBehaviours,
+        world::{components::Voxel, lighting::light_voxels},
+    };
+
+    const SIZE: IVec3 = IVec3::new(9, 6, 9);
+
+    /// Two layers of dirt with lit air above them
+    fn field() -> HashMap<IVec3, Voxel> {
+        let mut world = HashMap::new();
+        for x in 0..SIZE.x {
+            for y in 0..SIZE.y {
+                for z in 0..SIZE.z {
+                    let voxel = Voxel {
+                        solid: y < 2,
+                        block: BlockType::Dirt,
+                        ..default()
+                    };
+                    world.insert(IVec3::new(x, y, z), voxel);
+                }
+            }
+        }
+        let positions: Vec<IVec3> = world.keys().copied().collect();
+        light_voxels(&mut world, positions.into_iter());
+        world
+    }
+
+    fn block_at(world: &HashMap<IVec3, Voxel>, position: IVec3) -> BlockChange {
+        world[&position].block_change()
+    }
+
+    /// Random ticks `ticks` voxels of the world the way the random tick system does
+    fn random_ticks(world: &mut HashMap<IVec3, Voxel>, seed: u64, ticks: usize) {
+        let behaviours = BlockBehaviours::with_defaults();
+        let mut rng = StdRng::seed_from_u64(seed);
+        for _ in 0..ticks {
+            let position = IVec3::new(
+                rng.gen_range(0..SIZE.x),
+                rng.gen_range(0..SIZE.y),
+                rng.gen_range(0..SIZE.z),
+            );
+            let Some(behaviour) = world
+                .voxel(position)
+                .filter(|voxel| voxel.solid)
+                .and_then(|voxel| behaviours.get(voxel.block))
+            else {
+                continue;
+            };
+            for (position, change) in behaviour.random_tick(world, position, &mut rng) {
+                world.apply_change(position, change);
+            }
+        }
+    }
+
+    fn grass_positions(world: &HashMap<IVec3, Voxel>) -> Vec<IVec3> {
+        let mut positions: Vec<IVec3> = world
+            .iter()
+            .filter(|(_, voxel)| voxel.solid && voxel.block == BlockType::Grass)
+            .map(|(position, _)| *position)
+            .collect();
+        positions.sort_by_key(|position| position.to_array());
+        positions
+    }
+
+    #[test]
+    fn same_seed_spreads_grass_the_same_way() {
+        let spread = |seed| {
+            let mut world = field();
+            world.apply_change(IVec3::new(4, 1, 4), Some((BlockType::Grass, 0)));
+            random_ticks(&mut world, seed, 3000);
+            grass_positions(&world)
+        };
+        let grass = spread(7);
+        assert!(grass.len() > 1);
+        assert!(grass.iter().all(|position| position.y == 1));
+        assert_eq!(grass, spread(7));
+    }
+
+    #[test]
+    fn covered_grass_turns_to_dirt() {
+        let mut world = field();
+        let grass = IVec3::new(4, 1, 4);
+        world.apply_change(grass, Some((BlockType::Grass, 0)));
+        world.apply_change(grass + IVec3::Y, Some((BlockType::Stone, 0)));
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            GrassBehaviour.random_tick(&world, grass, &mut rng),
+            vec![(grass, Some((BlockType::Dirt, 0)))]
+        );
+    }
+
+    #[test]
+    fn crops_grow_only_in_light() {
+        let mut world = field();
+        let crop = IVec3::new(4, 2, 4);
+        world.apply_change(crop, Some((BlockType::Crop, 0)));
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            CropBehaviour.random_tick(&world, crop, &mut rng),
+            vec![(crop, Some((BlockType::Crop, 1)))]
+        );
+
+        world.apply_change(crop, Some((BlockType::Crop, CROP_MAX_AGE)));
+        assert!(CropBehaviour.random_tick(&world, crop, &mut rng).is_empty());
+
+        world.apply_change(crop, Some((BlockType::Crop, 0)));
+        world.apply_change(crop + IVec3::Y, Some((BlockType::Stone, 0)));
+        assert!(CropBehaviour.random_tick(&world, crop, &mut rng).is_empty());
+        assert_eq!(block_at(&world, crop), Some((BlockType::Crop, 0)));
+    }
+
+    #[test]
+    fn leaves_decay_only_away_from_logs() {
+        let mut world = field();
+        let leaves = IVec3::new(4, 4, 4);
+        world.apply_change(leaves, Some((BlockType::Leaves, 0)));
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(
+            LeavesBehaviour.random_tick(&world, leaves, &mut rng),
+            vec![(leaves, None)]
+        );
+
+        let log = leaves + IVec3::new(LEAVES_SUPPORT_DISTANCE, 0, 0);
+        world.apply_change(log, Some((BlockType::Log, 0)));
+        assert!(LeavesBehaviour
+            .random_tick(&world, leaves, &mut rng)
+            .is_empty());
+    }
+}
@@ -0,0 +1,29 @@
+mod systems;
+
+pub mod behaviours;
+pub mod resources;
+
+use bevy::prelude::*;
+
+use self::{resources::*, systems::*};
+
+use super::tick::WorldTickSet;
+
+/// Voxels picked at random in each chunk every world tick
+pub const RANDOM_TICKS_PER_CHUNK: u32 = 3;
+
+pub struct BlocksPlugin;
+
+impl Plugin for BlocksPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(BlockBehaviours::with_defaults())
+            .init_resource::<RandomTickSettings>()
+            .init_resource::<BlockUpdateQueue>()
+            .add_systems(
+                FixedUpdate,
+                (notify_neighbours, run_scheduled_updates, random_tick_blocks)
+                    .chain()
+                    .in_set(WorldTickSet::Blocks),
+            );
+    }
+}
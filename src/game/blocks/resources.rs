@@ -0,0 +1,220 @@
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::game::world::{components::BlockType, to_chunk_space, CHUNK_SIZE};
+
+use super::{
+    behaviours::{BlockBehaviour, CropBehaviour, GrassBehaviour, LeavesBehaviour},
+    RANDOM_TICKS_PER_CHUNK,
+};
+
+/// Behaviour of each block type that does more than sit there
+#[derive(Resource, Default)]
+pub struct BlockBehaviours(HashMap<BlockType, Box<dyn BlockBehaviour>>);
+
+impl BlockBehaviours {
+    pub fn register(&mut self, block: BlockType, behaviour: impl BlockBehaviour + 'static) {
+        self.0.insert(block, Box::new(behaviour));
+    }
+
+    pub fn get(&self, block: BlockType) -> Option<&dyn BlockBehaviour> {
+        self.0.get(&block).map(|behaviour| behaviour.as_ref())
+    }
+
+    /// The behaviours of the built in blocks
+    pub fn with_defaults() -> Self {
+        let mut behaviours = BlockBehaviours::default();
+        behaviours.register(BlockType::Grass, GrassBehaviour);
+        behaviours.register(BlockType::Crop, CropBehaviour);
+        behaviours.register(BlockType::Leaves, LeavesBehaviour);
+        behaviours
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct RandomTickSettings {
+    /// voxels picked in every loaded chunk each world tick
+    pub ticks_per_chunk: u32,
+}
+
+impl Default for RandomTickSettings {
+    fn default() -> Self {
+        RandomTickSettings {
+            ticks_per_chunk: RANDOM_TICKS_PER_CHUNK,
+        }
+    }
+}
+
+/// A block update due on a world tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledUpdate {
+    pub tick: u64,
+    /// world voxel position of the block to update
+    pub position: IVec3,
+}
+
+impl Ord for ScheduledUpdate {
+    /// Earliest tick first, updates due on the same tick go in position order
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.tick, self.position.to_array()).cmp(&(other.tick, other.position.to_array()))
+    }
+}
+
+impl PartialOrd for ScheduledUpdate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Updates scheduled for the blocks of one chunk, kept per chunk so they can be saved with it
+#[derive(Debug, Clone, Default)]
+pub struct ChunkUpdateQueue {
+    updates: BinaryHeap<Reverse<ScheduledUpdate>>,
+    /// positions with an update waiting, a block is only ever scheduled once at a time
+    scheduled: HashSet<IVec3>,
+}
+
+impl ChunkUpdateQueue {
+    /// Returns false when the block already has an update waiting
+    pub fn schedule(&mut self, update: ScheduledUpdate) -> bool {
+        if !self.scheduled.insert(update.position) {
+            return false;
+        }
+        self.updates.push(Reverse(update));
+        true
+    }
+
+    /// Removes and returns every update due on or before `tick`, earliest first
+    pub fn drain_due(&mut self, tick: u64) -> Vec<ScheduledUpdate> {
+        let mut due = vec![];
+        while let Some(Reverse(update)) = self.updates.peek().copied() {
+            if update.tick > tick {
+                break;
+            }
+            self.updates.pop();
+            self.scheduled.remove(&update.position);
+            due.push(update);
+        }
+        due
+    }
+
+    /// Every waiting update, earliest first
+    pub fn updates(&self) -> Vec<ScheduledUpdate> {
+        let mut updates: Vec<_> = self.updates.iter().map(|Reverse(update)| *update).collect();
+        updates.sort();
+        updates
+    }
+}
+
+/// Block updates scheduled for later world ticks, by chunk position
+#[derive(Resource, Debug, Default)]
+pub struct BlockUpdateQueue {
+    pub chunks: HashMap<IVec3, ChunkUpdateQueue>,
+}
+
+impl BlockUpdateQueue {
+    /// Schedules an update of the block at `position` on world tick `tick`,
+    /// returns false when the block already has an update waiting
+    pub fn schedule(&mut self, position: IVec3, tick: u64) -> bool {
+        let (chunk_position, _) = to_chunk_space(position);
+        self.chunks
+            .entry(chunk_position)
+            .or_default()
+            .schedule(ScheduledUpdate { tick, position })
+    }
+
+    /// Removes and returns the updates due on or before `tick` in every chunk, earliest first
+    pub fn drain_due(&mut self, tick: u64) -> Vec<ScheduledUpdate> {
+        let mut due: Vec<_> = self
+            .chunks
+            .values_mut()
+            .flat_map(|chunk_queue| chunk_queue.drain_due(tick))
+            .collect();
+        due.sort();
+        due
+    }
+
+    /// Updates waiting in a chunk as ticks from `now` and positions inside the chunk,
+    /// the form they are saved in
+    pub fn chunk_updates(&self, chunk_position: IVec3, now: u64) -> Vec<(u64, IVec3)> {
+        self.chunks
+            .get(&chunk_position)
+            .map(|chunk_queue| {
+                chunk_queue
+                    .updates()
+                    .into_iter()
+                    .map(|update| {
+                        (
+                            update.tick.saturating_sub(now),
+                            update.position - chunk_position * CHUNK_SIZE,
+                        )
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates_come_out_by_tick_then_position() {
+        let mut queue = BlockUpdateQueue::default();
+        // spread over two chunks
+        queue.schedule(IVec3::new(20, 0, 0), 5);
+        queue.schedule(IVec3::new(3, 0, 0), 7);
+        queue.schedule(IVec3::new(2, 0, 0), 7);
+        queue.schedule(IVec3::new(1, 0, 0), 9);
+        let due: Vec<(u64, IVec3)> = queue
+            .drain_due(7)
+            .into_iter()
+            .map(|update| (update.tick, update.position))
+            .collect();
+        assert_eq!(
+            due,
+            vec![
+                (5, IVec3::new(20, 0, 0)),
+                (7, IVec3::new(2, 0, 0)),
+                (7, IVec3::new(3, 0, 0)),
+            ]
+        );
+        assert!(queue.drain_due(8).is_empty());
+        assert_eq!(queue.drain_due(9).len(), 1);
+    }
+
+    #[test]
+    fn a_block_is_only_scheduled_once_at_a_time() {
+        let mut queue = BlockUpdateQueue::default();
+        let position = IVec3::new(4, 4, 4);
+        assert!(queue.schedule(position, 3));
+        assert!(!queue.schedule(position, 1));
+        assert_eq!(queue.drain_due(3).len(), 1);
+        assert!(queue.schedule(position, 6));
+    }
+
+    #[test]
+    fn chunk_updates_are_relative_to_now_and_the_chunk() {
+        let mut queue = BlockUpdateQueue::default();
+        let chunk_position = IVec3::new(1, 0, 2);
+        let origin = chunk_position * CHUNK_SIZE;
+        queue.schedule(origin + IVec3::new(1, 2, 3), 14);
+        queue.schedule(origin + IVec3::new(0, 0, 1), 12);
+        // already overdue updates run straight away after loading
+        queue.schedule(origin, 8);
+        queue.schedule(IVec3::ZERO, 11);
+        assert_eq!(
+            queue.chunk_updates(chunk_position, 10),
+            vec![
+                (0, IVec3::ZERO),
+                (2, IVec3::new(0, 0, 1)),
+                (4, IVec3::new(1, 2, 3)),
+            ]
+        );
+    }
+}
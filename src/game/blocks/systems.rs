@@ -0,0 +1,171 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::game::{
+    tick::resources::{WorldRng, WorldTick},
+    world::{
+        access::{VoxelAccess, WorldVoxels},
+        CHUNK_SIZE,
+    },
+};
+
+use super::resources::{BlockBehaviours, BlockUpdateQueue, RandomTickSettings};
+
+/// Tells the blocks next to every changed voxel about it, they may schedule an update in return
+pub fn notify_neighbours(
+    voxels: WorldVoxels,
+    behaviours: Res<BlockBehaviours>,
+    world_tick: Res<WorldTick>,
+    mut update_queue: ResMut<BlockUpdateQueue>,
+) {
+    for &position in voxels.neighbour_updates() {
+        let Some(behaviour) = voxels
+            .voxel(position)
+            .filter(|voxel| voxel.solid)
+            .and_then(|voxel| behaviours.get(voxel.block))
+        else {
+            continue;
+        };
+        if let Some(delay) = behaviour.neighbour_changed(&voxels, position) {
+            update_queue.schedule(position, world_tick.0 + delay);
+        }
+    }
+}
+
+/// Runs every scheduled block update that is due, in tick then position order
+pub fn run_scheduled_updates(
+    mut voxels: WorldVoxels,
+    behaviours: Res<BlockBehaviours>,
+    world_tick: Res<WorldTick>,
+    mut update_queue: ResMut<BlockUpdateQueue>,
+    mut rng: ResMut<WorldRng>,
+) {
+    for update in update_queue.drain_due(world_tick.0) {
+        // the block may have changed since the update was scheduled
+        let Some(behaviour) = voxels
+            .voxel(update.position)
+            .filter(|voxel| voxel.solid)
+            .and_then(|voxel| behaviours.get(voxel.block))
+        else {
+            continue;
+        };
+        for (position, change) in behaviour.scheduled_update(&voxels, update.position, &mut rng.0) {
+            voxels.apply_change(position, change);
+        }
+    }
+    voxels.update_touched_chunks();
+}
+
+/// Picks `ticks_per_chunk` random voxels in every loaded chunk and lets their block behaviour act
+pub fn random_tick_blocks(
+    mut voxels: WorldVoxels,
+    behaviours: Res<BlockBehaviours>,
+    settings: Res<RandomTickSettings>,
+    mut rng: ResMut<WorldRng>,
+) {
+    // chunks are visited in a fixed order so the same seed always ticks the same voxels
+    let mut chunk_positions: Vec<IVec3> = voxels.voxel_world.chunks.keys().copied().collect();
+    chunk_positions.sort_by_key(|position| position.to_array());
+
+    for chunk_position in chunk_positions {
+        for _ in 0..settings.ticks_per_chunk {
+            let position = chunk_position * CHUNK_SIZE
+                + IVec3::new(
+                    rng.0.gen_range(0..CHUNK_SIZE.x),
+                    rng.0.gen_range(0..CHUNK_SIZE.y),
+                    rng.0.gen_range(0..CHUNK_SIZE.z),
+                );
+            let Some(behaviour) = voxels
+                .voxel(position)
+                .filter(|voxel| voxel.solid)
+                .and_then(|voxel| behaviours.get(voxel.block))
+            else {
+                continue;
+            };
+            for (position, change) in behaviour.random_tick(&voxels, position, &mut rng.0) {
+                voxels.apply_change(position, change);
+            }
+        }
+    }
+    voxels.update_touched_chunks();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use rand::rngs::StdRng;
+
+    use super::*;
+    use crate::game::{
+        blocks::behaviours::BlockBehaviour,
+        world::{
+            access::BlockChange,
+            components::BlockType,
+            resources::{NeighbourUpdates, VoxelWorld},
+        },
+    };
+
+    /// Waits three ticks after a neighbour changed, then turns into stone
+    struct Hardens;
+
+    impl BlockBehaviour for Hardens {
+        fn neighbour_changed(&self, _world: &dyn VoxelAccess, _position: IVec3) -> Option<u64> {
+            Some(3)
+        }
+
+        fn scheduled_update(
+            &self,
+            _world: &dyn VoxelAccess,
+            position: IVec3,
+            _rng: &mut StdRng,
+        ) -> Vec<(IVec3, BlockChange)> {
+            vec![(position, Some((BlockType::Stone, 0)))]
+        }
+    }
+
+    fn block_at(app: &mut App, position: IVec3) -> BlockChange {
+        app.world.run_system_once(move |voxels: WorldVoxels| {
+            voxels.voxel(position).unwrap().block_change()
+        })
+    }
+
+    fn set_block_at(app: &mut App, position: IVec3, block: BlockChange) {
+        app.world.run_system_once(move |mut voxels: WorldVoxels| {
+            voxels.apply_change(position, block);
+        });
+    }
+
+    #[test]
+    fn changed_neighbours_schedule_an_update() {
+        let mut app = App::new();
+        let mut behaviours = BlockBehaviours::default();
+        behaviours.register(BlockType::Sand, Hardens);
+        app.insert_resource(behaviours)
+            .insert_resource(WorldTick(10))
+            .insert_resource(WorldRng::from_seed(0))
+            .init_resource::<BlockUpdateQueue>()
+            .init_resource::<NeighbourUpdates>();
+        VoxelWorld::spawn_test_world(&mut app.world, IVec3::splat(3));
+
+        let sand = IVec3::new(1, 0, 1);
+        set_block_at(&mut app, sand, Some((BlockType::Sand, 0)));
+        app.world.resource_mut::<NeighbourUpdates>().advance();
+        app.world.run_system_once(notify_neighbours);
+        // sand has no neighbours yet that could have been told about it
+        let queue = app.world.resource::<BlockUpdateQueue>();
+        assert!(queue.chunk_updates(IVec3::ZERO, 10).is_empty());
+
+        set_block_at(&mut app, sand + IVec3::Y, Some((BlockType::Dirt, 0)));
+        app.world.resource_mut::<NeighbourUpdates>().advance();
+        app.world.run_system_once(notify_neighbours);
+        let queue = app.world.resource::<BlockUpdateQueue>();
+        assert_eq!(queue.chunk_updates(IVec3::ZERO, 10), vec![(3, sand)]);
+
+        app.world.resource_mut::<WorldTick>().0 = 12;
+        app.world.run_system_once(run_scheduled_updates);
+        assert_eq!(block_at(&mut app, sand), Some((BlockType::Sand, 0)));
+        app.world.resource_mut::<WorldTick>().0 = 13;
+        app.world.run_system_once(run_scheduled_updates);
+        assert_eq!(block_at(&mut app, sand), Some((BlockType::Stone, 0)));
+    }
+}
@@ -0,0 +1,27 @@
+pub mod parser;
+pub mod resources;
+pub mod systems;
+
+use bevy::prelude::*;
+
+use crate::{
+    events::{CommandEvent, CommandFeedbackEvent},
+    AppState,
+};
+
+use self::{resources::*, systems::*};
+
+/// Most blocks one `/give` drops
+pub const MAX_GIVE_COUNT: u32 = 64;
+
+/// Runs parsed commands against the world, whoever sends them: the chat window, the server console or a player
+pub struct CommandsPlugin;
+
+impl Plugin for CommandsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CommandEvent>()
+            .add_event::<CommandFeedbackEvent>()
+            .init_resource::<GameMode>()
+            .add_systems(Update, run_commands.run_if(in_state(AppState::Game)));
+    }
+}
@@ -0,0 +1,803 @@
+use std::fmt;
+
+use crate::game::{structures::schematic::SchematicTransform, world::components::BlockType};
+
+use super::{resources::GameMode, MAX_GIVE_COUNT};
+
+/// How much a command sender is trusted, each command needs a level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum PermissionLevel {
+    /// any player
+    #[default]
+    Player,
+    /// can change the world and other players
+    Operator,
+    /// can run everything, the server console and single player always are
+    Admin,
+}
+
+impl PermissionLevel {
+    pub const ALL: [PermissionLevel; 3] = [
+        PermissionLevel::Player,
+        PermissionLevel::Operator,
+        PermissionLevel::Admin,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PermissionLevel::Player => "player",
+            PermissionLevel::Operator => "operator",
+            PermissionLevel::Admin => "admin",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        PermissionLevel::ALL
+            .into_iter()
+            .find(|level| level.name() == name)
+    }
+}
+
+/// One axis of a position, relative ones are written with a `~` and added to where the sender is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Coordinate {
+    Absolute(f32),
+    Relative(f32),
+}
+
+impl Coordinate {
+    pub fn resolve(&self, origin: f32) -> f32 {
+        match self {
+            Coordinate::Absolute(value) => *value,
+            Coordinate::Relative(offset) => origin + offset,
+        }
+    }
+}
+
+/// Named times of day `/time set` takes besides a number
+pub const TIME_PRESETS: [(&str, f32); 4] = [
+    ("midnight", 0.0),
+    ("day", 0.3),
+    ("noon", 0.5),
+    ("night", 0.8),
+];
+
+/// A parsed command with its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    /// moves the sender, in voxels
+    Teleport([Coordinate; 3]),
+    /// drops blocks at the sender
+    Give {
+        block: BlockType,
+        count: u32,
+    },
+    /// sets the time of day, 0.0 is midnight and 0.5 is noon
+    SetTime(f32),
+    Seed,
+    Save,
+    SetGameMode(GameMode),
+    /// sets how much a player on a server is trusted
+    SetPermission {
+        name: String,
+        permission: PermissionLevel,
+    },
+    Kick {
+        name: String,
+        reason: String,
+    },
+    Ban {
+        name: String,
+        reason: String,
+    },
+    /// lifts a ban on a name or an address
+    Pardon(String),
+    Whitelist(WhitelistChange),
+    /// saves the voxels between two corners, both included, to a file in the schematics directory
+    CopySchematic {
+        corners: [[Coordinate; 3]; 2],
+        file_name: String,
+    },
+    /// pastes a schematic from the schematics directory with its lowest corner at `origin`
+    PasteSchematic {
+        file_name: String,
+        origin: [Coordinate; 3],
+        transform: SchematicTransform,
+        include_air: bool,
+    },
+    /// writes the meshes of the world to an OBJ file in the exports directory
+    ExportMesh(String),
+    Help,
+}
+
+/// What `/whitelist` does
+#[derive(Debug, Clone, PartialEq)]
+pub enum WhitelistChange {
+    Enable,
+    Disable,
+    Add(String),
+    Remove(String),
+}
+
+/// The kinds of argument commands take, used for tab completion
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArgumentKind {
+    Coordinate,
+    Block,
+    Count,
+    TimeOfDay,
+    GameMode,
+    PermissionLevel,
+    /// the name of a player, they may not be online
+    Player,
+    /// every word left on the line
+    Text,
+    /// a fixed word, like the `set` in `/time set`
+    Literal(&'static str),
+    /// one of a few fixed words
+    Choice(&'static [&'static str]),
+    /// a file inside a directory the game picks, like the schematics directory
+    FileName,
+}
+
+/// Words `/paste` takes after the position, in any order
+pub const PASTE_OPTIONS: [&str; 6] = ["0", "90", "180", "270", "mirror", "air"];
+
+/// A command as it is typed, with what it needs to run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub arguments: &'static [ArgumentKind],
+    pub permission: PermissionLevel,
+}
+
+pub const COMMANDS: [CommandSpec; 15] = [
+    CommandSpec {
+        name: "tp",
+        usage: "/tp <x> <y> <z>",
+        arguments: &[
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+        ],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "give",
+        usage: "/give <block> [count]",
+        arguments: &[ArgumentKind::Block, ArgumentKind::Count],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "time",
+        usage: "/time set <day|noon|night|midnight|0.0-1.0>",
+        arguments: &[ArgumentKind::Literal("set"), ArgumentKind::TimeOfDay],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "seed",
+        usage: "/seed",
+        arguments: &[],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "save",
+        usage: "/save",
+        arguments: &[],
+        permission: PermissionLevel::Admin,
+    },
+    CommandSpec {
+        name: "gamemode",
+        usage: "/gamemode <survival|creative|spectator>",
+        arguments: &[ArgumentKind::GameMode],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "op",
+        usage: "/op <player> <player|operator|admin>",
+        arguments: &[ArgumentKind::Player, ArgumentKind::PermissionLevel],
+        permission: PermissionLevel::Admin,
+    },
+    CommandSpec {
+        name: "kick",
+        usage: "/kick <player> [reason]",
+        arguments: &[ArgumentKind::Player, ArgumentKind::Text],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "ban",
+        usage: "/ban <player> [reason]",
+        arguments: &[ArgumentKind::Player, ArgumentKind::Text],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "pardon",
+        usage: "/pardon <player|address>",
+        arguments: &[ArgumentKind::Player],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "whitelist",
+        usage: "/whitelist <on|off|add|remove> [player]",
+        arguments: &[
+            ArgumentKind::Choice(&["on", "off", "add", "remove"]),
+            ArgumentKind::Player,
+        ],
+        permission: PermissionLevel::Admin,
+    },
+    CommandSpec {
+        name: "copy",
+        usage: "/copy <x1> <y1> <z1> <x2> <y2> <z2> <file>",
+        arguments: &[
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::FileName,
+        ],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "paste",
+        usage: "/paste <file> <x> <y> <z> [0|90|180|270] [mirror] [air]",
+        arguments: &[
+            ArgumentKind::FileName,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Coordinate,
+            ArgumentKind::Choice(&PASTE_OPTIONS),
+            ArgumentKind::Choice(&PASTE_OPTIONS),
+            ArgumentKind::Choice(&PASTE_OPTIONS),
+        ],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "export",
+        usage: "/export <file>",
+        arguments: &[ArgumentKind::FileName],
+        permission: PermissionLevel::Operator,
+    },
+    CommandSpec {
+        name: "help",
+        usage: "/help",
+        arguments: &[],
+        permission: PermissionLevel::Player,
+    },
+];
+
+impl Command {
+    pub fn spec(&self) -> &'static CommandSpec {
+        let name = match self {
+            Command::Teleport(_) => "tp",
+            Command::Give { .. } => "give",
+            Command::SetTime(_) => "time",
+            Command::Seed => "seed",
+            Command::Save => "save",
+            Command::SetGameMode(_) => "gamemode",
+            Command::SetPermission { .. } => "op",
+            Command::Kick { .. } => "kick",
+            Command::Ban { .. } => "ban",
+            Command::Pardon(_) => "pardon",
+            Command::Whitelist(_) => "whitelist",
+            Command::CopySchematic { .. } => "copy",
+            Command::PasteSchematic { .. } => "paste",
+            Command::ExportMesh(_) => "export",
+            Command::Help => "help",
+        };
+        COMMANDS.iter().find(|spec| spec.name == name).unwrap()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    UnknownCommand(String),
+    MissingArgument { usage: &'static str },
+    InvalidArgument { value: String, usage: &'static str },
+    TooManyArguments { usage: &'static str },
+    PermissionDenied(&'static str),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CommandError::UnknownCommand(name) => {
+                write!(f, "Unknown command {name}, try /help")
+            }
+            CommandError::MissingArgument { usage } => {
+                write!(f, "Missing argument, usage: {usage}")
+            }
+            CommandError::InvalidArgument { value, usage } => {
+                write!(f, "Invalid argument {value}, usage: {usage}")
+            }
+            CommandError::TooManyArguments { usage } => {
+                write!(f, "Too many arguments, usage: {usage}")
+            }
+            CommandError::PermissionDenied(name) => {
+                write!(f, "You do not have permission to use /{name}")
+            }
+        }
+    }
+}
+
+/// Typed reads of the words after a command name
+struct Arguments<'a> {
+    words: std::str::SplitWhitespace<'a>,
+    usage: &'static str,
+}
+
+impl<'a> Arguments<'a> {
+    fn next(&mut self) -> Result<&'a str, CommandError> {
+        self.words
+            .next()
+            .ok_or(CommandError::MissingArgument { usage: self.usage })
+    }
+
+    fn invalid(&self, value: &str) -> CommandError {
+        CommandError::InvalidArgument {
+            value: value.to_string(),
+            usage: self.usage,
+        }
+    }
+
+    fn coordinate(&mut self) -> Result<Coordinate, CommandError> {
+        let word = self.next()?;
+        let coordinate = match word.strip_prefix('~') {
+            Some("") => Some(Coordinate::Relative(0.0)),
+            Some(offset) => offset.parse().ok().map(Coordinate::Relative),
+            None => word.parse().ok().map(Coordinate::Absolute),
+        };
+        coordinate
+            .filter(|coordinate| match coordinate {
+                Coordinate::Absolute(value) | Coordinate::Relative(value) => value.is_finite(),
+            })
+            .ok_or_else(|| self.invalid(word))
+    }
+
+    fn block(&mut self) -> Result<BlockType, CommandError> {
+        let word = self.next()?;
+        BlockType::from_name(word).ok_or_else(|| self.invalid(word))
+    }
+
+    fn optional_count(&mut self, default: u32) -> Result<u32, CommandError> {
+        let Some(word) = self.words.next() else {
+            return Ok(default);
+        };
+        word.parse()
+            .ok()
+            .filter(|count| (1..=MAX_GIVE_COUNT).contains(count))
+            .ok_or_else(|| self.invalid(word))
+    }
+
+    fn time_of_day(&mut self) -> Result<f32, CommandError> {
+        let word = self.next()?;
+        TIME_PRESETS
+            .iter()
+            .find(|(name, _)| *name == word)
+            .map(|(_, time_of_day)| *time_of_day)
+            .or_else(|| word.parse().ok().filter(|time| (0.0..=1.0).contains(time)))
+            .map(|time_of_day: f32| time_of_day.rem_euclid(1.0))
+            .ok_or_else(|| self.invalid(word))
+    }
+
+    fn game_mode(&mut self) -> Result<GameMode, CommandError> {
+        let word = self.next()?;
+        GameMode::from_name(word).ok_or_else(|| self.invalid(word))
+    }
+
+    fn permission(&mut self) -> Result<PermissionLevel, CommandError> {
+        let word = self.next()?;
+        PermissionLevel::from_name(word).ok_or_else(|| self.invalid(word))
+    }
+
+    fn position(&mut self) -> Result<[Coordinate; 3], CommandError> {
+        Ok([self.coordinate()?, self.coordinate()?, self.coordinate()?])
+    }
+
+    /// A bare file name, it can not lead out of the directory it is looked for in
+    fn file_name(&mut self) -> Result<String, CommandError> {
+        let word = self.next()?;
+        if word.starts_with('.') || word.contains(['/', '\\', ':']) {
+            return Err(self.invalid(word));
+        }
+        Ok(word.to_string())
+    }
+
+    fn player(&mut self) -> Result<String, CommandError> {
+        Ok(self.next()?.to_string())
+    }
+
+    fn rest(&mut self) -> String {
+        self.words.by_ref().collect::<Vec<_>>().join(" ")
+    }
+
+    fn literal(&mut self, literal: &str) -> Result<(), CommandError> {
+        let word = self.next()?;
+        if word == literal {
+            Ok(())
+        } else {
+            Err(self.invalid(word))
+        }
+    }
+
+    fn finish(mut self) -> Result<(), CommandError> {
+        match self.words.next() {
+            Some(_) => Err(CommandError::TooManyArguments { usage: self.usage }),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Parses a command line, with or without its leading `/`, and checks the sender may run it
+pub fn parse_command(line: &str, permission: PermissionLevel) -> Result<Command, CommandError> {
+    let line = line.trim();
+    let line = line.strip_prefix('/').unwrap_or(line);
+    let mut words = line.split_whitespace();
+    let name = words.next().unwrap_or_default();
+    let spec = COMMANDS
+        .iter()
+        .find(|spec| spec.name == name)
+        .ok_or_else(|| CommandError::UnknownCommand(name.to_string()))?;
+    if permission < spec.permission {
+        return Err(CommandError::PermissionDenied(spec.name));
+    }
+    let mut arguments = Arguments {
+        words,
+        usage: spec.usage,
+    };
+    let command = match spec.name {
+        "tp" => Command::Teleport(arguments.position()?),
+        "give" => Command::Give {
+            block: arguments.block()?,
+            count: arguments.optional_count(1)?,
+        },
+        "time" => {
+            arguments.literal("set")?;
+            Command::SetTime(arguments.time_of_day()?)
+        }
+        "seed" => Command::Seed,
+        "save" => Command::Save,
+        "gamemode" => Command::SetGameMode(arguments.game_mode()?),
+        "op" => Command::SetPermission {
+            name: arguments.player()?,
+            permission: arguments.permission()?,
+        },
+        "kick" => Command::Kick {
+            name: arguments.player()?,
+            reason: arguments.rest(),
+        },
+        "ban" => Command::Ban {
+            name: arguments.player()?,
+            reason: arguments.rest(),
+        },
+        "pardon" => Command::Pardon(arguments.player()?),
+        "whitelist" => {
+            let word = arguments.next()?;
+            Command::Whitelist(match word {
+                "on" => WhitelistChange::Enable,
+                "off" => WhitelistChange::Disable,
+                "add" => WhitelistChange::Add(arguments.player()?),
+                "remove" => WhitelistChange::Remove(arguments.player()?),
+                _ => return Err(arguments.invalid(word)),
+            })
+        }
+        "copy" => Command::CopySchematic {
+            corners: [arguments.position()?, arguments.position()?],
+            file_name: arguments.file_name()?,
+        },
+        "paste" => {
+            let file_name = arguments.file_name()?;
+            let origin = arguments.position()?;
+            let mut transform = SchematicTransform::default();
+            let mut include_air = false;
+            while let Some(word) = arguments.words.next() {
+                match word {
+                    "mirror" => transform.mirror_x = true,
+                    "air" => include_air = true,
+                    _ => match word.parse::<u16>() {
+                        Ok(degrees) if PASTE_OPTIONS.contains(&word) => {
+                            transform.quarter_turns = (degrees / 90) as u8;
+                        }
+                        _ => return Err(arguments.invalid(word)),
+                    },
+                }
+            }
+            Command::PasteSchematic {
+                file_name,
+                origin,
+                transform,
+                include_air,
+            }
+        }
+        "export" => Command::ExportMesh(arguments.file_name()?),
+        _ => Command::Help,
+    };
+    arguments.finish()?;
+    Ok(command)
+}
+
+/// Words that could go in place of an argument
+fn argument_candidates(kind: ArgumentKind) -> Vec<&'static str> {
+    match kind {
+        ArgumentKind::Coordinate => vec!["~"],
+        ArgumentKind::Block => BlockType::ALL.iter().map(BlockType::name).collect(),
+        ArgumentKind::Count => vec![],
+        ArgumentKind::TimeOfDay => TIME_PRESETS.iter().map(|(name, _)| *name).collect(),
+        ArgumentKind::GameMode => GameMode::ALL.iter().map(GameMode::name).collect(),
+        ArgumentKind::PermissionLevel => PermissionLevel::ALL
+            .iter()
+            .map(PermissionLevel::name)
+            .collect(),
+        ArgumentKind::Player | ArgumentKind::Text | ArgumentKind::FileName => vec![],
+        ArgumentKind::Literal(literal) => vec![literal],
+        ArgumentKind::Choice(choices) => choices.to_vec(),
+    }
+}
+
+/// Every way the last word of a partly typed command line could be finished, as whole lines.
+/// Only commands the sender may run are offered.
+pub fn complete(line: &str, permission: PermissionLevel) -> Vec<String> {
+    let slash = if line.starts_with('/') { "/" } else { "" };
+    let body = &line[slash.len()..];
+    // the word being typed is everything after the last space, it can be empty
+    let (head, partial) = match body.rfind(' ') {
+        Some(index) => (&body[..=index], &body[index + 1..]),
+        None => ("", body),
+    };
+    let previous: Vec<&str> = head.split_whitespace().collect();
+
+    let candidates = match previous.split_first() {
+        None => COMMANDS
+            .iter()
+            .filter(|spec| permission >= spec.permission)
+            .map(|spec| spec.name)
+            .collect(),
+        Some((name, arguments)) => COMMANDS
+            .iter()
+            .find(|spec| spec.name == *name && permission >= spec.permission)
+            .and_then(|spec| spec.arguments.get(arguments.len()))
+            .map(|kind| argument_candidates(*kind))
+            .unwrap_or_default(),
+    };
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(partial))
+        .map(|candidate| format!("{slash}{head}{candidate}"))
+        .collect()
+}
+
+/// Usage of every command the sender may run, one per line
+pub fn help_text(permission: PermissionLevel) -> String {
+    COMMANDS
+        .iter()
+        .filter(|spec| permission >= spec.permission)
+        .map(|spec| spec.usage)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_parse_with_typed_arguments() {
+        let parse = |line: &str| parse_command(line, PermissionLevel::Admin);
+        assert_eq!(
+            parse("/tp 1 ~ ~-2.5"),
+            Ok(Command::Teleport([
+                Coordinate::Absolute(1.0),
+                Coordinate::Relative(0.0),
+                Coordinate::Relative(-2.5),
+            ]))
+        );
+        assert_eq!(
+            parse("give stone"),
+            Ok(Command::Give {
+                block: BlockType::Stone,
+                count: 1,
+            })
+        );
+        assert_eq!(
+            parse("  /give gold_ore 64 "),
+            Ok(Command::Give {
+                block: BlockType::GoldOre,
+                count: 64,
+            })
+        );
+        assert_eq!(parse("/time set noon"), Ok(Command::SetTime(0.5)));
+        assert_eq!(parse("/time set 1"), Ok(Command::SetTime(0.0)));
+        assert_eq!(
+            parse("/gamemode creative"),
+            Ok(Command::SetGameMode(GameMode::Creative))
+        );
+        assert_eq!(
+            parse("/ban Steve griefing the spawn"),
+            Ok(Command::Ban {
+                name: "Steve".to_string(),
+                reason: "griefing the spawn".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("/whitelist add Alex"),
+            Ok(Command::Whitelist(WhitelistChange::Add("Alex".to_string())))
+        );
+    }
+
+    #[test]
+    fn bad_command_lines_say_what_is_wrong() {
+        let parse = |line: &str| parse_command(line, PermissionLevel::Admin);
+        assert_eq!(
+            parse("/fly"),
+            Err(CommandError::UnknownCommand("fly".to_string()))
+        );
+        assert_eq!(
+            parse("/tp 1 2"),
+            Err(CommandError::MissingArgument {
+                usage: "/tp <x> <y> <z>"
+            })
+        );
+        for line in [
+            "/tp 1 2 NaN",
+            "/give bedrock",
+            "/give stone 0",
+            "/give stone many",
+            "/time set 1.5",
+            "/time to noon",
+            "/op Steve king",
+            "/whitelist maybe",
+        ] {
+            assert!(
+                matches!(parse(line), Err(CommandError::InvalidArgument { .. })),
+                "{line}"
+            );
+        }
+        assert_eq!(
+            parse(&format!("/give stone {}", MAX_GIVE_COUNT + 1)),
+            Err(CommandError::InvalidArgument {
+                value: (MAX_GIVE_COUNT + 1).to_string(),
+                usage: "/give <block> [count]",
+            })
+        );
+        assert_eq!(
+            parse("/seed 42"),
+            Err(CommandError::TooManyArguments { usage: "/seed" })
+        );
+    }
+
+    #[test]
+    fn commands_above_the_sender_are_refused() {
+        for spec in &COMMANDS {
+            let line = format!("/{}", spec.name);
+            for permission in PermissionLevel::ALL {
+                let refused = parse_command(&line, permission)
+                    == Err(CommandError::PermissionDenied(spec.name));
+                assert_eq!(
+                    refused,
+                    permission < spec.permission,
+                    "{line} as {permission:?}"
+                );
+            }
+        }
+        assert_eq!(
+            parse_command("/help", PermissionLevel::Player),
+            Ok(Command::Help)
+        );
+        assert_eq!(help_text(PermissionLevel::Player), "/help");
+    }
+
+    #[test]
+    fn completion_offers_what_the_sender_may_type() {
+        assert_eq!(complete("/", PermissionLevel::Player), vec!["/help"]);
+        assert_eq!(
+            complete("/g", PermissionLevel::Player),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            complete("/g", PermissionLevel::Operator),
+            vec!["/give", "/gamemode"]
+        );
+        assert_eq!(
+            complete("/gamemode s", PermissionLevel::Operator),
+            vec!["/gamemode survival", "/gamemode spectator"]
+        );
+        assert_eq!(
+            complete("give iron", PermissionLevel::Admin),
+            vec!["give iron_ore"]
+        );
+        assert_eq!(
+            complete("/time ", PermissionLevel::Operator),
+            vec!["/time set"]
+        );
+        assert_eq!(
+            complete("/time set n", PermissionLevel::Operator),
+            vec!["/time set noon", "/time set night"]
+        );
+        assert_eq!(
+            complete("/op Steve ", PermissionLevel::Admin),
+            vec!["/op Steve player", "/op Steve operator", "/op Steve admin"]
+        );
+        // arguments of commands the sender may not run, and past the last argument, are not offered
+        assert!(complete("/op Steve ", PermissionLevel::Operator).is_empty());
+        assert!(complete("/gamemode creative ", PermissionLevel::Admin).is_empty());
+    }
+
+    #[test]
+    fn copy_takes_two_corners_and_a_file() {
+        assert_eq!(
+            parse_command(
+                "/copy 0 0 0 ~ ~1 ~-2 house.schem",
+                PermissionLevel::Operator
+            ),
+            Ok(Command::CopySchematic {
+                corners: [
+                    [Coordinate::Absolute(0.0); 3],
+                    [
+                        Coordinate::Relative(0.0),
+                        Coordinate::Relative(1.0),
+                        Coordinate::Relative(-2.0),
+                    ],
+                ],
+                file_name: "house.schem".to_string(),
+            })
+        );
+        for file_name in ["../house", "a/b", "a\\b", ".hidden"] {
+            let line = format!("/copy 0 0 0 1 1 1 {file_name}");
+            assert!(matches!(
+                parse_command(&line, PermissionLevel::Operator),
+                Err(CommandError::InvalidArgument { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn paste_options_come_in_any_order() {
+        assert_eq!(
+            parse_command(
+                "/paste tree.vox 1 2 3 air 270 mirror",
+                PermissionLevel::Admin
+            ),
+            Ok(Command::PasteSchematic {
+                file_name: "tree.vox".to_string(),
+                origin: [
+                    Coordinate::Absolute(1.0),
+                    Coordinate::Absolute(2.0),
+                    Coordinate::Absolute(3.0),
+                ],
+                transform: SchematicTransform {
+                    quarter_turns: 3,
+                    mirror_x: true,
+                },
+                include_air: true,
+            })
+        );
+        assert!(matches!(
+            parse_command("/paste tree.vox 1 2 3 45", PermissionLevel::Admin),
+            Err(CommandError::InvalidArgument { .. })
+        ));
+        assert_eq!(
+            parse_command("/paste tree.vox 1 2 3", PermissionLevel::Player),
+            Err(CommandError::PermissionDenied("paste"))
+        );
+    }
+
+    #[test]
+    fn export_takes_a_file() {
+        assert_eq!(
+            parse_command("/export castle", PermissionLevel::Operator),
+            Ok(Command::ExportMesh("castle".to_string()))
+        );
+        assert_eq!(
+            parse_command("/export", PermissionLevel::Operator),
+            Err(CommandError::MissingArgument {
+                usage: "/export <file>"
+            })
+        );
+        assert!(matches!(
+            parse_command("/export ../castle", PermissionLevel::Operator),
+            Err(CommandError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,49 @@
+use bevy::prelude::*;
+
+use crate::game::world::components::BlockType;
+
+/// What a player can do in the world, the local player's is a resource
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum GameMode {
+    #[default]
+    Survival,
+    Creative,
+    /// can fly around and look but not change anything
+    Spectator,
+}
+
+impl GameMode {
+    pub const ALL: [GameMode; 3] = [GameMode::Survival, GameMode::Creative, GameMode::Spectator];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::Survival => "survival",
+            GameMode::Creative => "creative",
+            GameMode::Spectator => "spectator",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        GameMode::ALL.into_iter().find(|mode| mode.name() == name)
+    }
+
+    pub fn can_edit_blocks(&self) -> bool {
+        *self != GameMode::Spectator
+    }
+
+    /// Whether a player in this mode may place `block`, fluids and ores only come from the world in survival
+    pub fn can_place(&self, block: BlockType) -> bool {
+        match self {
+            GameMode::Survival => !matches!(
+                block,
+                BlockType::Water
+                    | BlockType::Lava
+                    | BlockType::CoalOre
+                    | BlockType::IronOre
+                    | BlockType::GoldOre
+            ),
+            GameMode::Creative => true,
+            GameMode::Spectator => false,
+        }
+    }
+}
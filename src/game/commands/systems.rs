@@ -0,0 +1,310 @@
+use std::path::Path;
+
+use bevy::prelude::*;
+
+use crate::{
+    events::{
+        CommandEvent, CommandFeedbackEvent, CommandSender, CopySchematicEvent, ExportMeshEvent,
+        PasteSchematicEvent, SaveWorldEvent,
+    },
+    game::{
+        falling::{components::DroppedItem, DROPPED_ITEM_SCALE},
+        sky::resources::WorldTime,
+        structures::{MAX_SCHEMATIC_SIDE, MAX_SCHEMATIC_VOLUME, SCHEMATIC_DIRECTORY_NAME},
+        world::{resources::WorldSeed, EXPORT_DIRECTORY_NAME, VOXEL_SIZE},
+    },
+};
+
+use super::{
+    parser::{help_text, Command, Coordinate},
+    resources::GameMode,
+};
+
+/// The world voxel position typed coordinates point at, relative ones count from `origin` in world units
+fn resolve_voxel_position(coordinates: &[Coordinate; 3], origin: Option<Vec3>) -> IVec3 {
+    let origin = origin.unwrap_or_default() / VOXEL_SIZE;
+    Vec3::new(
+        coordinates[0].resolve(origin.x),
+        coordinates[1].resolve(origin.y),
+        coordinates[2].resolve(origin.z),
+    )
+    .round()
+    .as_ivec3()
+}
+
+/// Runs commands that change the world or the local player.
+/// Teleports and game modes of players on a server are left to the server, which keeps track of them.
+#[allow(clippy::too_many_arguments)]
+pub fn run_commands(
+    mut commands: Commands,
+    mut command_events: EventReader<CommandEvent>,
+    mut feedback_events: EventWriter<CommandFeedbackEvent>,
+    mut save_world_events: EventWriter<SaveWorldEvent>,
+    mut copy_schematic_events: EventWriter<CopySchematicEvent>,
+    mut paste_schematic_events: EventWriter<PasteSchematicEvent>,
+    mut export_mesh_events: EventWriter<ExportMeshEvent>,
+    mut camera_query: Query<&mut Transform, With<Camera3d>>,
+    mut world_time: ResMut<WorldTime>,
+    world_seed: Res<WorldSeed>,
+    mut game_mode: ResMut<GameMode>,
+) {
+    for event in command_events.read() {
+        let text = match &event.command {
+            Command::Teleport(coordinates) => {
+                match event.sender {
+                    CommandSender::LocalPlayer => {}
+                    CommandSender::Player(_) => continue,
+                    CommandSender::Console => {
+                        feedback_events.send(CommandFeedbackEvent {
+                            recipient: event.sender,
+                            text: "Only players can be teleported".to_string(),
+                        });
+                        continue;
+                    }
+                }
+                let origin = event.origin.unwrap_or_default() / VOXEL_SIZE;
+                let target = Vec3::new(
+                    coordinates[0].resolve(origin.x),
+                    coordinates[1].resolve(origin.y),
+                    coordinates[2].resolve(origin.z),
+                );
+                for mut camera_transform in camera_query.iter_mut() {
+                    camera_transform.translation = target * VOXEL_SIZE;
+                }
+                format!("Teleported to {} {} {}", target.x, target.y, target.z)
+            }
+            Command::Give { block, count } => match event.origin {
+                Some(origin) => {
+                    for _ in 0..*count {
+                        commands.spawn((
+                            SpatialBundle::from_transform(
+                                Transform::from_translation(origin)
+                                    .with_scale(Vec3::splat(DROPPED_ITEM_SCALE)),
+                            ),
+                            DroppedItem { block: *block },
+                            Name::new("Dropped Item"),
+                        ));
+                    }
+                    format!("Gave {count} {}", block.name())
+                }
+                None => "Only players can be given blocks".to_string(),
+            },
+            Command::SetTime(time_of_day) => {
+                world_time.time_of_day = *time_of_day;
+                format!("Set the time to {time_of_day}")
+            }
+            Command::Seed => format!("Seed: {}", world_seed.0),
+            Command::Save => {
+                save_world_events.send(SaveWorldEvent);
+                "Saving the world".to_string()
+            }
+            Command::SetGameMode(mode) => {
+                match event.sender {
+                    CommandSender::LocalPlayer => {}
+                    CommandSender::Player(_) => continue,
+                    CommandSender::Console => {
+                        feedback_events.send(CommandFeedbackEvent {
+                            recipient: event.sender,
+                            text: "Only players have a game mode".to_string(),
+                        });
+                        continue;
+                    }
+                }
+                *game_mode = *mode;
+                format!("Set game mode to {}", mode.name())
+            }
+            Command::SetPermission { .. }
+            | Command::Kick { .. }
+            | Command::Ban { .. }
+            | Command::Pardon(_)
+            | Command::Whitelist(_) => {
+                // the server keeps the accounts
+                if event.sender != CommandSender::LocalPlayer {
+                    continue;
+                }
+                "Only servers have player accounts".to_string()
+            }
+            Command::CopySchematic { corners, file_name } => {
+                let min = resolve_voxel_position(&corners[0], event.origin);
+                let max = resolve_voxel_position(&corners[1], event.origin);
+                let size = ((max - min).abs() + IVec3::ONE).as_i64vec3();
+                if size.max_element() > MAX_SCHEMATIC_SIDE {
+                    format!("Can not copy more than {MAX_SCHEMATIC_SIDE} voxels along a side")
+                } else if size.x * size.y * size.z > MAX_SCHEMATIC_VOLUME {
+                    format!("Can not copy more than {MAX_SCHEMATIC_VOLUME} voxels at once")
+                } else {
+                    copy_schematic_events.send(CopySchematicEvent {
+                        min,
+                        max,
+                        path: Path::new(SCHEMATIC_DIRECTORY_NAME).join(file_name),
+                    });
+                    format!("Copying {} voxels to {file_name}", size.x * size.y * size.z)
+                }
+            }
+            Command::PasteSchematic {
+                file_name,
+                origin,
+                transform,
+                include_air,
+            } => {
+                let origin = resolve_voxel_position(origin, event.origin);
+                paste_schematic_events.send(PasteSchematicEvent {
+                    path: Path::new(SCHEMATIC_DIRECTORY_NAME).join(file_name),
+                    origin,
+                    transform: *transform,
+                    include_air: *include_air,
+                });
+                format!(
+                    "Pasting {file_name} at {} {} {}",
+                    origin.x, origin.y, origin.z
+                )
+            }
+            Command::ExportMesh(file_name) => {
+                // only games that draw the world have meshes to export
+                if event.sender != CommandSender::LocalPlayer {
+                    "Only single player worlds can be exported".to_string()
+                } else {
+                    let mut path = Path::new(EXPORT_DIRECTORY_NAME).join(file_name);
+                    if path.extension().is_none() {
+                        path.set_extension("obj");
+                    }
+                    let text = format!("Exporting the world to {}", path.display());
+                    export_mesh_events.send(ExportMeshEvent { path });
+                    text
+                }
+            }
+            Command::Help => help_text(event.permission),
+        };
+        feedback_events.send(CommandFeedbackEvent {
+            recipient: event.sender,
+            text,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::commands::parser::{parse_command, PermissionLevel};
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<CommandEvent>()
+            .add_event::<CommandFeedbackEvent>()
+            .add_event::<SaveWorldEvent>()
+            .add_event::<CopySchematicEvent>()
+            .add_event::<PasteSchematicEvent>()
+            .add_event::<ExportMeshEvent>()
+            .init_resource::<WorldTime>()
+            .insert_resource(WorldSeed(42))
+            .init_resource::<GameMode>()
+            .add_systems(Update, run_commands);
+        app.world.spawn((Camera3d::default(), Transform::default()));
+        app
+    }
+
+    /// Runs one command from `sender` standing at `origin`, returns everything it answered
+    fn run_command(
+        app: &mut App,
+        sender: CommandSender,
+        origin: Option<Vec3>,
+        line: &str,
+    ) -> Vec<String> {
+        app.world
+            .resource_mut::<Events<CommandFeedbackEvent>>()
+            .clear();
+        app.world.send_event(CommandEvent {
+            sender,
+            permission: PermissionLevel::Admin,
+            origin,
+            command: parse_command(line, PermissionLevel::Admin).unwrap(),
+        });
+        app.update();
+        let events = app.world.resource::<Events<CommandFeedbackEvent>>();
+        let mut reader = events.get_reader();
+        reader
+            .read(events)
+            .map(|feedback| {
+                assert_eq!(feedback.recipient, sender);
+                feedback.text.clone()
+            })
+            .collect()
+    }
+
+    fn camera_position(app: &mut App) -> Vec3 {
+        app.world
+            .query_filtered::<&Transform, With<Camera3d>>()
+            .single(&app.world)
+            .translation
+    }
+
+    #[test]
+    fn relative_teleports_count_from_the_sender() {
+        let mut app = app();
+        let origin = Some(Vec3::new(1.0, 2.0, 3.0));
+        let feedback = run_command(&mut app, CommandSender::LocalPlayer, origin, "/tp ~5 ~ 40");
+        assert_eq!(feedback, vec!["Teleported to 15 20 40"]);
+        let expected = Vec3::new(1.5, 2.0, 4.0);
+        assert!(camera_position(&mut app).abs_diff_eq(expected, 1e-4));
+    }
+
+    #[test]
+    fn time_seed_and_save_commands() {
+        let mut app = app();
+        let feedback = run_command(&mut app, CommandSender::Console, None, "/time set 0.25");
+        assert_eq!(feedback, vec!["Set the time to 0.25"]);
+        assert_eq!(app.world.resource::<WorldTime>().time_of_day, 0.25);
+
+        let feedback = run_command(&mut app, CommandSender::Console, None, "/seed");
+        assert_eq!(feedback, vec!["Seed: 42"]);
+
+        assert!(app.world.resource::<Events<SaveWorldEvent>>().is_empty());
+        let feedback = run_command(&mut app, CommandSender::Console, None, "/save");
+        assert_eq!(feedback, vec!["Saving the world"]);
+        assert!(!app.world.resource::<Events<SaveWorldEvent>>().is_empty());
+    }
+
+    #[test]
+    fn game_mode_changes_the_local_player() {
+        let mut app = app();
+        let feedback = run_command(
+            &mut app,
+            CommandSender::LocalPlayer,
+            Some(Vec3::ZERO),
+            "/gamemode creative",
+        );
+        assert_eq!(feedback, vec!["Set game mode to creative"]);
+        assert_eq!(*app.world.resource::<GameMode>(), GameMode::Creative);
+    }
+
+    #[test]
+    fn only_the_local_player_is_moved_here() {
+        let mut app = app();
+        // the console is nobody to move
+        let feedback = run_command(&mut app, CommandSender::Console, None, "/tp 1 2 3");
+        assert_eq!(feedback, vec!["Only players can be teleported"]);
+        let feedback = run_command(
+            &mut app,
+            CommandSender::Console,
+            None,
+            "/gamemode spectator",
+        );
+        assert_eq!(feedback, vec!["Only players have a game mode"]);
+
+        // players on a server are the server's to move, it answers them
+        let origin = Some(Vec3::ONE);
+        let feedback = run_command(&mut app, CommandSender::Player(3), origin, "/tp 1 2 3");
+        assert!(feedback.is_empty());
+        let feedback = run_command(
+            &mut app,
+            CommandSender::Player(3),
+            origin,
+            "/gamemode spectator",
+        );
+        assert!(feedback.is_empty());
+
+        assert_eq!(camera_position(&mut app), Vec3::ZERO);
+        assert_eq!(*app.world.resource::<GameMode>(), GameMode::Survival);
+    }
+}
@@ -0,0 +1,20 @@
+use bevy::prelude::*;
+
+use crate::game::world::components::BlockType;
+
+/// A block that came loose from the voxel grid and is falling down
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Component)]
+pub struct FallingBlock {
+    pub block: BlockType,
+    pub state: u8,
+    /// downwards speed in voxels per second
+    pub velocity: f32,
+}
+
+/// A block that had nowhere to land, left lying around as an item
+#[derive(Component, Debug, Clone, Copy, Default, Reflect)]
+#[reflect(Component)]
+pub struct DroppedItem {
+    pub block: BlockType,
+}
@@ -0,0 +1,150 @@
+mod systems;
+
+pub mod components;
+pub mod resources;
+
+use bevy::prelude::*;
+
+use self::{components::*, resources::*, systems::*};
+
+use super::{tick::WorldTickSet, world::resources::CubeMesh};
+
+/// How fast falling blocks speed up, in voxels per second squared
+pub const FALLING_BLOCK_GRAVITY: f32 = 16.0;
+/// Fastest a falling block moves, in voxels per second.
+/// Kept at one voxel per world tick so a block never skips over the voxel it should land on.
+pub const FALLING_BLOCK_TERMINAL_VELOCITY: f32 = 20.0;
+/// Size of a dropped item compared to a full voxel
+pub const DROPPED_ITEM_SCALE: f32 = 0.3;
+
+pub struct FallingPlugin;
+
+impl Plugin for FallingPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<FallingBlock>()
+            .register_type::<DroppedItem>()
+            .add_systems(
+                FixedUpdate,
+                (
+                    release_falling_blocks.in_set(WorldTickSet::Blocks),
+                    update_falling_blocks.in_set(WorldTickSet::Entities),
+                ),
+            );
+    }
+}
+
+pub struct FallingRenderPlugin;
+
+impl Plugin for FallingRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BlockMaterials>().add_systems(
+            Update,
+            add_falling_block_meshes.run_if(resource_exists::<CubeMesh>()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{ecs::system::RunSystemOnce, time::TimeUpdateStrategy};
+
+    use super::*;
+    use crate::{
+        game::{
+            tick::{systems::advance_world_tick, TickPlugin, WORLD_TICKS_PER_SECOND},
+            world::{
+                access::{BlockChange, VoxelAccess, WorldVoxels},
+                components::BlockType,
+                resources::{NeighbourUpdates, VoxelWorld},
+                systems::advance_neighbour_updates,
+            },
+            SimulationState,
+        },
+        AppState,
+    };
+
+    /// A headless world one tick per update, with a column of sand from y 4 to 7 resting on stone
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, TickPlugin, FallingPlugin))
+            .add_state::<AppState>()
+            .add_state::<SimulationState>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+                1.0 / WORLD_TICKS_PER_SECOND,
+            )))
+            .init_resource::<NeighbourUpdates>()
+            .add_systems(
+                FixedUpdate,
+                advance_neighbour_updates
+                    .after(advance_world_tick)
+                    .in_set(WorldTickSet::Begin),
+            );
+        VoxelWorld::spawn_test_world(&mut app.world, IVec3::new(3, 10, 3));
+        set_block_at(&mut app, IVec3::new(1, 3, 1), Some((BlockType::Stone, 0)));
+        for y in 4..8 {
+            set_block_at(&mut app, IVec3::new(1, y, 1), Some((BlockType::Sand, 0)));
+        }
+        app
+    }
+
+    fn set_block_at(app: &mut App, position: IVec3, block: BlockChange) {
+        app.world.run_system_once(move |mut voxels: WorldVoxels| {
+            voxels.apply_change(position, block);
+        });
+    }
+
+    fn column(app: &mut App) -> Vec<BlockChange> {
+        app.world.run_system_once(|voxels: WorldVoxels| {
+            (0..10)
+                .map(|y| voxels.voxel(IVec3::new(1, y, 1)).unwrap().block_change())
+                .collect()
+        })
+    }
+
+    fn falling_blocks(app: &mut App) -> usize {
+        app.world.query::<&FallingBlock>().iter(&app.world).count()
+    }
+
+    #[test]
+    fn supported_sand_stays_put() {
+        let mut app = app();
+        for _ in 0..20 {
+            app.update();
+        }
+        assert_eq!(falling_blocks(&mut app), 0);
+        let sand = Some((BlockType::Sand, 0));
+        assert_eq!(
+            column(&mut app)[3..8],
+            [Some((BlockType::Stone, 0)), sand, sand, sand, sand]
+        );
+    }
+
+    #[test]
+    fn sand_column_collapses_when_its_support_goes() {
+        let mut app = app();
+        // settle the neighbour changes from building the column
+        app.update();
+        app.update();
+        set_block_at(&mut app, IVec3::new(1, 3, 1), None);
+        app.update();
+        // only the bottom block is told at first, the rest follow one tick after another
+        assert_eq!(falling_blocks(&mut app), 1);
+        app.update();
+        assert_eq!(falling_blocks(&mut app), 2);
+        for _ in 0..60 {
+            app.update();
+        }
+        assert_eq!(falling_blocks(&mut app), 0);
+        assert_eq!(
+            app.world.query::<&DroppedItem>().iter(&app.world).count(),
+            0
+        );
+        let sand = Some((BlockType::Sand, 0));
+        assert_eq!(
+            column(&mut app),
+            [sand, sand, sand, sand, None, None, None, None, None, None]
+        );
+    }
+}
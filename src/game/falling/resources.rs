@@ -0,0 +1,29 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::game::world::components::BlockType;
+
+/// A plain colored material for each block type, for blocks drawn outside of chunk meshes
+#[derive(Resource, Debug, Default)]
+pub struct BlockMaterials(HashMap<BlockType, Handle<StandardMaterial>>);
+
+impl BlockMaterials {
+    pub fn get_or_add(
+        &mut self,
+        block: BlockType,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Handle<StandardMaterial> {
+        self.0
+            .entry(block)
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    alpha_mode: if block.is_transparent() {
+                        AlphaMode::Blend
+                    } else {
+                        AlphaMode::Opaque
+                    },
+                    ..block.color().into()
+                })
+            })
+            .clone()
+    }
+}
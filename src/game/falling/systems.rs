@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+
+use crate::game::world::{
+    access::{VoxelAccess, WorldVoxels},
+    components::Voxel,
+    resources::CubeMesh,
+    VOXEL_SIZE,
+};
+
+use super::{
+    components::{DroppedItem, FallingBlock},
+    resources::BlockMaterials,
+    DROPPED_ITEM_SCALE, FALLING_BLOCK_GRAVITY, FALLING_BLOCK_TERMINAL_VELOCITY,
+};
+
+/// Whether a falling block stops on this voxel, fluids are fallen through.
+/// The bottom of the world stops blocks too.
+fn stops_falling(voxel: Option<&Voxel>) -> bool {
+    voxel.map_or(true, |voxel| voxel.solid && !voxel.block.is_fluid())
+}
+
+/// Turns loose blocks whose neighbours changed into falling block entities
+pub fn release_falling_blocks(mut commands: Commands, mut voxels: WorldVoxels) {
+    for position in voxels.neighbour_updates().to_vec() {
+        let Some(voxel) = voxels.voxel(position) else {
+            continue;
+        };
+        if !voxel.solid || !voxel.block.falls() || stops_falling(voxels.voxel(position - IVec3::Y))
+        {
+            continue;
+        }
+        let falling_block = FallingBlock {
+            block: voxel.block,
+            state: voxel.state,
+            velocity: 0.0,
+        };
+        // the block above is told and falls on the next tick, so columns collapse from the bottom up
+        voxels.set_block(position, None, 0);
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(
+                position.as_vec3() * VOXEL_SIZE,
+            )),
+            falling_block,
+            Name::new("Falling Block"),
+        ));
+    }
+    voxels.update_touched_chunks();
+}
+
+/// Moves falling blocks down and puts them back into the voxel grid when they land,
+/// a block landing where a voxel is already taken is dropped as an item instead
+pub fn update_falling_blocks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut voxels: WorldVoxels,
+    mut falling_block_query: Query<(Entity, &mut FallingBlock, &mut Transform)>,
+) {
+    // land lower blocks first so a block falling onto another lands on top of it
+    let mut falling_blocks: Vec<_> = falling_block_query.iter_mut().collect();
+    falling_blocks.sort_by(|(_, _, a), (_, _, b)| a.translation.y.total_cmp(&b.translation.y));
+
+    for (entity, mut falling_block, mut transform) in falling_blocks {
+        let delta_seconds = time.delta_seconds();
+        falling_block.velocity = (falling_block.velocity + FALLING_BLOCK_GRAVITY * delta_seconds)
+            .min(FALLING_BLOCK_TERMINAL_VELOCITY);
+        let voxel_position = transform.translation / VOXEL_SIZE;
+        let next_height = voxel_position.y - falling_block.velocity * delta_seconds;
+        let below = IVec3::new(
+            voxel_position.x.round() as i32,
+            next_height.floor() as i32,
+            voxel_position.z.round() as i32,
+        );
+        if !stops_falling(voxels.voxel(below)) {
+            transform.translation.y = next_height * VOXEL_SIZE;
+            continue;
+        }
+
+        let landing = below + IVec3::Y;
+        commands.entity(entity).despawn_recursive();
+        let is_free = voxels
+            .voxel(landing)
+            .is_some_and(|voxel| !stops_falling(Some(voxel)));
+        if is_free {
+            voxels.set_block(landing, Some(falling_block.block), falling_block.state);
+        } else {
+            commands.spawn((
+                SpatialBundle::from_transform(
+                    Transform::from_translation(landing.as_vec3() * VOXEL_SIZE)
+                        .with_scale(Vec3::splat(DROPPED_ITEM_SCALE)),
+                ),
+                DroppedItem {
+                    block: falling_block.block,
+                },
+                Name::new("Dropped Item"),
+            ));
+        }
+    }
+    voxels.update_touched_chunks();
+}
+
+/// Gives newly spawned falling blocks and dropped items a cube in the color of their block
+pub fn add_falling_block_meshes(
+    mut commands: Commands,
+    falling_block_query: Query<(Entity, &FallingBlock), Added<FallingBlock>>,
+    dropped_item_query: Query<(Entity, &DroppedItem), Added<DroppedItem>>,
+    cube_mesh: Res<CubeMesh>,
+    mut block_materials: ResMut<BlockMaterials>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let blocks = falling_block_query
+        .iter()
+        .map(|(entity, falling_block)| (entity, falling_block.block))
+        .chain(
+            dropped_item_query
+                .iter()
+                .map(|(entity, dropped_item)| (entity, dropped_item.block)),
+        );
+    for (entity, block) in blocks {
+        commands.entity(entity).insert((
+            cube_mesh.mesh_handle.clone(),
+            block_materials.get_or_add(block, &mut materials),
+        ));
+    }
+}
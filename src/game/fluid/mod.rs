@@ -0,0 +1,46 @@
+pub mod resources;
+pub mod simulation;
+pub mod systems;
+
+use bevy::prelude::*;
+
+use crate::{
+    game::{
+        tick::{every_n_ticks, WorldTickSet},
+        world::resources::VoxelWorld,
+    },
+    AppState,
+};
+
+use self::{resources::*, systems::*};
+
+/// Bits of a fluid voxel `state` holding how far it is from its source, 0 is a source
+pub const FLUID_LEVEL_MASK: u8 = 0b0111;
+/// Set in a fluid voxel `state` when it is fed from above
+pub const FLUID_FALLING: u8 = 0b1000;
+
+/// World ticks between fluid steps
+pub const FLUID_TICK_INTERVAL: u64 = 5;
+
+pub struct FluidPlugin;
+
+impl Plugin for FluidPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FluidUpdates>()
+            .add_systems(
+                Update,
+                seed_fluid_updates
+                    .run_if(in_state(AppState::Game).and_then(resource_added::<VoxelWorld>())),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    // every tick, neighbour updates are only handed out for the tick after the change
+                    track_fluid_updates,
+                    tick_fluids.run_if(every_n_ticks(FLUID_TICK_INTERVAL)),
+                )
+                    .chain()
+                    .in_set(WorldTickSet::Fluids),
+            );
+    }
+}
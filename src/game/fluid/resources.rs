@@ -0,0 +1,5 @@
+use bevy::{prelude::*, utils::HashSet};
+
+/// Positions that may hold a fluid that needs to move on the next tick
+#[derive(Resource, Default)]
+pub struct FluidUpdates(pub HashSet<IVec3>);
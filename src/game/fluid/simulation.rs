@@ -0,0 +1,297 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::game::world::{
+    access::{BlockChange, VoxelAccess},
+    components::BlockType,
+};
+
+use super::{FLUID_FALLING, FLUID_LEVEL_MASK};
+
+/// Horizontal directions fluids spread in, in a fixed order so the simulation is deterministic
+const HORIZONTAL_DIRECTIONS: [IVec3; 4] = [IVec3::NEG_X, IVec3::X, IVec3::NEG_Z, IVec3::Z];
+
+/// How far a fluid flows sideways from its source before it stops
+pub fn max_flow_distance(fluid: BlockType) -> u8 {
+    match fluid {
+        BlockType::Lava => 3,
+        _ => 7,
+    }
+}
+
+/// The fluid in a voxel and its state, `None` for anything that is not a fluid
+fn fluid_at(access: &impl VoxelAccess, position: IVec3) -> Option<(BlockType, u8)> {
+    access
+        .voxel(position)
+        .filter(|voxel| voxel.solid && voxel.block.is_fluid())
+        .map(|voxel| (voxel.block, voxel.state))
+}
+
+fn is_source(state: u8) -> bool {
+    state == 0
+}
+
+/// The distance from a source a flowing fluid acts as, falling fluid spreads like a source
+fn effective_level(state: u8) -> u8 {
+    if state & FLUID_FALLING != 0 {
+        0
+    } else {
+        state & FLUID_LEVEL_MASK
+    }
+}
+
+/// Whether `fluid` at `state` may flow into `position`, air is always replaced
+/// and flowing fluid of the same kind is replaced by a stronger flow
+fn can_flow_into(access: &impl VoxelAccess, position: IVec3, fluid: BlockType, state: u8) -> bool {
+    let Some(voxel) = access.voxel(position) else {
+        return false;
+    };
+    if !voxel.solid {
+        return true;
+    }
+    voxel.block == fluid
+        && !is_source(voxel.state)
+        && (state & FLUID_FALLING != 0 && voxel.state & FLUID_FALLING == 0
+            || effective_level(state) < effective_level(voxel.state))
+}
+
+/// What a flowing (non source) fluid should be given its neighbours, `None` when nothing feeds it anymore
+fn expected_state(access: &impl VoxelAccess, position: IVec3, fluid: BlockType) -> Option<u8> {
+    if fluid_at(access, position + IVec3::Y).is_some_and(|(above, _)| above == fluid) {
+        return Some(FLUID_FALLING | 1);
+    }
+    HORIZONTAL_DIRECTIONS
+        .iter()
+        .filter_map(|direction| fluid_at(access, position + *direction))
+        .filter(|(neighbour, _)| *neighbour == fluid)
+        .map(|(_, state)| effective_level(state) + 1)
+        .min()
+        .filter(|level| *level <= max_flow_distance(fluid))
+}
+
+/// Ranks changes made to the same voxel in one step, the highest wins
+fn change_priority(change: &BlockChange) -> u8 {
+    match change {
+        None => 0,
+        Some((block, _)) if !block.is_fluid() => u8::MAX,
+        Some((_, state)) if state & FLUID_FALLING != 0 => u8::MAX - 1,
+        Some((_, state)) => u8::MAX - 2 - effective_level(*state),
+    }
+}
+
+fn push_change(changes: &mut HashMap<IVec3, BlockChange>, position: IVec3, change: BlockChange) {
+    let keep_existing = changes
+        .get(&position)
+        .is_some_and(|existing| change_priority(existing) >= change_priority(&change));
+    if !keep_existing {
+        changes.insert(position, change);
+    }
+}
+
+/// Advances every fluid in `active` by one tick.
+/// All decisions are made from the world as it was before the step, and the changes are returned
+/// sorted by position so stepping the same world always gives the same result.
+pub fn step_fluids(
+    access: &impl VoxelAccess,
+    active: &HashSet<IVec3>,
+) -> Vec<(IVec3, BlockChange)> {
+    let mut positions: Vec<IVec3> = active.iter().copied().collect();
+    positions.sort_by_key(|position| (position.x, position.y, position.z));
+
+    let mut changes: HashMap<IVec3, BlockChange> = HashMap::new();
+    for position in positions {
+        let Some((fluid, state)) = fluid_at(access, position) else {
+            continue;
+        };
+
+        // lava touching water cools down
+        if fluid == BlockType::Lava
+            && HORIZONTAL_DIRECTIONS
+                .iter()
+                .chain([IVec3::Y, IVec3::NEG_Y].iter())
+                .any(|direction| {
+                    fluid_at(access, position + *direction)
+                        .is_some_and(|(neighbour, _)| neighbour == BlockType::Water)
+                })
+        {
+            push_change(&mut changes, position, Some((BlockType::Stone, 0)));
+            continue;
+        }
+
+        // flowing fluid follows whatever feeds it, drying up when nothing does
+        let state = if is_source(state) {
+            state
+        } else {
+            match expected_state(access, position, fluid) {
+                Some(expected) if expected == state => state,
+                Some(expected) => {
+                    push_change(&mut changes, position, Some((fluid, expected)));
+                    continue;
+                }
+                None => {
+                    push_change(&mut changes, position, None);
+                    continue;
+                }
+            }
+        };
+
+        // fall first, only spread sideways once resting on something
+        let below = position + IVec3::NEG_Y;
+        if can_flow_into(access, below, fluid, FLUID_FALLING | 1) {
+            push_change(&mut changes, below, Some((fluid, FLUID_FALLING | 1)));
+            continue;
+        }
+        if fluid_at(access, below).is_some_and(|(below_fluid, _)| below_fluid == fluid) {
+            continue;
+        }
+        let spread_level = effective_level(state) + 1;
+        if spread_level > max_flow_distance(fluid) {
+            continue;
+        }
+        for direction in HORIZONTAL_DIRECTIONS {
+            let neighbour = position + direction;
+            if can_flow_into(access, neighbour, fluid, spread_level) {
+                push_change(&mut changes, neighbour, Some((fluid, spread_level)));
+            }
+        }
+    }
+
+    let mut changes: Vec<(IVec3, BlockChange)> = changes.into_iter().collect();
+    changes.sort_by_key(|(position, _)| (position.x, position.y, position.z));
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::components::Voxel;
+
+    /// A stone floor at y 0 with air above it, `size` voxels out from the origin along x and z
+    fn basin(size: i32, reversed: bool) -> HashMap<IVec3, Voxel> {
+        let mut positions = vec![];
+        for x in -size..=size {
+            for y in 0..4 {
+                for z in -size..=size {
+                    positions.push(IVec3::new(x, y, z));
+                }
+            }
+        }
+        // the same world built in another order, hash map iteration order changes with it
+        if reversed {
+            positions.reverse();
+        }
+        positions
+            .into_iter()
+            .map(|position| {
+                let voxel = Voxel {
+                    solid: position.y == 0,
+                    block: BlockType::Stone,
+                    ..default()
+                };
+                (position, voxel)
+            })
+            .collect()
+    }
+
+    fn place(world: &mut HashMap<IVec3, Voxel>, position: IVec3, block: BlockType, state: u8) {
+        let voxel = world.get_mut(&position).unwrap();
+        voxel.solid = true;
+        voxel.block = block;
+        voxel.state = state;
+    }
+
+    /// Steps the fluids until nothing changes or `steps` runs out, like the fluid systems do
+    fn run(world: &mut HashMap<IVec3, Voxel>, mut active: HashSet<IVec3>, steps: usize) {
+        for _ in 0..steps {
+            let changes = step_fluids(world, &active);
+            if changes.is_empty() {
+                return;
+            }
+            active.clear();
+            for (position, change) in changes {
+                world.apply_change(position, change);
+                active.insert(position);
+                for direction in HORIZONTAL_DIRECTIONS
+                    .iter()
+                    .chain([IVec3::Y, IVec3::NEG_Y].iter())
+                {
+                    active.insert(position + *direction);
+                }
+            }
+        }
+    }
+
+    fn blocks(world: &HashMap<IVec3, Voxel>) -> Vec<(IVec3, BlockChange)> {
+        let mut blocks: Vec<(IVec3, BlockChange)> = world
+            .iter()
+            .map(|(position, voxel)| (*position, voxel.block_change()))
+            .collect();
+        blocks.sort_by_key(|(position, _)| (position.x, position.y, position.z));
+        blocks
+    }
+
+    #[test]
+    fn same_world_gives_same_changes() {
+        let source = IVec3::new(0, 3, 0);
+        let mut first = basin(8, false);
+        let mut second = basin(8, true);
+        place(&mut first, source, BlockType::Water, 0);
+        place(&mut second, source, BlockType::Water, 0);
+        let active = HashSet::from_iter([source]);
+        assert_eq!(step_fluids(&first, &active), step_fluids(&second, &active));
+
+        run(&mut first, active.clone(), 40);
+        run(&mut second, active, 40);
+        assert_eq!(blocks(&first), blocks(&second));
+    }
+
+    #[test]
+    fn water_falls_then_spreads_as_far_as_it_can() {
+        let mut world = basin(9, false);
+        let source = IVec3::new(0, 3, 0);
+        place(&mut world, source, BlockType::Water, 0);
+        run(&mut world, HashSet::from_iter([source]), 100);
+
+        let fluid = |position: IVec3| fluid_at(&world, position);
+        assert_eq!(
+            fluid(IVec3::new(0, 1, 0)),
+            Some((BlockType::Water, FLUID_FALLING | 1))
+        );
+        let reach = i32::from(max_flow_distance(BlockType::Water));
+        assert_eq!(
+            fluid(IVec3::new(reach, 1, 0)),
+            Some((BlockType::Water, reach as u8))
+        );
+        assert_eq!(fluid(IVec3::new(reach + 1, 1, 0)), None);
+    }
+
+    #[test]
+    fn flowing_water_dries_up_without_its_source() {
+        let mut world = basin(9, false);
+        let source = IVec3::new(0, 1, 0);
+        place(&mut world, source, BlockType::Water, 0);
+        run(&mut world, HashSet::from_iter([source]), 100);
+        world.apply_change(source, None);
+        let active = world
+            .iter()
+            .filter(|(_, voxel)| voxel.solid && voxel.block.is_fluid())
+            .map(|(position, _)| *position)
+            .collect();
+        run(&mut world, active, 100);
+        assert!(world
+            .values()
+            .all(|voxel| !(voxel.solid && voxel.block.is_fluid())));
+    }
+
+    #[test]
+    fn lava_next_to_water_turns_to_stone() {
+        let mut world = basin(2, false);
+        let lava = IVec3::new(0, 1, 0);
+        place(&mut world, lava, BlockType::Lava, 0);
+        place(&mut world, IVec3::new(1, 1, 0), BlockType::Water, 0);
+        let changes = step_fluids(&world, &HashSet::from_iter([lava]));
+        assert_eq!(changes, vec![(lava, Some((BlockType::Stone, 0)))]);
+    }
+}
@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+
+use crate::game::world::{access::VoxelAccess, access::WorldVoxels, FACE_DIRECTIONS};
+
+use super::{resources::FluidUpdates, simulation::step_fluids};
+
+/// Wakes up every fluid in a freshly spawned world
+pub fn seed_fluid_updates(voxels: WorldVoxels, mut fluid_updates: ResMut<FluidUpdates>) {
+    for position in voxels.voxel_world.voxel_positions() {
+        if voxels
+            .voxel(position)
+            .is_some_and(|voxel| voxel.solid && voxel.block.is_fluid())
+        {
+            fluid_updates.0.insert(position);
+        }
+    }
+}
+
+/// Any changed block may let fluid next to it flow again, or be fluid itself.
+/// Edits, block updates, falling blocks and pastes alike show up as neighbour updates.
+pub fn track_fluid_updates(voxels: WorldVoxels, mut fluid_updates: ResMut<FluidUpdates>) {
+    for &position in voxels.neighbour_updates() {
+        // the changed block is next to every one of its neighbours
+        fluid_updates.0.insert(position);
+        for (_, direction) in FACE_DIRECTIONS {
+            fluid_updates.0.insert(position + direction);
+        }
+    }
+}
+
+pub fn tick_fluids(mut voxels: WorldVoxels, mut fluid_updates: ResMut<FluidUpdates>) {
+    let active = std::mem::take(&mut fluid_updates.0);
+    for (position, change) in step_fluids(&voxels, &active) {
+        voxels.apply_change(position, change);
+        // whatever changed, and whatever is next to it, moves on the next tick
+        fluid_updates.0.insert(position);
+        for (_, direction) in FACE_DIRECTIONS {
+            fluid_updates.0.insert(position + direction);
+        }
+    }
+    voxels.update_touched_chunks();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::game::world::{
+        access::BlockChange,
+        components::BlockType,
+        resources::{NeighbourUpdates, VoxelWorld},
+    };
+
+    fn block_at(world: &mut World, position: IVec3) -> BlockChange {
+        world.run_system_once(move |voxels: WorldVoxels| {
+            voxels.voxel(position).unwrap().block_change()
+        })
+    }
+
+    #[test]
+    fn fluid_changed_without_an_edit_event_flows() {
+        let mut world = World::new();
+        world.init_resource::<NeighbourUpdates>();
+        world.init_resource::<FluidUpdates>();
+        VoxelWorld::spawn_test_world(&mut world, IVec3::new(3, 1, 1));
+        // like a random tick or a paste would, without a `SetBlockEvent`
+        let source = IVec3::ZERO;
+        world.run_system_once(move |mut voxels: WorldVoxels| {
+            voxels.apply_change(source, Some((BlockType::Water, 0)));
+        });
+        world.resource_mut::<NeighbourUpdates>().advance();
+
+        world.run_system_once(track_fluid_updates);
+        assert!(world.resource::<FluidUpdates>().0.contains(&source));
+        world.run_system_once(tick_fluids);
+        assert_eq!(block_at(&mut world, IVec3::X), Some((BlockType::Water, 1)));
+    }
+}
@@ -2,20 +2,65 @@ use bevy::prelude::*;
 
 use crate::AppState;
 
-use self::{camera::CameraPlugin, systems::*, world::WorldPlugin};
+use self::{
+    blocks::BlocksPlugin,
+    camera::CameraPlugin,
+    commands::CommandsPlugin,
+    falling::{FallingPlugin, FallingRenderPlugin},
+    fluid::FluidPlugin,
+    save::SavePlugin,
+    sky::{SkyPlugin, SkyRenderPlugin},
+    structures::StructuresPlugin,
+    systems::*,
+    tick::TickPlugin,
+    world::{WorldPlugin, WorldRenderPlugin},
+};
 
+/// The world simulation, runs without a window so it can be hosted by a headless server
 pub struct GamePlugin;
 
+/// Drawing the world and playing in it, needs `DefaultPlugins`
+pub struct GameRenderPlugin;
+
+pub mod blocks;
 mod camera;
+pub mod commands;
+pub mod falling;
+pub mod fluid;
+pub mod save;
+pub mod sky;
+pub mod structures;
 mod systems;
-mod world;
+pub mod tick;
+pub mod world;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<SimulationState>()
             // plugins
-            .add_plugins((WorldPlugin, CameraPlugin))
-            .add_systems(Update, toggle_simulation.run_if(in_state(AppState::Game)));
+            .add_plugins((
+                WorldPlugin,
+                SkyPlugin,
+                SavePlugin,
+                FluidPlugin,
+                TickPlugin,
+                BlocksPlugin,
+                FallingPlugin,
+                StructuresPlugin,
+                CommandsPlugin,
+            ));
+    }
+}
+
+impl Plugin for GameRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            WorldRenderPlugin,
+            CameraPlugin,
+            SkyRenderPlugin,
+            FallingRenderPlugin,
+        ))
+        .add_systems(Update, toggle_simulation.run_if(in_state(AppState::Game)));
     }
 }
 
@@ -0,0 +1,193 @@
+use std::io;
+
+use bevy::prelude::*;
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+
+use crate::{
+    bytes::{invalid_data, write_runs, ByteReader},
+    game::world::{access::BlockChange, components::BlockType, CHUNK_SIZE},
+};
+
+/// Number of voxels in a chunk
+pub const CHUNK_VOLUME: usize = (CHUNK_SIZE.x * CHUNK_SIZE.y * CHUNK_SIZE.z) as usize;
+
+/// First byte of every encoded chunk, bumped whenever the encoding changes.
+/// Chunks are saved in this format, a chunk of another version is refused rather than misread.
+pub const CHUNK_FORMAT_VERSION: u8 = 1;
+
+/// Deflate level used for chunk payloads, higher barely helps on run length encoded data
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// Index of a local voxel position in a chunk's block list
+pub fn chunk_index(local_position: IVec3) -> usize {
+    ((local_position.x * CHUNK_SIZE.y + local_position.y) * CHUNK_SIZE.z + local_position.z)
+        as usize
+}
+
+/// Local voxel position of an index into a chunk's block list
+pub fn chunk_local_position(index: usize) -> IVec3 {
+    let index = index as i32;
+    IVec3::new(
+        index / (CHUNK_SIZE.y * CHUNK_SIZE.z),
+        index / CHUNK_SIZE.z % CHUNK_SIZE.y,
+        index % CHUNK_SIZE.z,
+    )
+}
+
+/// Compresses the blocks of a chunk, `CHUNK_VOLUME` of them in `chunk_index` order, for saves and
+/// for sending to clients. Blocks are written as indices into a palette of the distinct blocks,
+/// those are run length encoded and the whole thing is deflated behind `CHUNK_FORMAT_VERSION`.
+pub fn encode_chunk(blocks: &[BlockChange]) -> Vec<u8> {
+    let mut palette: Vec<BlockChange> = vec![];
+    let indices: Vec<u16> = blocks
+        .iter()
+        .map(|block| {
+            let index = palette.iter().position(|existing| existing == block);
+            index.unwrap_or_else(|| {
+                palette.push(*block);
+                palette.len() - 1
+            }) as u16
+        })
+        .collect();
+
+    let mut bytes = (palette.len() as u16).to_le_bytes().to_vec();
+    for block in &palette {
+        write_block_change(&mut bytes, *block);
+    }
+    write_runs(&mut bytes, &indices);
+    let mut encoded = vec![CHUNK_FORMAT_VERSION];
+    encoded.extend(compress_to_vec(&bytes, COMPRESSION_LEVEL));
+    encoded
+}
+
+/// Blocks of a chunk written by `encode_chunk`
+pub fn decode_chunk(bytes: &[u8]) -> io::Result<Vec<BlockChange>> {
+    let Some((&CHUNK_FORMAT_VERSION, bytes)) = bytes.split_first() else {
+        return Err(invalid_data("unknown chunk format"));
+    };
+    // the worst case is a palette and a run for every voxel
+    let limit = 2 + CHUNK_VOLUME * 6;
+    let bytes = decompress_to_vec_with_limit(bytes, limit)
+        .map_err(|_| invalid_data("chunk does not decompress"))?;
+    let mut reader = ByteReader::new(&bytes);
+    let palette = (0..reader.u16()?)
+        .map(|_| read_block_change(&mut reader))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut blocks = Vec::with_capacity(CHUNK_VOLUME);
+    while !reader.is_empty() {
+        let length = reader.u16()? as usize;
+        let block = palette
+            .get(reader.u16()? as usize)
+            .ok_or_else(|| invalid_data("chunk block is not in its palette"))?;
+        if blocks.len() + length > CHUNK_VOLUME {
+            return Err(invalid_data("chunk has too many blocks"));
+        }
+        blocks.extend(std::iter::repeat(*block).take(length));
+    }
+    if blocks.len() != CHUNK_VOLUME {
+        return Err(invalid_data("chunk has too few blocks"));
+    }
+    Ok(blocks)
+}
+
+/// Writes a block as two bytes, its place in `BlockType::ALL` counted from one and its state,
+/// air is two zeroes
+pub fn write_block_change(bytes: &mut Vec<u8>, block: BlockChange) {
+    match block {
+        None => bytes.extend_from_slice(&[0, 0]),
+        Some((block, state)) => {
+            let index = BlockType::ALL.iter().position(|other| *other == block);
+            bytes.extend_from_slice(&[index.unwrap_or_default() as u8 + 1, state]);
+        }
+    }
+}
+
+pub fn read_block_change(reader: &mut ByteReader) -> io::Result<BlockChange> {
+    let index = reader.u8()?;
+    let state = reader.u8()?;
+    if index == 0 {
+        return Ok(None);
+    }
+    let block = BlockType::ALL
+        .get(index as usize - 1)
+        .ok_or_else(|| invalid_data("unknown block"))?;
+    Ok(Some((*block, state)))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    /// Layers of stone, dirt and grass with a pond and a few ores, like the generator makes
+    fn ground() -> Vec<BlockChange> {
+        (0..CHUNK_VOLUME)
+            .map(|index| {
+                let position = chunk_local_position(index);
+                match position.y {
+                    0..=5 if (position.x * 7 + position.z * 3) % 23 == 0 => {
+                        Some((BlockType::CoalOre, 0))
+                    }
+                    0..=5 => Some((BlockType::Stone, 0)),
+                    6 | 7 => Some((BlockType::Dirt, 0)),
+                    8 if position.x < 4 && position.z < 4 => {
+                        Some((BlockType::Water, (position.x + position.z) as u8 % 4))
+                    }
+                    8 => Some((BlockType::Grass, 0)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn index_and_local_position_round_trip() {
+        for index in [0, 1, CHUNK_SIZE.z as usize, 1234, CHUNK_VOLUME - 1] {
+            assert_eq!(chunk_index(chunk_local_position(index)), index);
+        }
+        assert_eq!(
+            chunk_local_position(CHUNK_VOLUME - 1),
+            CHUNK_SIZE - IVec3::ONE
+        );
+    }
+
+    #[test]
+    fn chunks_decode_to_what_was_encoded() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let noise: Vec<BlockChange> = (0..CHUNK_VOLUME)
+            .map(|_| {
+                let block = BlockType::ALL[rng.gen_range(0..BlockType::ALL.len())];
+                rng.gen_bool(0.5).then_some((block, rng.gen_range(0..8)))
+            })
+            .collect();
+        for blocks in [vec![None; CHUNK_VOLUME], ground(), noise] {
+            assert_eq!(decode_chunk(&encode_chunk(&blocks)).unwrap(), blocks);
+        }
+    }
+
+    #[test]
+    fn ground_compresses_well() {
+        let bytes = encode_chunk(&ground());
+        // two bytes a voxel, block and state, is what storing it raw would take
+        let raw_size = CHUNK_VOLUME * 2;
+        assert!(bytes.len() * 20 < raw_size, "{} bytes", bytes.len());
+    }
+
+    #[test]
+    fn broken_chunks_are_refused() {
+        let mut short = ground();
+        short.pop();
+        assert!(decode_chunk(&encode_chunk(&short)).is_err());
+        assert!(decode_chunk(&[1, 2, 3]).is_err());
+        assert!(decode_chunk(&[]).is_err());
+    }
+
+    #[test]
+    fn chunks_of_another_format_are_refused() {
+        let mut bytes = encode_chunk(&ground());
+        assert_eq!(bytes[0], CHUNK_FORMAT_VERSION);
+        bytes[0] = CHUNK_FORMAT_VERSION + 1;
+        assert!(decode_chunk(&bytes).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+mod systems;
+
+pub mod chunk;
+pub mod resources;
+
+use bevy::prelude::*;
+
+use crate::{
+    events::SaveWorldEvent,
+    game::{
+        fluid::systems::seed_fluid_updates,
+        world::{
+            resources::VoxelWorld,
+            systems::{light_world, spawn_world},
+        },
+    },
+    AppState,
+};
+
+use self::{resources::*, systems::*};
+
+use super::SimulationState;
+
+/// Directory every world is saved in, a directory each
+pub const SAVES_DIRECTORY_NAME: &str = "saves";
+/// Name of the file holding world wide state inside a save directory
+pub const METADATA_FILE_NAME: &str = "world.txt";
+/// Directory inside a save directory holding a file per chunk
+pub const CHUNK_DIRECTORY_NAME: &str = "chunks";
+
+/// Systems loading chunks from and saving the world to the save directory.
+/// A client on a server leaves these out, the world it shows is the server's.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SaveSet;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveWorldEvent>()
+            .init_resource::<SaveDirectory>()
+            // the world is generated from the saved seed
+            .add_systems(
+                OnEnter(AppState::Game),
+                load_world_metadata.before(spawn_world),
+            )
+            .add_systems(
+                Update,
+                load_chunks
+                    // the saved blocks have to be in place before the world is lit and its fluids found
+                    .before(light_world)
+                    .before(seed_fluid_updates)
+                    .in_set(SaveSet)
+                    .run_if(in_state(AppState::Game).and_then(resource_added::<VoxelWorld>())),
+            )
+            // save whenever the game is paused, when asked to and when it closes
+            .add_systems(
+                OnEnter(SimulationState::Paused),
+                (save_world_metadata, save_chunks).in_set(SaveSet),
+            )
+            .add_systems(
+                Last,
+                (save_world_metadata, save_chunks).in_set(SaveSet).run_if(
+                    in_state(AppState::Game).and_then(
+                        on_event::<bevy::app::AppExit>().or_else(on_event::<SaveWorldEvent>()),
+                    ),
+                ),
+            );
+    }
+}
@@ -0,0 +1,222 @@
+use std::{fmt::Write, fs, io, path::PathBuf};
+
+use bevy::prelude::*;
+
+use crate::{
+    bytes::{from_hex, to_hex},
+    game::{
+        sky::resources::WorldTime,
+        world::{access::BlockChange, generation::TerrainShape},
+    },
+};
+
+use super::chunk::{decode_chunk, encode_chunk};
+
+/// Where the current world is saved to
+#[derive(Resource, Debug, Clone)]
+pub struct SaveDirectory(pub PathBuf);
+
+impl Default for SaveDirectory {
+    fn default() -> Self {
+        SaveDirectory(PathBuf::from(super::SAVES_DIRECTORY_NAME).join("world"))
+    }
+}
+
+/// World wide state saved next to the chunks, stored as `key=value` lines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct WorldMetadata {
+    pub seed: u64,
+    pub terrain: TerrainShape,
+    pub world_time: WorldTime,
+}
+
+impl WorldMetadata {
+    /// Reads metadata written by `to_text`, keys that are missing or unreadable keep their default
+    pub fn from_text(text: &str) -> Self {
+        let mut metadata = WorldMetadata::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "seed" => {
+                    if let Ok(seed) = value.parse() {
+                        metadata.seed = seed;
+                    }
+                }
+                "terrain" => match TerrainShape::from_name(value) {
+                    Some(terrain) => metadata.terrain = terrain,
+                    None => warn!("Unknown terrain {value}"),
+                },
+                "day" => {
+                    if let Ok(day) = value.parse() {
+                        metadata.world_time.day = day;
+                    }
+                }
+                "time_of_day" => {
+                    if let Ok(time_of_day) = value.parse::<f32>() {
+                        metadata.world_time.time_of_day = time_of_day.rem_euclid(1.0);
+                    }
+                }
+                _ => warn!("Unknown world metadata key {key}"),
+            }
+        }
+        metadata
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        // writing to a string can not fail
+        let _ = writeln!(text, "seed={}", self.seed);
+        let _ = writeln!(text, "terrain={}", self.terrain.name());
+        let _ = writeln!(text, "day={}", self.world_time.day);
+        let _ = writeln!(text, "time_of_day={}", self.world_time.time_of_day);
+        text
+    }
+
+    pub fn load(directory: &SaveDirectory) -> io::Result<Self> {
+        let text = fs::read_to_string(directory.0.join(super::METADATA_FILE_NAME))?;
+        Ok(WorldMetadata::from_text(&text))
+    }
+
+    pub fn save(&self, directory: &SaveDirectory) -> io::Result<()> {
+        fs::create_dir_all(&directory.0)?;
+        fs::write(directory.0.join(super::METADATA_FILE_NAME), self.to_text())
+    }
+}
+
+/// State of one chunk saved in its own file, stored as `key=value` lines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChunkData {
+    /// every block of the chunk in `chunk_index` order, the chunk is generated again when missing
+    pub blocks: Option<Vec<BlockChange>>,
+    /// block updates waiting in the chunk, as ticks from now and a position inside the chunk
+    pub scheduled_updates: Vec<(u64, IVec3)>,
+}
+
+impl ChunkData {
+    /// Reads chunk data written by `to_text`, lines that are unreadable are skipped
+    pub fn from_text(text: &str) -> Self {
+        let mut data = ChunkData::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "update" => {
+                    let numbers: Vec<i64> = value
+                        .split_whitespace()
+                        .filter_map(|number| number.parse().ok())
+                        .collect();
+                    if let [delay, x, y, z] = numbers[..] {
+                        data.scheduled_updates.push((
+                            delay.max(0) as u64,
+                            IVec3::new(x as i32, y as i32, z as i32),
+                        ));
+                    }
+                }
+                // compressed the way chunks are also sent to clients, as hex
+                "blocks" => match from_hex(value.trim()).and_then(|bytes| decode_chunk(&bytes)) {
+                    Ok(blocks) => data.blocks = Some(blocks),
+                    Err(error) => warn!("Skipping unreadable chunk blocks: {error}"),
+                },
+                _ => warn!("Unknown chunk data key {key}"),
+            }
+        }
+        data
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        if let Some(blocks) = &self.blocks {
+            let _ = writeln!(text, "blocks={}", to_hex(&encode_chunk(blocks)));
+        }
+        for (delay, position) in &self.scheduled_updates {
+            let _ = writeln!(
+                text,
+                "update={delay} {} {} {}",
+                position.x, position.y, position.z
+            );
+        }
+        text
+    }
+
+    /// File of the chunk at `chunk_position` inside a save directory
+    pub fn path(directory: &SaveDirectory, chunk_position: IVec3) -> PathBuf {
+        directory.0.join(super::CHUNK_DIRECTORY_NAME).join(format!(
+            "{}_{}_{}.txt",
+            chunk_position.x, chunk_position.y, chunk_position.z
+        ))
+    }
+
+    pub fn load(directory: &SaveDirectory, chunk_position: IVec3) -> io::Result<Self> {
+        let text = fs::read_to_string(ChunkData::path(directory, chunk_position))?;
+        Ok(ChunkData::from_text(&text))
+    }
+
+    pub fn save(&self, directory: &SaveDirectory, chunk_position: IVec3) -> io::Result<()> {
+        fs::create_dir_all(directory.0.join(super::CHUNK_DIRECTORY_NAME))?;
+        fs::write(ChunkData::path(directory, chunk_position), self.to_text())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{save::chunk::CHUNK_VOLUME, world::components::BlockType};
+
+    fn chunk_data() -> ChunkData {
+        let mut blocks = vec![None; CHUNK_VOLUME];
+        blocks[0] = Some((BlockType::Stone, 0));
+        blocks[1] = Some((BlockType::Water, 3));
+        blocks[CHUNK_VOLUME - 1] = Some((BlockType::Crop, 5));
+        ChunkData {
+            blocks: Some(blocks),
+            scheduled_updates: vec![(0, IVec3::new(1, 2, 3)), (40, IVec3::ZERO)],
+        }
+    }
+
+    #[test]
+    fn chunk_data_round_trips_through_text() {
+        let data = chunk_data();
+        assert_eq!(ChunkData::from_text(&data.to_text()), data);
+        assert_eq!(ChunkData::from_text(""), ChunkData::default());
+    }
+
+    #[test]
+    fn unreadable_blocks_leave_the_chunk_to_be_generated() {
+        let text = "blocks=zz\nupdate=4 1 1 1\n";
+        let data = ChunkData::from_text(text);
+        assert_eq!(data.blocks, None);
+        assert_eq!(data.scheduled_updates, vec![(4, IVec3::ONE)]);
+    }
+
+    #[test]
+    fn chunk_data_round_trips_through_a_save_directory() {
+        let directory = SaveDirectory(
+            std::env::temp_dir().join(format!("voxel_game_chunk_data_{}", std::process::id())),
+        );
+        let data = chunk_data();
+        let chunk_position = IVec3::new(2, 0, -1);
+        data.save(&directory, chunk_position).unwrap();
+        let loaded = ChunkData::load(&directory, chunk_position);
+        let missing = ChunkData::load(&directory, IVec3::ZERO);
+        fs::remove_dir_all(&directory.0).unwrap();
+        assert_eq!(loaded.unwrap(), data);
+        assert_eq!(missing.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn metadata_round_trips_through_text() {
+        let metadata = WorldMetadata {
+            seed: 1234,
+            terrain: TerrainShape::default(),
+            world_time: WorldTime {
+                day: 3,
+                time_of_day: 0.25,
+            },
+        };
+        assert_eq!(WorldMetadata::from_text(&metadata.to_text()), metadata);
+    }
+}
@@ -0,0 +1,189 @@
+use std::io;
+
+use bevy::prelude::*;
+
+use crate::game::{
+    blocks::resources::BlockUpdateQueue,
+    sky::resources::WorldTime,
+    tick::resources::{WorldRng, WorldTick},
+    world::{
+        components::Voxel,
+        resources::{VoxelWorld, WorldSeed, WorldTerrain},
+        CHUNK_SIZE,
+    },
+};
+
+use super::{
+    chunk::{chunk_index, CHUNK_VOLUME},
+    resources::{ChunkData, SaveDirectory, WorldMetadata},
+};
+
+/// Loads the seed, terrain and clock of a saved world. A new world keeps the seed and terrain it was created with.
+pub fn load_world_metadata(
+    mut commands: Commands,
+    save_directory: Res<SaveDirectory>,
+    world_seed: Res<WorldSeed>,
+) {
+    let seed = match WorldMetadata::load(&save_directory) {
+        Ok(metadata) => {
+            commands.insert_resource(metadata.world_time);
+            commands.insert_resource(WorldTerrain(metadata.terrain));
+            info!("Loaded world from {}", save_directory.0.display());
+            WorldSeed(metadata.seed)
+        }
+        // a brand new world
+        Err(error) if error.kind() == io::ErrorKind::NotFound => *world_seed,
+        Err(error) => {
+            error!("Could not load world metadata: {error}");
+            *world_seed
+        }
+    };
+    commands.insert_resource(WorldRng::from_seed(seed.0));
+    commands.insert_resource(seed);
+}
+
+pub fn save_world_metadata(
+    save_directory: Res<SaveDirectory>,
+    world_seed: Res<WorldSeed>,
+    world_terrain: Res<WorldTerrain>,
+    world_time: Res<WorldTime>,
+) {
+    let metadata = WorldMetadata {
+        seed: world_seed.0,
+        terrain: world_terrain.0,
+        world_time: *world_time,
+    };
+    match metadata.save(&save_directory) {
+        Ok(()) => info!("Saved world to {}", save_directory.0.display()),
+        Err(error) => error!("Could not save world: {error}"),
+    }
+}
+
+/// Reads back the saved state of every chunk in a freshly spawned world.
+/// Saved blocks replace the generated ones before the world is lit, then their scheduled updates are restored.
+pub fn load_chunks(
+    save_directory: Res<SaveDirectory>,
+    voxel_world: Res<VoxelWorld>,
+    world_tick: Res<WorldTick>,
+    mut update_queue: ResMut<BlockUpdateQueue>,
+    mut voxel_query: Query<&mut Voxel>,
+) {
+    for (chunk_position, chunk) in voxel_world.chunks.iter() {
+        let data = match ChunkData::load(&save_directory, *chunk_position) {
+            Ok(data) => data,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+            Err(error) => {
+                error!("Could not load chunk {chunk_position}: {error}");
+                continue;
+            }
+        };
+        if let Some(blocks) = data.blocks {
+            for (local_position, entity) in chunk.blocks.iter() {
+                let Ok(mut voxel) = voxel_query.get_mut(*entity) else {
+                    continue;
+                };
+                let block = blocks[chunk_index(*local_position)];
+                voxel.solid = block.is_some();
+                if let Some((block, state)) = block {
+                    voxel.block = block;
+                    voxel.state = state;
+                } else {
+                    voxel.state = 0;
+                }
+            }
+        }
+        for (delay, local_position) in data.scheduled_updates {
+            update_queue.schedule(
+                *chunk_position * CHUNK_SIZE + local_position,
+                world_tick.0 + delay,
+            );
+        }
+    }
+}
+
+pub fn save_chunks(
+    save_directory: Res<SaveDirectory>,
+    voxel_world: Res<VoxelWorld>,
+    world_tick: Res<WorldTick>,
+    update_queue: Res<BlockUpdateQueue>,
+    voxel_query: Query<&Voxel>,
+) {
+    for (chunk_position, chunk) in voxel_world.chunks.iter() {
+        let mut blocks = vec![None; CHUNK_VOLUME];
+        for (local_position, entity) in chunk.blocks.iter() {
+            if let Ok(voxel) = voxel_query.get(*entity) {
+                blocks[chunk_index(*local_position)] = voxel.block_change();
+            }
+        }
+        let data = ChunkData {
+            blocks: Some(blocks),
+            scheduled_updates: update_queue.chunk_updates(*chunk_position, world_tick.0),
+        };
+        if let Err(error) = data.save(&save_directory, *chunk_position) {
+            error!("Could not save chunk {chunk_position}: {error}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::game::world::{
+        access::{BlockChange, VoxelAccess, WorldVoxels},
+        components::BlockType,
+        resources::NeighbourUpdates,
+    };
+
+    fn app(directory: &SaveDirectory) -> App {
+        let mut app = App::new();
+        app.insert_resource(directory.clone())
+            .insert_resource(WorldTick(100))
+            .init_resource::<BlockUpdateQueue>()
+            .init_resource::<NeighbourUpdates>();
+        VoxelWorld::spawn_test_world(&mut app.world, IVec3::splat(4));
+        app
+    }
+
+    fn block_at(app: &mut App, position: IVec3) -> BlockChange {
+        app.world.run_system_once(move |voxels: WorldVoxels| {
+            voxels.voxel(position).unwrap().block_change()
+        })
+    }
+
+    #[test]
+    fn edits_and_scheduled_updates_survive_a_reload() {
+        let directory = SaveDirectory(
+            std::env::temp_dir().join(format!("voxel_game_save_chunks_{}", std::process::id())),
+        );
+        let edited = IVec3::new(1, 2, 3);
+        let mut saved = app(&directory);
+        saved.world.run_system_once(move |mut voxels: WorldVoxels| {
+            voxels.apply_change(edited, Some((BlockType::Water, 2)));
+        });
+        saved
+            .world
+            .resource_mut::<BlockUpdateQueue>()
+            .schedule(edited, 105);
+        saved.world.run_system_once(save_chunks);
+
+        // a world loaded later on, its generated blocks are replaced by the saved ones
+        let mut loaded = app(&directory);
+        loaded.world.resource_mut::<WorldTick>().0 = 7;
+        loaded
+            .world
+            .run_system_once(move |mut voxels: WorldVoxels| {
+                voxels.apply_change(IVec3::ZERO, Some((BlockType::Stone, 0)));
+            });
+        loaded.world.run_system_once(load_chunks);
+        fs::remove_dir_all(&directory.0).unwrap();
+
+        assert_eq!(block_at(&mut loaded, edited), Some((BlockType::Water, 2)));
+        assert_eq!(block_at(&mut loaded, IVec3::ZERO), None);
+        let queue = loaded.world.resource::<BlockUpdateQueue>();
+        assert_eq!(queue.chunk_updates(IVec3::ZERO, 7), vec![(5, edited)]);
+    }
+}
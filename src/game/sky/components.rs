@@ -0,0 +1,7 @@
+use bevy::prelude::Component;
+
+#[derive(Component)]
+pub struct Sun;
+
+#[derive(Component)]
+pub struct Moon;
@@ -0,0 +1,163 @@
+pub mod components;
+pub mod resources;
+pub mod systems;
+
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::AppState;
+
+use self::{resources::*, systems::*};
+
+use super::tick::{systems::advance_world_tick, WorldTickSet};
+
+/// Sun illuminance in lux at noon
+pub const SUN_ILLUMINANCE: f32 = 10000.0;
+/// Moon illuminance in lux at midnight
+pub const MOON_ILLUMINANCE: f32 = 400.0;
+
+const DAY_SKY_COLOR: Color = Color::rgb(0.47, 0.66, 1.0);
+const NIGHT_SKY_COLOR: Color = Color::rgb(0.01, 0.01, 0.04);
+const TWILIGHT_SKY_COLOR: Color = Color::rgb(0.93, 0.52, 0.32);
+const NOON_SUN_COLOR: Color = Color::rgb(1.0, 0.98, 0.92);
+const LOW_SUN_COLOR: Color = Color::rgb(1.0, 0.55, 0.25);
+const MOON_COLOR: Color = Color::rgb(0.6, 0.7, 1.0);
+
+/// The world clock
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldTime>()
+            .init_resource::<DayNightSettings>()
+            .add_systems(
+                FixedUpdate,
+                advance_world_time
+                    .after(advance_world_tick)
+                    .in_set(WorldTickSet::Begin),
+            );
+    }
+}
+
+/// The sun, moon and sky color following the world clock
+pub struct SkyRenderPlugin;
+
+impl Plugin for SkyRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), spawn_sky)
+            .add_systems(Update, update_sky.run_if(in_state(AppState::Game)));
+    }
+}
+
+/// Everything the scene lighting needs at one moment of the day
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkyLighting {
+    /// direction the sun light travels in
+    pub sun_direction: Vec3,
+    pub sun_color: Color,
+    pub sun_illuminance: f32,
+    /// direction the moon light travels in
+    pub moon_direction: Vec3,
+    pub moon_illuminance: f32,
+    pub ambient_color: Color,
+    pub ambient_brightness: f32,
+    pub clear_color: Color,
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let from = Vec4::from(from.as_rgba_f32());
+    let to = Vec4::from(to.as_rgba_f32());
+    Color::from(from.lerp(to, amount))
+}
+
+/// The sky at `time_of_day`, where 0.0 is midnight, 0.25 is dawn, 0.5 is noon and 0.75 is dusk
+pub fn sky_lighting(time_of_day: f32) -> SkyLighting {
+    let angle = (time_of_day.rem_euclid(1.0) - 0.25) * TAU;
+    // the sun rises in +x and sets in -x, tilted a little towards +z so noon shadows are not straight down
+    let towards_sun = Vec3::new(angle.cos(), angle.sin(), 0.3).normalize();
+    let elevation = towards_sun.y;
+
+    let daylight = smoothstep(-0.1, 0.15, elevation);
+    let moonlight = smoothstep(-0.1, 0.15, -elevation);
+    // strongest when the sun sits on the horizon
+    let twilight = 1.0 - smoothstep(0.0, 0.3, elevation.abs());
+
+    let sky_color = mix(NIGHT_SKY_COLOR, DAY_SKY_COLOR, daylight);
+    let clear_color = mix(sky_color, TWILIGHT_SKY_COLOR, twilight * 0.6);
+
+    SkyLighting {
+        sun_direction: -towards_sun,
+        sun_color: mix(
+            LOW_SUN_COLOR,
+            NOON_SUN_COLOR,
+            smoothstep(0.0, 0.4, elevation),
+        ),
+        sun_illuminance: SUN_ILLUMINANCE * daylight,
+        moon_direction: towards_sun,
+        moon_illuminance: MOON_ILLUMINANCE * moonlight,
+        ambient_color: mix(MOON_COLOR, Color::WHITE, daylight),
+        ambient_brightness: 0.05 + 0.25 * daylight,
+        clear_color,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_is_overhead_at_noon_and_below_at_midnight() {
+        let noon = sky_lighting(0.5);
+        let midnight = sky_lighting(0.0);
+        // the light travels down at noon and up from below the world at midnight
+        assert!(noon.sun_direction.y < -0.9);
+        assert!(midnight.sun_direction.y > 0.9);
+        assert_eq!(noon.moon_direction, -noon.sun_direction);
+    }
+
+    #[test]
+    fn daylight_comes_from_the_sun_and_night_light_from_the_moon() {
+        let noon = sky_lighting(0.5);
+        assert_eq!(noon.sun_illuminance, SUN_ILLUMINANCE);
+        assert_eq!(noon.moon_illuminance, 0.0);
+        assert_eq!(noon.clear_color, DAY_SKY_COLOR);
+
+        let midnight = sky_lighting(0.0);
+        assert_eq!(midnight.sun_illuminance, 0.0);
+        assert_eq!(midnight.moon_illuminance, MOON_ILLUMINANCE);
+        assert_eq!(midnight.clear_color, NIGHT_SKY_COLOR);
+        assert!(midnight.ambient_brightness < noon.ambient_brightness);
+    }
+
+    #[test]
+    fn sunrise_is_tinted_and_the_sun_rises_along_positive_x() {
+        let dawn = sky_lighting(0.25);
+        assert!(dawn.sun_direction.x < 0.0);
+        assert!(dawn.sun_illuminance > 0.0 && dawn.sun_illuminance < SUN_ILLUMINANCE);
+        assert_ne!(dawn.clear_color, DAY_SKY_COLOR);
+        assert_ne!(dawn.clear_color, NIGHT_SKY_COLOR);
+    }
+
+    #[test]
+    fn time_of_day_wraps_around() {
+        assert_eq!(sky_lighting(1.25), sky_lighting(0.25));
+        assert_eq!(sky_lighting(-0.5), sky_lighting(0.5));
+    }
+
+    #[test]
+    fn world_time_rolls_over_into_the_next_day() {
+        let mut world_time = WorldTime {
+            day: 3,
+            time_of_day: 0.9,
+        };
+        world_time.advance(0.2);
+        assert_eq!(world_time.day, 4);
+        assert!((world_time.time_of_day - 0.1).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+
+/// Time of the world, kept separate from real time so it stops while paused
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct WorldTime {
+    /// whole days since the world was created
+    pub day: u32,
+    /// how far through the current day, 0.0 is midnight and 0.5 is noon
+    pub time_of_day: f32,
+}
+
+impl Default for WorldTime {
+    fn default() -> Self {
+        // start the world in the morning
+        WorldTime {
+            day: 0,
+            time_of_day: 0.3,
+        }
+    }
+}
+
+impl WorldTime {
+    /// Moves the clock on by `days`, rolling over into the next day as needed
+    pub fn advance(&mut self, days: f32) {
+        let time = self.time_of_day + days;
+        self.day += time.floor() as u32;
+        self.time_of_day = time.rem_euclid(1.0);
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct DayNightSettings {
+    /// real seconds a full day takes while the simulation is running
+    pub day_length_seconds: f32,
+}
+
+impl Default for DayNightSettings {
+    fn default() -> Self {
+        DayNightSettings {
+            day_length_seconds: 600.0,
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+
+use super::{
+    components::{Moon, Sun},
+    resources::{DayNightSettings, WorldTime},
+    sky_lighting,
+};
+
+type SkyLights = Or<(With<Sun>, With<Moon>)>;
+
+pub fn spawn_sky(mut commands: Commands) {
+    let sun = (
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                shadows_enabled: true,
+                ..default()
+            },
+            ..default()
+        },
+        Sun,
+        Name::new("Sun"),
+    );
+    let moon = (
+        DirectionalLightBundle {
+            directional_light: DirectionalLight {
+                color: super::MOON_COLOR,
+                ..default()
+            },
+            ..default()
+        },
+        Moon,
+        Name::new("Moon"),
+    );
+
+    commands.spawn(sun);
+    commands.spawn(moon);
+}
+
+/// Moves the world clock on by one world tick, however long the tick rate makes that
+pub fn advance_world_time(
+    time: Res<Time>,
+    settings: Res<DayNightSettings>,
+    mut world_time: ResMut<WorldTime>,
+) {
+    world_time.advance(time.delta_seconds() / settings.day_length_seconds);
+}
+
+pub fn update_sky(
+    world_time: Res<WorldTime>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut clear_color: ResMut<ClearColor>,
+    mut light_query: Query<(&mut DirectionalLight, &mut Transform, Has<Sun>), SkyLights>,
+) {
+    let sky = sky_lighting(world_time.time_of_day);
+
+    for (mut light, mut transform, is_sun) in light_query.iter_mut() {
+        if is_sun {
+            light.color = sky.sun_color;
+            light.illuminance = sky.sun_illuminance;
+            *transform = Transform::default().looking_to(sky.sun_direction, Vec3::Y);
+        } else {
+            light.illuminance = sky.moon_illuminance;
+            *transform = Transform::default().looking_to(sky.moon_direction, Vec3::Y);
+        }
+    }
+    ambient_light.color = sky.ambient_color;
+    ambient_light.brightness = sky.ambient_brightness;
+    clear_color.0 = sky.clear_color;
+}
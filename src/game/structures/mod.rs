@@ -0,0 +1,39 @@
+mod systems;
+
+pub mod schematic;
+pub mod vox;
+
+use bevy::prelude::*;
+
+use crate::{
+    events::{CopySchematicEvent, PasteSchematicEvent},
+    AppState,
+};
+
+use self::systems::*;
+
+use super::world::systems::set_blocks;
+
+/// Directory next to the game `/copy` and `/paste` keep their schematic files in
+pub const SCHEMATIC_DIRECTORY_NAME: &str = "schematics";
+/// Most voxels `/copy` takes at once, and a schematic file may hold
+pub const MAX_SCHEMATIC_VOLUME: i64 = 1 << 18;
+/// Longest side a schematic file has room for
+pub const MAX_SCHEMATIC_SIDE: i64 = u16::MAX as i64;
+
+pub struct StructuresPlugin;
+
+impl Plugin for StructuresPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CopySchematicEvent>()
+            .add_event::<PasteSchematicEvent>()
+            .add_systems(
+                Update,
+                // copies see the world after this frame's edits
+                (copy_schematics, paste_schematics)
+                    .chain()
+                    .after(set_blocks)
+                    .run_if(in_state(AppState::Game)),
+            );
+    }
+}
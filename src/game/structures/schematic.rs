@@ -0,0 +1,358 @@
+use std::{fs, io, path::Path};
+
+use bevy::prelude::*;
+
+use super::{vox, MAX_SCHEMATIC_VOLUME};
+
+use crate::bytes::{invalid_data, write_runs, ByteReader};
+
+use crate::game::world::{
+    access::{BlockChange, VoxelAccess},
+    components::BlockType,
+};
+
+fn is_vox_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("vox"))
+}
+
+/// First bytes of every schematic file
+pub const SCHEMATIC_MAGIC: &[u8; 4] = b"VXSC";
+pub const SCHEMATIC_VERSION: u8 = 1;
+
+/// How a schematic is turned before it is pasted, the mirror is applied first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SchematicTransform {
+    /// quarter turns clockwise around the y axis, seen from above
+    pub quarter_turns: u8,
+    /// flips the schematic along the x axis
+    pub mirror_x: bool,
+}
+
+/// A box of voxels copied out of the world
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schematic {
+    pub size: IVec3,
+    /// x major, then y, then z
+    blocks: Vec<BlockChange>,
+}
+
+impl Schematic {
+    /// An all air schematic
+    pub fn new(size: IVec3) -> Self {
+        Schematic {
+            size,
+            blocks: vec![None; (size.x * size.y * size.z).max(0) as usize],
+        }
+    }
+
+    fn index(&self, position: IVec3) -> Option<usize> {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self.size).any() {
+            return None;
+        }
+        Some(((position.x * self.size.y + position.y) * self.size.z + position.z) as usize)
+    }
+
+    pub fn set(&mut self, position: IVec3, block: BlockChange) {
+        if let Some(index) = self.index(position) {
+            self.blocks[index] = block;
+        }
+    }
+
+    /// Copies the voxels between `min` and `max`, both inclusive.
+    /// Voxels outside the loaded world are copied as air.
+    pub fn copy(world: &dyn VoxelAccess, min: IVec3, max: IVec3) -> Self {
+        let (min, max) = (min.min(max), min.max(max));
+        let mut schematic = Schematic::new(max - min + IVec3::ONE);
+        for x in 0..schematic.size.x {
+            for y in 0..schematic.size.y {
+                for z in 0..schematic.size.z {
+                    let position = IVec3::new(x, y, z);
+                    let block = world
+                        .voxel(min + position)
+                        .filter(|voxel| voxel.solid)
+                        .map(|voxel| (voxel.block, voxel.state));
+                    schematic.set(position, block);
+                }
+            }
+        }
+        schematic
+    }
+
+    /// Every position inside the schematic with its block
+    pub fn blocks(&self) -> impl Iterator<Item = (IVec3, BlockChange)> + '_ {
+        let size = self.size;
+        self.blocks.iter().enumerate().map(move |(index, block)| {
+            let index = index as i32;
+            let position = IVec3::new(
+                index / (size.y * size.z),
+                index / size.z % size.y,
+                index % size.z,
+            );
+            (position, *block)
+        })
+    }
+
+    /// A copy turned by `transform`, still starting at the origin
+    pub fn transformed(&self, transform: SchematicTransform) -> Self {
+        let mut schematic = self.clone();
+        if transform.mirror_x {
+            let mut mirrored = Schematic::new(schematic.size);
+            for (position, block) in schematic.blocks() {
+                let flipped = IVec3::new(schematic.size.x - 1 - position.x, position.y, position.z);
+                mirrored.set(flipped, block);
+            }
+            schematic = mirrored;
+        }
+        for _ in 0..transform.quarter_turns % 4 {
+            let size = schematic.size;
+            let mut turned = Schematic::new(IVec3::new(size.z, size.y, size.x));
+            for (position, block) in schematic.blocks() {
+                // clockwise from above: +x turns into +z and +z into -x
+                turned.set(
+                    IVec3::new(size.z - 1 - position.z, position.y, position.x),
+                    block,
+                );
+            }
+            schematic = turned;
+        }
+        schematic
+    }
+
+    /// Binary form of the schematic: magic, version, size, a palette of
+    /// block names with their state, then run length encoded palette indices where 0 is air.
+    /// Sides longer than a `u16` holds do not fit.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut palette: Vec<(BlockType, u8)> = vec![];
+        let indices: Vec<u16> = self
+            .blocks
+            .iter()
+            .map(|block| match block {
+                None => 0,
+                Some(entry) => {
+                    let index = palette.iter().position(|existing| existing == entry);
+                    let index = index.unwrap_or_else(|| {
+                        palette.push(*entry);
+                        palette.len() - 1
+                    });
+                    index as u16 + 1
+                }
+            })
+            .collect();
+
+        let mut bytes = SCHEMATIC_MAGIC.to_vec();
+        bytes.push(SCHEMATIC_VERSION);
+        for axis in self.size.to_array() {
+            let axis = u16::try_from(axis).map_err(|_| invalid_data("schematic is too large"))?;
+            bytes.extend_from_slice(&axis.to_le_bytes());
+        }
+        bytes.extend_from_slice(&(palette.len() as u16).to_le_bytes());
+        for (block, state) in &palette {
+            let name = block.name().as_bytes();
+            bytes.push(name.len() as u8);
+            bytes.extend_from_slice(name);
+            bytes.push(*state);
+        }
+        write_runs(&mut bytes, &indices);
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(4)? != SCHEMATIC_MAGIC {
+            return Err(invalid_data("not a schematic"));
+        }
+        let version = reader.u8()?;
+        if version != SCHEMATIC_VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported schematic version {version}"
+            )));
+        }
+        let size = IVec3::new(
+            reader.u16()? as i32,
+            reader.u16()? as i32,
+            reader.u16()? as i32,
+        );
+        if size.as_i64vec3().to_array().iter().product::<i64>() > MAX_SCHEMATIC_VOLUME {
+            return Err(invalid_data("schematic is too large"));
+        }
+        let mut palette = vec![];
+        for _ in 0..reader.u16()? {
+            let length = reader.u8()? as usize;
+            let name = std::str::from_utf8(reader.take(length)?)
+                .map_err(|_| invalid_data("block name is not utf-8"))?;
+            let block = BlockType::from_name(name)
+                .ok_or_else(|| invalid_data(&format!("unknown block {name}")))?;
+            palette.push((block, reader.u8()?));
+        }
+
+        let mut schematic = Schematic::new(size);
+        let mut filled = 0;
+        while filled < schematic.blocks.len() {
+            let run = reader.u16()? as usize;
+            let index = reader.u16()? as usize;
+            let block = match index {
+                0 => None,
+                index => Some(
+                    *palette
+                        .get(index - 1)
+                        .ok_or_else(|| invalid_data("palette index out of range"))?,
+                ),
+            };
+            if filled + run > schematic.blocks.len() {
+                return Err(invalid_data("schematic has too many blocks"));
+            }
+            schematic.blocks[filled..filled + run].fill(block);
+            filled += run;
+        }
+        Ok(schematic)
+    }
+
+    /// Loads a schematic file, or a MagicaVoxel model for paths ending in `.vox`
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if is_vox_path(path) {
+            vox::from_vox(&bytes)
+        } else {
+            Schematic::from_bytes(&bytes)
+        }
+    }
+
+    /// Saves a schematic file, or a MagicaVoxel model for paths ending in `.vox`
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = if is_vox_path(path) {
+            vox::to_vox(self)?
+        } else {
+            self.to_bytes()?
+        };
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 3 wide, 2 high, 2 deep schematic with a different block in every corner on the ground
+    fn corners() -> Schematic {
+        let mut schematic = Schematic::new(IVec3::new(3, 2, 2));
+        schematic.set(IVec3::new(0, 0, 0), Some((BlockType::Stone, 0)));
+        schematic.set(IVec3::new(2, 0, 0), Some((BlockType::Dirt, 0)));
+        schematic.set(IVec3::new(0, 0, 1), Some((BlockType::Log, 0)));
+        schematic.set(IVec3::new(2, 0, 1), Some((BlockType::Water, 3)));
+        schematic
+    }
+
+    fn block_at(schematic: &Schematic, position: IVec3) -> BlockChange {
+        schematic.blocks[schematic.index(position).unwrap()]
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let schematic = corners();
+        assert_eq!(
+            Schematic::from_bytes(&schematic.to_bytes().unwrap()).unwrap(),
+            schematic
+        );
+
+        // more air in a row than one run holds
+        let mut large = Schematic::new(IVec3::new(300, 1, 300));
+        large.set(IVec3::new(299, 0, 299), Some((BlockType::Crop, 7)));
+        assert_eq!(
+            Schematic::from_bytes(&large.to_bytes().unwrap()).unwrap(),
+            large
+        );
+    }
+
+    #[test]
+    fn broken_bytes_are_refused() {
+        let bytes = corners().to_bytes().unwrap();
+        assert!(Schematic::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(Schematic::from_bytes(b"VXSC").is_err());
+        assert!(Schematic::from_bytes(b"not a schematic").is_err());
+    }
+
+    #[test]
+    fn oversized_schematics_are_refused() {
+        // a side that does not fit the size field is not written cut short
+        let long = Schematic::new(IVec3::new(u16::MAX as i32 + 1, 1, 1));
+        assert!(long.to_bytes().is_err());
+        let longest = Schematic::new(IVec3::new(u16::MAX as i32, 1, 1));
+        let bytes = longest.to_bytes().unwrap();
+        assert_eq!(Schematic::from_bytes(&bytes).unwrap(), longest);
+
+        // a header claiming more than a copy can hold is refused before anything is allocated
+        let mut header = SCHEMATIC_MAGIC.to_vec();
+        header.push(SCHEMATIC_VERSION);
+        for _ in 0..3 {
+            header.extend_from_slice(&u16::MAX.to_le_bytes());
+        }
+        header.extend_from_slice(&0u16.to_le_bytes());
+        assert_eq!(
+            Schematic::from_bytes(&header).unwrap_err().to_string(),
+            "schematic is too large"
+        );
+    }
+
+    #[test]
+    fn quarter_turns_go_clockwise() {
+        let schematic = corners();
+        // where the stone corner ends up, and the size, after each number of turns
+        let expected = [
+            (IVec3::new(3, 2, 2), IVec3::new(0, 0, 0)),
+            (IVec3::new(2, 2, 3), IVec3::new(1, 0, 0)),
+            (IVec3::new(3, 2, 2), IVec3::new(2, 0, 1)),
+            (IVec3::new(2, 2, 3), IVec3::new(0, 0, 2)),
+        ];
+        for (quarter_turns, (size, stone)) in expected.into_iter().enumerate() {
+            let transform = SchematicTransform {
+                quarter_turns: quarter_turns as u8,
+                mirror_x: false,
+            };
+            let turned = schematic.transformed(transform);
+            assert_eq!(turned.size, size, "{quarter_turns} turns");
+            assert_eq!(
+                block_at(&turned, stone),
+                Some((BlockType::Stone, 0)),
+                "{quarter_turns} turns"
+            );
+            let solid = turned.blocks().filter(|(_, block)| block.is_some()).count();
+            assert_eq!(solid, 4, "{quarter_turns} turns");
+        }
+        let four_turns = SchematicTransform {
+            quarter_turns: 4,
+            mirror_x: false,
+        };
+        assert_eq!(schematic.transformed(four_turns), schematic);
+    }
+
+    #[test]
+    fn mirror_flips_x_before_turning() {
+        let schematic = corners();
+        let mirrored = schematic.transformed(SchematicTransform {
+            quarter_turns: 0,
+            mirror_x: true,
+        });
+        assert_eq!(
+            block_at(&mirrored, IVec3::new(2, 0, 0)),
+            Some((BlockType::Stone, 0))
+        );
+        assert_eq!(
+            block_at(&mirrored, IVec3::new(0, 0, 1)),
+            Some((BlockType::Water, 3))
+        );
+
+        let mirrored_and_turned = schematic.transformed(SchematicTransform {
+            quarter_turns: 1,
+            mirror_x: true,
+        });
+        let turned_mirror = mirrored.transformed(SchematicTransform {
+            quarter_turns: 1,
+            mirror_x: false,
+        });
+        assert_eq!(mirrored_and_turned, turned_mirror);
+    }
+}
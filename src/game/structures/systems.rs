@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+
+use crate::{
+    events::{CopySchematicEvent, PasteSchematicEvent},
+    game::world::access::{VoxelAccess, WorldVoxels},
+};
+
+use super::schematic::Schematic;
+
+pub fn copy_schematics(
+    mut copy_schematic_events: EventReader<CopySchematicEvent>,
+    voxels: WorldVoxels,
+) {
+    for event in copy_schematic_events.read() {
+        let schematic = Schematic::copy(&voxels, event.min, event.max);
+        match schematic.save(&event.path) {
+            Ok(()) => info!("Saved schematic to {}", event.path.display()),
+            Err(error) => error!("Could not save schematic: {error}"),
+        }
+    }
+}
+
+/// Pastes schematics into the world, relighting around every changed voxel
+pub fn paste_schematics(
+    mut paste_schematic_events: EventReader<PasteSchematicEvent>,
+    mut voxels: WorldVoxels,
+) {
+    for event in paste_schematic_events.read() {
+        let schematic = match Schematic::load(&event.path) {
+            Ok(schematic) => schematic.transformed(event.transform),
+            Err(error) => {
+                error!("Could not load schematic {}: {error}", event.path.display());
+                continue;
+            }
+        };
+        for (position, block) in schematic.blocks() {
+            if block.is_some() || event.include_air {
+                voxels.apply_change(event.origin + position, block);
+            }
+        }
+    }
+    voxels.update_touched_chunks();
+}
@@ -0,0 +1,271 @@
+use std::io;
+
+use bevy::prelude::*;
+
+use crate::game::world::components::BlockType;
+
+use crate::bytes::{invalid_data, ByteReader};
+
+use super::schematic::Schematic;
+
+pub const VOX_MAGIC: &[u8; 4] = b"VOX ";
+/// Version written to exported files
+pub const VOX_VERSION: u32 = 150;
+/// Largest model MagicaVoxel opens along each axis. Voxel positions are stored in a byte,
+/// so 256 still fits: the last voxel along an axis is at 255.
+pub const VOX_MAX_SIZE: i32 = 256;
+
+/// The palette MagicaVoxel uses for models without an `RGBA` chunk:
+/// a 6 step color cube without black, then ramps of red, green, blue and gray
+fn default_palette() -> [[u8; 4]; 256] {
+    let mut palette = [[0, 0, 0, 255]; 256];
+    let steps = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+    let mut index = 0;
+    for r in steps {
+        for g in steps {
+            for b in steps {
+                if r == 0 && g == 0 && b == 0 {
+                    continue;
+                }
+                palette[index] = [r, g, b, 255];
+                index += 1;
+            }
+        }
+    }
+    for channel in 0..4 {
+        for value in ramp {
+            palette[index] = match channel {
+                0 => [value, 0, 0, 255],
+                1 => [0, value, 0, 255],
+                2 => [0, 0, value, 255],
+                _ => [value, value, value, 255],
+            };
+            index += 1;
+        }
+    }
+    palette
+}
+
+fn block_rgba(block: BlockType) -> [u8; 4] {
+    block.color().as_rgba_u8()
+}
+
+/// The block whose color is closest to a palette color
+fn closest_block(rgba: [u8; 4]) -> BlockType {
+    let distance = |block: &BlockType| {
+        block_rgba(*block)
+            .iter()
+            .zip(rgba)
+            .take(3)
+            .map(|(a, b)| (*a as i32 - b as i32).pow(2))
+            .sum::<i32>()
+    };
+    BlockType::ALL
+        .into_iter()
+        .min_by_key(distance)
+        .unwrap_or_default()
+}
+
+/// Reads the first model of a `.vox` file, mapping each palette color to the closest block.
+/// Scene graph, material and layer chunks are skipped.
+/// MagicaVoxel has z pointing up, so its y and z axes are swapped here and in `to_vox`.
+pub fn from_vox(bytes: &[u8]) -> io::Result<Schematic> {
+    let mut reader = ByteReader::new(bytes);
+    if reader.take(4)? != VOX_MAGIC {
+        return Err(invalid_data("not a vox file"));
+    }
+    let _version = reader.u32()?;
+    if reader.take(4)? != b"MAIN" {
+        return Err(invalid_data("vox file has no MAIN chunk"));
+    }
+    let main_content_size = reader.u32()? as usize;
+    let _main_children_size = reader.u32()?;
+    reader.take(main_content_size)?;
+
+    let mut size = None;
+    let mut voxels: Option<Vec<[u8; 4]>> = None;
+    let mut palette = default_palette();
+    while !reader.is_empty() {
+        let id = reader.take(4)?;
+        let content_size = reader.u32()? as usize;
+        let children_size = reader.u32()? as usize;
+        let mut content = ByteReader::new(reader.take(content_size)?);
+        reader.take(children_size)?;
+        match id {
+            b"SIZE" if size.is_none() => {
+                let (x, y, z) = (content.u32()?, content.u32()?, content.u32()?);
+                if [x, y, z].iter().any(|axis| *axis > VOX_MAX_SIZE as u32) {
+                    return Err(invalid_data("vox model is too big"));
+                }
+                size = Some(IVec3::new(x as i32, z as i32, y as i32));
+            }
+            b"XYZI" if voxels.is_none() => {
+                let count = content.u32()? as usize;
+                let mut model = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let voxel = content.take(4)?;
+                    model.push([voxel[0], voxel[1], voxel[2], voxel[3]]);
+                }
+                voxels = Some(model);
+            }
+            b"RGBA" => {
+                // palette entry i is color index i + 1, index 0 is empty
+                for color in palette.iter_mut().take(255) {
+                    let rgba = content.take(4)?;
+                    *color = [rgba[0], rgba[1], rgba[2], rgba[3]];
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (Some(size), Some(voxels)) = (size, voxels) else {
+        return Err(invalid_data("vox file has no model"));
+    };
+    let mut schematic = Schematic::new(size);
+    for [x, y, z, color_index] in voxels {
+        if color_index == 0 {
+            continue;
+        }
+        let block = closest_block(palette[color_index as usize - 1]);
+        schematic.set(IVec3::new(x as i32, z as i32, y as i32), Some((block, 0)));
+    }
+    Ok(schematic)
+}
+
+fn push_chunk(bytes: &mut Vec<u8>, id: &[u8; 4], content: &[u8], children: &[u8]) {
+    bytes.extend_from_slice(id);
+    bytes.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&(children.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(content);
+    bytes.extend_from_slice(children);
+}
+
+/// Writes a schematic as a single model `.vox` file, with a palette entry per block type.
+/// Block states are not kept.
+pub fn to_vox(schematic: &Schematic) -> io::Result<Vec<u8>> {
+    // a size of exactly `VOX_MAX_SIZE` is fine, positions only go up to one less
+    if schematic.size.cmpgt(IVec3::splat(VOX_MAX_SIZE)).any() {
+        return Err(invalid_data(&format!(
+            "vox models can be at most {VOX_MAX_SIZE} voxels along each axis"
+        )));
+    }
+    let mut size = vec![];
+    for axis in [schematic.size.x, schematic.size.z, schematic.size.y] {
+        size.extend_from_slice(&(axis as u32).to_le_bytes());
+    }
+
+    let mut xyzi = vec![];
+    let mut count = 0u32;
+    for (position, block) in schematic.blocks() {
+        let Some((block, _)) = block else {
+            continue;
+        };
+        let color_index = BlockType::ALL
+            .iter()
+            .position(|candidate| *candidate == block)
+            .unwrap_or_default() as u8
+            + 1;
+        xyzi.extend_from_slice(&[
+            position.x as u8,
+            position.z as u8,
+            position.y as u8,
+            color_index,
+        ]);
+        count += 1;
+    }
+    let mut xyzi_content = count.to_le_bytes().to_vec();
+    xyzi_content.extend_from_slice(&xyzi);
+
+    let mut rgba = vec![];
+    for index in 0..256 {
+        let color = BlockType::ALL
+            .get(index)
+            .map_or([0, 0, 0, 255], |block| block_rgba(*block));
+        rgba.extend_from_slice(&color);
+    }
+
+    let mut children = vec![];
+    push_chunk(&mut children, b"SIZE", &size, &[]);
+    push_chunk(&mut children, b"XYZI", &xyzi_content, &[]);
+    push_chunk(&mut children, b"RGBA", &rgba, &[]);
+
+    let mut bytes = VOX_MAGIC.to_vec();
+    bytes.extend_from_slice(&VOX_VERSION.to_le_bytes());
+    push_chunk(&mut bytes, b"MAIN", &[], &children);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Four voxels in a 3 by 2 by 4 (z up) model, saved with a scene graph, layers and materials
+    const SMALL_VOX: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/small.vox"
+    ));
+
+    fn solid_blocks(schematic: &Schematic) -> Vec<(IVec3, BlockType)> {
+        let mut blocks: Vec<(IVec3, BlockType)> = schematic
+            .blocks()
+            .filter_map(|(position, block)| block.map(|(block, _)| (position, block)))
+            .collect();
+        blocks.sort_by_key(|(position, _)| position.to_array());
+        blocks
+    }
+
+    #[test]
+    fn imports_a_magicavoxel_file() {
+        let schematic = from_vox(SMALL_VOX).unwrap();
+        // z up in the file is y up here
+        assert_eq!(schematic.size, IVec3::new(3, 4, 2));
+        assert_eq!(
+            solid_blocks(&schematic),
+            vec![
+                (IVec3::new(0, 0, 0), BlockType::Stone),
+                (IVec3::new(0, 2, 1), BlockType::GoldOre),
+                (IVec3::new(1, 3, 0), BlockType::Sand),
+                (IVec3::new(2, 0, 1), BlockType::Grass),
+            ]
+        );
+    }
+
+    #[test]
+    fn palette_colors_map_to_the_closest_block() {
+        for block in BlockType::ALL {
+            assert_eq!(closest_block(block_rgba(block)), block);
+        }
+        assert_eq!(closest_block([250, 205, 40, 255]), BlockType::GoldOre);
+        // the default palette starts at white and ends in the gray ramp
+        let palette = default_palette();
+        assert_eq!(palette[0], [255, 255, 255, 255]);
+        assert_eq!(palette[254], [0x11, 0x11, 0x11, 255]);
+    }
+
+    #[test]
+    fn exports_read_back_without_their_states() {
+        let mut schematic = Schematic::new(IVec3::new(BlockType::ALL.len() as i32, 3, 2));
+        for (x, block) in BlockType::ALL.into_iter().enumerate() {
+            schematic.set(IVec3::new(x as i32, x as i32 % 3, 1), Some((block, 2)));
+        }
+        let read_back = from_vox(&to_vox(&schematic).unwrap()).unwrap();
+        assert_eq!(read_back.size, schematic.size);
+        assert_eq!(solid_blocks(&read_back), solid_blocks(&schematic));
+    }
+
+    #[test]
+    fn models_can_be_as_big_as_magicavoxel_allows() {
+        let mut largest = Schematic::new(IVec3::new(VOX_MAX_SIZE, 1, 1));
+        largest.set(
+            IVec3::new(VOX_MAX_SIZE - 1, 0, 0),
+            Some((BlockType::Stone, 0)),
+        );
+        let read_back = from_vox(&to_vox(&largest).unwrap()).unwrap();
+        assert_eq!(solid_blocks(&read_back), solid_blocks(&largest));
+
+        let too_big = Schematic::new(IVec3::new(1, VOX_MAX_SIZE + 1, 1));
+        assert!(to_vox(&too_big).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+pub mod resources;
+pub mod systems;
+
+use bevy::prelude::*;
+
+use crate::AppState;
+
+use self::{resources::*, systems::*};
+
+use super::SimulationState;
+
+/// How many times a second the world simulation steps
+pub const WORLD_TICKS_PER_SECOND: f64 = 20.0;
+
+/// Stages of a world tick, run in this order in `FixedUpdate` while the simulation is running
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorldTickSet {
+    /// the tick counter and world clock move on
+    Begin,
+    /// block updates and random ticks
+    Blocks,
+    Fluids,
+    Entities,
+}
+
+pub struct TickPlugin;
+
+impl Plugin for TickPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldTick>()
+            .insert_resource(Time::<Fixed>::from_hz(WORLD_TICKS_PER_SECOND))
+            .configure_sets(
+                FixedUpdate,
+                (
+                    WorldTickSet::Begin,
+                    WorldTickSet::Blocks,
+                    WorldTickSet::Fluids,
+                    WorldTickSet::Entities,
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Game))
+                    .run_if(in_state(SimulationState::Running)),
+            )
+            .add_systems(FixedUpdate, advance_world_tick.in_set(WorldTickSet::Begin));
+    }
+}
+
+/// Run condition for systems that only need to run every `interval` world ticks
+pub fn every_n_ticks(interval: u64) -> impl FnMut(Res<WorldTick>) -> bool + Clone {
+    move |world_tick: Res<WorldTick>| world_tick.0 % interval == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct EveryFifthTick(u64);
+
+    fn count_every_fifth_tick(mut count: ResMut<EveryFifthTick>) {
+        count.0 += 1;
+    }
+
+    /// An app that steps `frame_time` every update, with the tick running.
+    /// It has already been updated once, the first update has no time pass.
+    fn app(frame_time: Duration) -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, TickPlugin))
+            .add_state::<AppState>()
+            .add_state::<SimulationState>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(frame_time))
+            .init_resource::<EveryFifthTick>()
+            .add_systems(
+                FixedUpdate,
+                count_every_fifth_tick
+                    .in_set(WorldTickSet::Blocks)
+                    .run_if(every_n_ticks(5)),
+            );
+        app.update();
+        app
+    }
+
+    fn tick_duration() -> Duration {
+        Duration::from_secs_f64(1.0 / WORLD_TICKS_PER_SECOND)
+    }
+
+    #[test]
+    fn one_tick_per_tick_length_of_frame_time() {
+        let mut app = app(tick_duration());
+        for _ in 0..40 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<WorldTick>().0, 40);
+        assert_eq!(app.world.resource::<EveryFifthTick>().0, 8);
+    }
+
+    #[test]
+    fn slow_frames_catch_up_on_ticks() {
+        let mut app = app(tick_duration() * 3);
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<WorldTick>().0, 30);
+    }
+
+    #[test]
+    fn no_ticks_while_paused() {
+        let mut app = app(tick_duration());
+        let set_state = |app: &mut App, state| {
+            app.world
+                .resource_mut::<NextState<SimulationState>>()
+                .set(state);
+            for _ in 0..10 {
+                app.update();
+            }
+        };
+        set_state(&mut app, SimulationState::Paused);
+        assert_eq!(app.world.resource::<WorldTick>().0, 0);
+        set_state(&mut app, SimulationState::Running);
+        assert_eq!(app.world.resource::<WorldTick>().0, 10);
+    }
+}
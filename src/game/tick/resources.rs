@@ -0,0 +1,16 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+/// World ticks simulated since the world was loaded
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WorldTick(pub u64);
+
+/// Random numbers for the world simulation, seeded from the world seed so runs can be replayed
+#[derive(Resource)]
+pub struct WorldRng(pub StdRng);
+
+impl WorldRng {
+    pub fn from_seed(seed: u64) -> Self {
+        WorldRng(StdRng::seed_from_u64(seed))
+    }
+}
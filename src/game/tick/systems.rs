@@ -0,0 +1,7 @@
+use bevy::prelude::*;
+
+use super::resources::WorldTick;
+
+pub fn advance_world_tick(mut world_tick: ResMut<WorldTick>) {
+    world_tick.0 += 1;
+}
@@ -0,0 +1,123 @@
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::{
+    components::{BlockType, Chunk, Voxel},
+    lighting,
+    resources::{NeighbourUpdates, VoxelWorld},
+    to_chunk_space, FACE_DIRECTIONS,
+};
+
+/// A block a voxel is turned into, `None` for air, with its state
+pub type BlockChange = Option<(BlockType, u8)>;
+
+/// Random access to voxels by world voxel position,
+/// so world algorithms can run over the ecs or over plain data alike
+pub trait VoxelAccess {
+    fn voxel(&self, position: IVec3) -> Option<&Voxel>;
+    fn voxel_mut(&mut self, position: IVec3) -> Option<&mut Voxel>;
+
+    /// Told about every voxel next to a changed block, their faces may have been covered or uncovered
+    fn neighbour_changed(&mut self, _position: IVec3) {}
+
+    /// Turns the voxel at `position` into `block` with `state`, or air for `None`, and relights around it.
+    /// Returns false when there is no voxel there.
+    fn set_block(&mut self, position: IVec3, block: Option<BlockType>, state: u8) -> bool
+    where
+        Self: Sized,
+    {
+        let Some(voxel) = self.voxel_mut(position) else {
+            return false;
+        };
+        voxel.solid = block.is_some();
+        if let Some(block) = block {
+            voxel.block = block;
+        }
+        voxel.state = state;
+        lighting::update_light(self, position);
+        for (_, direction) in FACE_DIRECTIONS {
+            self.neighbour_changed(position + direction);
+        }
+        true
+    }
+
+    /// `set_block` for a change worked out by a simulation step
+    fn apply_change(&mut self, position: IVec3, change: BlockChange) -> bool
+    where
+        Self: Sized,
+    {
+        let (block, state) = change.map_or((None, 0), |(block, state)| (Some(block), state));
+        self.set_block(position, block, state)
+    }
+}
+
+impl VoxelAccess for HashMap<IVec3, Voxel> {
+    fn voxel(&self, position: IVec3) -> Option<&Voxel> {
+        self.get(&position)
+    }
+
+    fn voxel_mut(&mut self, position: IVec3) -> Option<&mut Voxel> {
+        self.get_mut(&position)
+    }
+}
+
+/// Voxels of the loaded `VoxelWorld`, looked up through their block entities
+#[derive(SystemParam)]
+pub struct WorldVoxels<'w, 's> {
+    pub voxel_world: Res<'w, VoxelWorld>,
+    voxels: Query<'w, 's, &'static mut Voxel>,
+    chunks: Query<'w, 's, &'static mut Chunk>,
+    neighbour_updates: ResMut<'w, NeighbourUpdates>,
+    /// chunk positions of every voxel borrowed mutably, these need to be remeshed
+    touched_chunks: Local<'s, HashSet<IVec3>>,
+}
+
+impl<'w, 's> WorldVoxels<'w, 's> {
+    /// Marks a chunk as needing to be remeshed
+    pub fn touch_chunk(&mut self, chunk_position: IVec3) {
+        self.touched_chunks.insert(chunk_position);
+    }
+
+    /// The chunks changed since the last call, emptying the list
+    pub fn take_touched_chunks(&mut self) -> HashSet<IVec3> {
+        std::mem::take(&mut self.touched_chunks)
+    }
+
+    /// Voxels next to a block that changed during the last world tick
+    pub fn neighbour_updates(&self) -> &[IVec3] {
+        self.neighbour_updates.current()
+    }
+
+    /// Flags every chunk changed since the last call as `updated` so it gets remeshed
+    pub fn update_touched_chunks(&mut self) {
+        for chunk_position in self.take_touched_chunks() {
+            if let Some(chunk_resource) = self.voxel_world.chunks.get(&chunk_position) {
+                if let Ok(mut chunk) = self.chunks.get_mut(chunk_resource.entity_id) {
+                    chunk.updated = true;
+                }
+            }
+        }
+    }
+}
+
+impl<'w, 's> VoxelAccess for WorldVoxels<'w, 's> {
+    fn voxel(&self, position: IVec3) -> Option<&Voxel> {
+        let entity = self.voxel_world.voxel_entity(position)?;
+        self.voxels.get(entity).ok()
+    }
+
+    fn voxel_mut(&mut self, position: IVec3) -> Option<&mut Voxel> {
+        let entity = self.voxel_world.voxel_entity(position)?;
+        let voxel = self.voxels.get_mut(entity).ok()?;
+        self.touched_chunks.insert(to_chunk_space(position).0);
+        Some(voxel.into_inner())
+    }
+
+    fn neighbour_changed(&mut self, position: IVec3) {
+        self.touch_chunk(to_chunk_space(position).0);
+        self.neighbour_updates.push(position);
+    }
+}
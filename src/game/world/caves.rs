@@ -0,0 +1,284 @@
+use std::f32::consts::PI;
+
+use bevy::{prelude::*, utils::HashSet};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    components::BlockType,
+    generation::{ChunkBlocks, DIRT_DEPTH, SEA_LEVEL},
+    noise::{fractal_noise_3d, hash},
+    CHUNK_SIZE,
+};
+
+/// Height the density falls through zero at, without any noise
+pub const CAVE_TERRAIN_BASE_HEIGHT: f32 = 9.0;
+/// Nothing is solid at or above this height
+pub const CAVE_TERRAIN_TOP: i32 = 15;
+/// Width in voxels of the lumps of the 3D noise
+pub const DENSITY_SCALE: f32 = 10.0;
+/// How much the density drops per voxel of height, lower values give more overhangs
+pub const DENSITY_HEIGHT_FALLOFF: f32 = 0.12;
+
+/// Worms a chunk starts at most
+pub const WORMS_PER_CHUNK: u32 = 2;
+/// Steps a worm takes at most, one voxel each
+pub const WORM_MAX_LENGTH: i32 = 28;
+/// Widest a worm carves
+pub const WORM_MAX_RADIUS: f32 = 2.0;
+/// Chunks around a chunk whose worms can reach into it
+pub const WORM_CHUNK_REACH: i32 = 2;
+/// Chance of a worm starting at the surface and so opening its cave to the sky
+pub const CAVE_ENTRANCE_CHANCE: f64 = 0.35;
+
+/// An ore, the highest it is found at, and how much of the stone there it takes up
+pub const ORE_VEINS: [(BlockType, i32, f32); 3] = [
+    // rarest first, so they win where veins overlap
+    (BlockType::GoldOre, 4, 0.1),
+    (BlockType::IronOre, 8, 0.15),
+    (BlockType::CoalOre, 13, 0.2),
+];
+/// Width in voxels of the lumps of an ore vein
+pub const ORE_VEIN_SCALE: f32 = 3.0;
+
+const WORM_SALT: u64 = 0x5752_4f4d;
+const ORE_SALT: u64 = 0x004f_5245;
+
+/// Above zero is solid. Mostly falls with height, the 3D noise on top adds overhangs and floating bits.
+pub fn density(seed: u64, position: IVec3) -> f32 {
+    let noise = fractal_noise_3d(seed, position.as_vec3() / DENSITY_SCALE, 3) * 2.0 - 1.0;
+    (CAVE_TERRAIN_BASE_HEIGHT - position.y as f32) * DENSITY_HEIGHT_FALLOFF + noise
+}
+
+/// Whether the density function makes a voxel solid, before worms carve it.
+/// The bottom layer of the world is always solid.
+pub fn is_solid(seed: u64, position: IVec3) -> bool {
+    position.y <= 0 || (position.y < CAVE_TERRAIN_TOP && density(seed, position) > 0.0)
+}
+
+/// Height of the topmost solid voxel of the column at `x`, `z`
+pub fn surface_height(seed: u64, x: i32, z: i32) -> i32 {
+    (0..CAVE_TERRAIN_TOP)
+        .rev()
+        .find(|y| is_solid(seed, IVec3::new(x, *y, z)))
+        .unwrap_or(0)
+}
+
+/// The ore at a stone voxel, if its vein reaches that deep
+fn ore(seed: u64, position: IVec3) -> Option<BlockType> {
+    ORE_VEINS
+        .iter()
+        .enumerate()
+        .find(|(index, (_, highest, share))| {
+            position.y <= *highest
+                && fractal_noise_3d(
+                    seed.wrapping_add(1 + *index as u64) ^ ORE_SALT,
+                    position.as_vec3() / ORE_VEIN_SCALE,
+                    2,
+                ) > 1.0 - share
+        })
+        .map(|(_, (block, _, _))| *block)
+}
+
+/// The block the density function has at a world voxel position, before worms carve it
+pub fn base_block(seed: u64, position: IVec3) -> Option<BlockType> {
+    if !is_solid(seed, position) {
+        return (position.y <= SEA_LEVEL).then_some(BlockType::Water);
+    }
+    // solid voxels under this one and the open air, the top ones get the surface blocks
+    let depth = (1..=DIRT_DEPTH + 1)
+        .find(|depth| !is_solid(seed, position + IVec3::Y * *depth))
+        .map(|depth| depth - 1);
+    Some(match depth {
+        Some(depth) if position.y + depth <= SEA_LEVEL + 1 => BlockType::Sand,
+        Some(0) => BlockType::Grass,
+        Some(_) => BlockType::Dirt,
+        None => ore(seed, position).unwrap_or(BlockType::Stone),
+    })
+}
+
+/// Positions inside a chunk carved out by the worms of every chunk close enough to reach it
+fn worm_carvings(seed: u64, chunk_position: IVec3) -> HashSet<IVec3> {
+    let mut carved = HashSet::new();
+    let chunk_origin = chunk_position * CHUNK_SIZE;
+    let reach = IVec3::splat(WORM_CHUNK_REACH);
+    for x in -reach.x..=reach.x {
+        for y in -reach.y..=reach.y {
+            for z in -reach.z..=reach.z {
+                let start_chunk = chunk_position + IVec3::new(x, y, z);
+                let mut rng = StdRng::seed_from_u64(hash(seed ^ WORM_SALT, start_chunk));
+                for _ in 0..rng.gen_range(0..=WORMS_PER_CHUNK) {
+                    let start_origin = start_chunk * CHUNK_SIZE;
+                    let mut position = (start_origin
+                        + IVec3::new(
+                            rng.gen_range(0..CHUNK_SIZE.x),
+                            rng.gen_range(0..CHUNK_SIZE.y),
+                            rng.gen_range(0..CHUNK_SIZE.z),
+                        ))
+                    .as_vec3();
+                    if rng.gen_bool(CAVE_ENTRANCE_CHANCE) {
+                        position.y =
+                            surface_height(seed, position.x as i32, position.z as i32) as f32;
+                    }
+                    let mut yaw = rng.gen_range(0.0..2.0 * PI);
+                    let mut pitch = rng.gen_range(-0.5..0.5f32);
+                    let radius = rng.gen_range(1.0..WORM_MAX_RADIUS);
+                    for _ in 0..rng.gen_range(WORM_MAX_LENGTH / 2..=WORM_MAX_LENGTH) {
+                        carve_sphere(&mut carved, chunk_origin, position, radius);
+                        yaw += rng.gen_range(-0.4..0.4);
+                        // worms mostly wander sideways
+                        pitch = (pitch + rng.gen_range(-0.2..0.2)).clamp(-0.8, 0.8);
+                        position += Vec3::new(
+                            yaw.cos() * pitch.cos(),
+                            pitch.sin(),
+                            yaw.sin() * pitch.cos(),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    carved
+}
+
+/// Adds the positions inside the chunk at `chunk_origin` within `radius` of `center`
+fn carve_sphere(carved: &mut HashSet<IVec3>, chunk_origin: IVec3, center: Vec3, radius: f32) {
+    let minimum = (center - radius).floor().as_ivec3();
+    let maximum = (center + radius).ceil().as_ivec3();
+    for x in minimum.x..=maximum.x {
+        for y in minimum.y..=maximum.y {
+            for z in minimum.z..=maximum.z {
+                let position = IVec3::new(x, y, z);
+                let local_position = position - chunk_origin;
+                if local_position.cmplt(IVec3::ZERO).any()
+                    || local_position.cmpge(CHUNK_SIZE).any()
+                    || position.as_vec3().distance_squared(center) > radius * radius
+                {
+                    continue;
+                }
+                carved.insert(local_position);
+            }
+        }
+    }
+}
+
+/// The base blocks of a chunk, with the worm caves carved out. The bottom layer is never carved.
+pub fn generate_chunk(seed: u64, chunk_position: IVec3) -> ChunkBlocks {
+    let chunk_origin = chunk_position * CHUNK_SIZE;
+    let carved = worm_carvings(seed, chunk_position);
+    let mut blocks = ChunkBlocks::new();
+    for x in 0..CHUNK_SIZE.x {
+        for y in 0..CHUNK_SIZE.y {
+            for z in 0..CHUNK_SIZE.z {
+                let local_position = IVec3::new(x, y, z);
+                let position = chunk_origin + local_position;
+                if position.y > 0 && carved.contains(&local_position) {
+                    continue;
+                }
+                if let Some(block) = base_block(seed, position) {
+                    blocks.insert(local_position, block);
+                }
+            }
+        }
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every block of a 4 by 4 chunk area, by world voxel position
+    fn area(seed: u64) -> Vec<(IVec3, Option<BlockType>)> {
+        let mut blocks = vec![];
+        for chunk_x in 0..4 {
+            for chunk_z in 0..4 {
+                let chunk_position = IVec3::new(chunk_x, 0, chunk_z);
+                let chunk_blocks = generate_chunk(seed, chunk_position);
+                for x in 0..CHUNK_SIZE.x {
+                    for y in 0..CHUNK_SIZE.y {
+                        for z in 0..CHUNK_SIZE.z {
+                            let local_position = IVec3::new(x, y, z);
+                            blocks.push((
+                                chunk_position * CHUNK_SIZE + local_position,
+                                chunk_blocks.get(&local_position).copied(),
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        blocks
+    }
+
+    fn is_ore(block: BlockType) -> bool {
+        ORE_VEINS.iter().any(|(ore, _, _)| *ore == block)
+    }
+
+    /// Share of the voxels with a height in `heights` that are solid ground, and of those that are ore
+    fn fractions(
+        blocks: &[(IVec3, Option<BlockType>)],
+        heights: std::ops::RangeInclusive<i32>,
+    ) -> (f32, f32) {
+        let band: Vec<BlockType> = blocks
+            .iter()
+            .filter(|(position, _)| heights.contains(&position.y))
+            .filter_map(|(_, block)| *block)
+            .filter(|block| *block != BlockType::Water)
+            .collect();
+        let voxels = blocks
+            .iter()
+            .filter(|(position, _)| heights.contains(&position.y))
+            .count();
+        let ores = band.iter().filter(|block| is_ore(**block)).count();
+        (
+            band.len() as f32 / voxels as f32,
+            ores as f32 / band.len().max(1) as f32,
+        )
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_chunks() {
+        for chunk_position in [IVec3::ZERO, IVec3::new(3, 0, -2)] {
+            assert!(generate_chunk(7, chunk_position) == generate_chunk(7, chunk_position));
+        }
+        assert!(generate_chunk(7, IVec3::ZERO) != generate_chunk(8, IVec3::ZERO));
+    }
+
+    #[test]
+    fn ground_thins_out_with_height() {
+        for seed in [1, 2, 3] {
+            let blocks = area(seed);
+            let (bottom, _) = fractions(&blocks, 0..=0);
+            let (deep, _) = fractions(&blocks, 1..=5);
+            let (middle, _) = fractions(&blocks, 6..=10);
+            let (high, _) = fractions(&blocks, 11..=14);
+            let (sky, _) = fractions(&blocks, CAVE_TERRAIN_TOP..=CHUNK_SIZE.y - 1);
+            assert_eq!(bottom, 1.0, "seed {seed}");
+            // caves take some of the deep ground, but not most of it
+            assert!((0.8..1.0).contains(&deep), "seed {seed}: {deep}");
+            assert!((0.3..0.9).contains(&middle), "seed {seed}: {middle}");
+            assert!((0.0..0.3).contains(&high), "seed {seed}: {high}");
+            assert_eq!(sky, 0.0, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn ores_only_show_up_at_their_depths() {
+        for seed in [1, 2, 3] {
+            let blocks = area(seed);
+            let (_, deep_ores) = fractions(&blocks, 0..=5);
+            assert!(
+                (0.001..0.2).contains(&deep_ores),
+                "seed {seed}: {deep_ores}"
+            );
+            for (ore, highest, _) in ORE_VEINS {
+                assert!(
+                    blocks
+                        .iter()
+                        .all(|(position, block)| *block != Some(ore) || position.y <= highest),
+                    "seed {seed}: {ore:?} above {highest}"
+                );
+            }
+        }
+    }
+}
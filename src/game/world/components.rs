@@ -1,15 +1,17 @@
 use bevy::{
     math::IVec3,
-    prelude::{Component, Transform, Vec3},
+    prelude::{Color, Component, Reflect, Transform, Vec3},
     render::{
         mesh::{shape, Indices, Mesh},
         render_resource::PrimitiveTopology,
     },
 };
 
+use crate::game::fluid::{FLUID_FALLING, FLUID_LEVEL_MASK};
+
 use super::{
     FACE_MASK_BACK, FACE_MASK_BOTTOM, FACE_MASK_FRONT, FACE_MASK_LEFT, FACE_MASK_RIGHT,
-    FACE_MASK_TOP,
+    FACE_MASK_TOP, MAX_LIGHT_LEVEL, VOXEL_SIZE,
 };
 
 #[derive(Component)]
@@ -17,21 +19,188 @@ pub struct Chunk {
     pub updated: bool,
 }
 
+/// Child of a chunk holding the mesh of its transparent blocks
 #[derive(Component)]
+pub struct TransparentChunkMesh;
+
+/// Level of detail a chunk is meshed at, 0 is full detail
+#[derive(Component, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkLod(pub u8);
+
+/// What a solid voxel is made of, air is a voxel that is not `solid`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Reflect)]
+pub enum BlockType {
+    #[default]
+    Stone,
+    Glowstone,
+    Water,
+    Glass,
+    Leaves,
+    Lava,
+    Dirt,
+    Grass,
+    Log,
+    /// grows through the voxel `state` from 0 up to `CROP_MAX_AGE`
+    Crop,
+    /// falls when there is nothing under it
+    Sand,
+    /// falls when there is nothing under it
+    Gravel,
+    CoalOre,
+    IronOre,
+    GoldOre,
+}
+
+/// Oldest a crop gets, fully grown
+pub const CROP_MAX_AGE: u8 = 7;
+
+impl BlockType {
+    pub const ALL: [BlockType; 15] = [
+        BlockType::Stone,
+        BlockType::Glowstone,
+        BlockType::Water,
+        BlockType::Glass,
+        BlockType::Leaves,
+        BlockType::Lava,
+        BlockType::Dirt,
+        BlockType::Grass,
+        BlockType::Log,
+        BlockType::Crop,
+        BlockType::Sand,
+        BlockType::Gravel,
+        BlockType::CoalOre,
+        BlockType::IronOre,
+        BlockType::GoldOre,
+    ];
+
+    /// Name the block is saved and typed under
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlockType::Stone => "stone",
+            BlockType::Glowstone => "glowstone",
+            BlockType::Water => "water",
+            BlockType::Glass => "glass",
+            BlockType::Leaves => "leaves",
+            BlockType::Lava => "lava",
+            BlockType::Dirt => "dirt",
+            BlockType::Grass => "grass",
+            BlockType::Log => "log",
+            BlockType::Crop => "crop",
+            BlockType::Sand => "sand",
+            BlockType::Gravel => "gravel",
+            BlockType::CoalOre => "coal_ore",
+            BlockType::IronOre => "iron_ore",
+            BlockType::GoldOre => "gold_ore",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        BlockType::ALL
+            .into_iter()
+            .find(|block| block.name() == name)
+    }
+
+    /// Block light level this block emits, 0 for blocks that do not glow
+    pub fn light_emission(&self) -> u8 {
+        match self {
+            BlockType::Glowstone | BlockType::Lava => MAX_LIGHT_LEVEL,
+            _ => 0,
+        }
+    }
+
+    /// Whether this block can be seen through, these are meshed separately and drawn with blending
+    pub fn is_transparent(&self) -> bool {
+        matches!(
+            self,
+            BlockType::Water | BlockType::Glass | BlockType::Leaves
+        )
+    }
+
+    /// Base color of the block, alpha is only used by transparent blocks
+    pub fn color(&self) -> Color {
+        match self {
+            BlockType::Stone => Color::SEA_GREEN,
+            BlockType::Glowstone => Color::rgb(1.0, 0.85, 0.45),
+            BlockType::Water => Color::rgba(0.15, 0.35, 0.85, 0.6),
+            BlockType::Glass => Color::rgba(0.85, 0.95, 1.0, 0.25),
+            BlockType::Leaves => Color::rgba(0.2, 0.6, 0.15, 0.85),
+            BlockType::Lava => Color::rgb(1.0, 0.35, 0.05),
+            BlockType::Dirt => Color::rgb(0.45, 0.3, 0.18),
+            BlockType::Grass => Color::rgb(0.3, 0.7, 0.2),
+            BlockType::Log => Color::rgb(0.4, 0.28, 0.15),
+            BlockType::Crop => Color::rgba(0.75, 0.8, 0.3, 0.9),
+            BlockType::Sand => Color::rgb(0.9, 0.85, 0.6),
+            BlockType::Gravel => Color::rgb(0.5, 0.48, 0.47),
+            BlockType::CoalOre => Color::rgb(0.15, 0.15, 0.17),
+            BlockType::IronOre => Color::rgb(0.75, 0.55, 0.45),
+            BlockType::GoldOre => Color::rgb(0.95, 0.8, 0.2),
+        }
+    }
+
+    /// Whether this block flows, the voxel `state` then holds its flow level
+    pub fn is_fluid(&self) -> bool {
+        matches!(self, BlockType::Water | BlockType::Lava)
+    }
+
+    /// Whether this block falls down when it loses the block under it
+    pub fn falls(&self) -> bool {
+        matches!(self, BlockType::Sand | BlockType::Gravel)
+    }
+
+    /// Whether this block ever has `state` in a voxel, blocks without state only have 0
+    pub fn is_valid_state(&self, state: u8) -> bool {
+        match self {
+            BlockType::Water | BlockType::Lava => state & !(FLUID_LEVEL_MASK | FLUID_FALLING) == 0,
+            BlockType::Crop => state <= CROP_MAX_AGE,
+            _ => state == 0,
+        }
+    }
+}
+
+#[derive(Component, Default)]
 pub struct Voxel {
     pub solid: bool,
     pub mask: u8,
+    pub block: BlockType,
+    /// block specific state, for fluids this is their flow level
+    pub state: u8,
+    /// light coming from the top of the world
+    pub sky_light: u8,
+    /// light coming from emissive blocks
+    pub block_light: u8,
 }
 
-impl Default for Voxel {
-    fn default() -> Self {
-        Voxel {
-            solid: false,
-            mask: 0b000000,
+impl Voxel {
+    /// Block light this voxel emits, air never emits light
+    pub fn light_emission(&self) -> u8 {
+        if self.solid {
+            self.block.light_emission()
+        } else {
+            0
         }
     }
-}
-impl Voxel {
+
+    /// Whether light can pass through this voxel
+    pub fn is_transparent(&self) -> bool {
+        !self.solid || self.block.is_transparent()
+    }
+
+    /// Whether the face of this voxel that touches `neighbour` needs to be drawn.
+    /// Faces against opaque blocks are hidden, and so are faces between two of the same transparent block.
+    pub fn shows_face_towards(&self, neighbour: &Voxel) -> bool {
+        !neighbour.solid || (neighbour.block.is_transparent() && neighbour.block != self.block)
+    }
+
+    /// Block and state of this voxel, `None` for air, in the form of a `BlockChange`
+    pub fn block_change(&self) -> Option<(BlockType, u8)> {
+        self.solid.then_some((self.block, self.state))
+    }
+
+    /// The brightest of the sky and block light in this voxel
+    pub fn light(&self) -> u8 {
+        self.sky_light.max(self.block_light)
+    }
+
     fn to_face_set(&self) -> (bool, bool, bool, bool, bool, bool) {
         let set = (
             self.mask & FACE_MASK_TOP == FACE_MASK_TOP,
@@ -46,16 +215,166 @@ impl Voxel {
     }
 }
 
+pub type Vertecies = ([f32; 3], [f32; 3], [f32; 2]);
+
+/// The four corners of a single face of a voxel centered on the origin,
+/// ordered so that `[0, 1, 2, 2, 3, 0]` winds counter clockwise.
+pub fn face_vertices(face: u8) -> [Vertecies; 4] {
+    let shape = shape::Box::new(VOXEL_SIZE, VOXEL_SIZE, VOXEL_SIZE);
+    // suppose Y-up right hand, and camera look from +z to -z
+    match face {
+        FACE_MASK_FRONT => [
+            (
+                [shape.min_x, shape.min_y, shape.max_z],
+                [0., 0., 1.0],
+                [0., 0.],
+            ),
+            (
+                [shape.max_x, shape.min_y, shape.max_z],
+                [0., 0., 1.0],
+                [1.0, 0.],
+            ),
+            (
+                [shape.max_x, shape.max_y, shape.max_z],
+                [0., 0., 1.0],
+                [1.0, 1.0],
+            ),
+            (
+                [shape.min_x, shape.max_y, shape.max_z],
+                [0., 0., 1.0],
+                [0., 1.0],
+            ),
+        ],
+        FACE_MASK_BACK => [
+            (
+                [shape.min_x, shape.max_y, shape.min_z],
+                [0., 0., -1.0],
+                [1.0, 0.],
+            ),
+            (
+                [shape.max_x, shape.max_y, shape.min_z],
+                [0., 0., -1.0],
+                [0., 0.],
+            ),
+            (
+                [shape.max_x, shape.min_y, shape.min_z],
+                [0., 0., -1.0],
+                [0., 1.0],
+            ),
+            (
+                [shape.min_x, shape.min_y, shape.min_z],
+                [0., 0., -1.0],
+                [1.0, 1.0],
+            ),
+        ],
+        FACE_MASK_RIGHT => [
+            (
+                [shape.max_x, shape.min_y, shape.min_z],
+                [1.0, 0., 0.],
+                [0., 0.],
+            ),
+            (
+                [shape.max_x, shape.max_y, shape.min_z],
+                [1.0, 0., 0.],
+                [1.0, 0.],
+            ),
+            (
+                [shape.max_x, shape.max_y, shape.max_z],
+                [1.0, 0., 0.],
+                [1.0, 1.0],
+            ),
+            (
+                [shape.max_x, shape.min_y, shape.max_z],
+                [1.0, 0., 0.],
+                [0., 1.0],
+            ),
+        ],
+        FACE_MASK_LEFT => [
+            (
+                [shape.min_x, shape.min_y, shape.max_z],
+                [-1.0, 0., 0.],
+                [1.0, 0.],
+            ),
+            (
+                [shape.min_x, shape.max_y, shape.max_z],
+                [-1.0, 0., 0.],
+                [0., 0.],
+            ),
+            (
+                [shape.min_x, shape.max_y, shape.min_z],
+                [-1.0, 0., 0.],
+                [0., 1.0],
+            ),
+            (
+                [shape.min_x, shape.min_y, shape.min_z],
+                [-1.0, 0., 0.],
+                [1.0, 1.0],
+            ),
+        ],
+        FACE_MASK_TOP => [
+            (
+                [shape.max_x, shape.max_y, shape.min_z],
+                [0., 1.0, 0.],
+                [1.0, 0.],
+            ),
+            (
+                [shape.min_x, shape.max_y, shape.min_z],
+                [0., 1.0, 0.],
+                [0., 0.],
+            ),
+            (
+                [shape.min_x, shape.max_y, shape.max_z],
+                [0., 1.0, 0.],
+                [0., 1.0],
+            ),
+            (
+                [shape.max_x, shape.max_y, shape.max_z],
+                [0., 1.0, 0.],
+                [1.0, 1.0],
+            ),
+        ],
+        FACE_MASK_BOTTOM => [
+            (
+                [shape.max_x, shape.min_y, shape.max_z],
+                [0., -1.0, 0.],
+                [0., 0.],
+            ),
+            (
+                [shape.min_x, shape.min_y, shape.max_z],
+                [0., -1.0, 0.],
+                [1.0, 0.],
+            ),
+            (
+                [shape.min_x, shape.min_y, shape.min_z],
+                [0., -1.0, 0.],
+                [1.0, 1.0],
+            ),
+            (
+                [shape.max_x, shape.min_y, shape.min_z],
+                [0., -1.0, 0.],
+                [0., 1.0],
+            ),
+        ],
+        _ => panic!("{face:#08b} is not a single face"),
+    }
+}
+
 impl From<&Voxel> for Mesh {
     fn from(sp: &Voxel) -> Self {
-        type Vertecies = ([f32; 3], [f32; 3], [f32; 2]);
-        // TODO make voxel size a const
-        let shape = shape::Box::new(0.1, 0.1, 0.1);
         let (top, bottom, left, right, front, back) = sp.to_face_set();
-        // suppose Y-up right hand, and camera look from +z to -z
         let mut vertices: Vec<Vertecies> = vec![];
         let mut indices: Vec<u32> = vec![];
-        if front {
+        for (visible, face) in [
+            (front, FACE_MASK_FRONT),
+            (back, FACE_MASK_BACK),
+            (right, FACE_MASK_RIGHT),
+            (left, FACE_MASK_LEFT),
+            (top, FACE_MASK_TOP),
+            (bottom, FACE_MASK_BOTTOM),
+        ] {
+            if !visible {
+                continue;
+            }
             let current_indices_count = vertices.len() as u32;
             indices.extend_from_slice(&[
                 current_indices_count,
@@ -65,193 +384,7 @@ impl From<&Voxel> for Mesh {
                 current_indices_count + 3,
                 current_indices_count,
             ]);
-            vertices.extend_from_slice(&[
-                (
-                    [shape.min_x, shape.min_y, shape.max_z],
-                    [0., 0., 1.0],
-                    [0., 0.],
-                ),
-                (
-                    [shape.max_x, shape.min_y, shape.max_z],
-                    [0., 0., 1.0],
-                    [1.0, 0.],
-                ),
-                (
-                    [shape.max_x, shape.max_y, shape.max_z],
-                    [0., 0., 1.0],
-                    [1.0, 1.0],
-                ),
-                (
-                    [shape.min_x, shape.max_y, shape.max_z],
-                    [0., 0., 1.0],
-                    [0., 1.0],
-                ),
-            ]);
-        }
-        if back {
-            let current_indices_count = vertices.len() as u32;
-            indices.extend_from_slice(&[
-                current_indices_count,
-                current_indices_count + 1,
-                current_indices_count + 2,
-                current_indices_count + 2,
-                current_indices_count + 3,
-                current_indices_count,
-            ]);
-            vertices.extend_from_slice(&[
-                (
-                    [shape.min_x, shape.max_y, shape.min_z],
-                    [0., 0., -1.0],
-                    [1.0, 0.],
-                ),
-                (
-                    [shape.max_x, shape.max_y, shape.min_z],
-                    [0., 0., -1.0],
-                    [0., 0.],
-                ),
-                (
-                    [shape.max_x, shape.min_y, shape.min_z],
-                    [0., 0., -1.0],
-                    [0., 1.0],
-                ),
-                (
-                    [shape.min_x, shape.min_y, shape.min_z],
-                    [0., 0., -1.0],
-                    [1.0, 1.0],
-                ),
-            ]);
-        }
-        if right {
-            let current_indices_count = vertices.len() as u32;
-            indices.extend_from_slice(&[
-                current_indices_count,
-                current_indices_count + 1,
-                current_indices_count + 2,
-                current_indices_count + 2,
-                current_indices_count + 3,
-                current_indices_count,
-            ]);
-            vertices.extend_from_slice(&[
-                (
-                    [shape.max_x, shape.min_y, shape.min_z],
-                    [1.0, 0., 0.],
-                    [0., 0.],
-                ),
-                (
-                    [shape.max_x, shape.max_y, shape.min_z],
-                    [1.0, 0., 0.],
-                    [1.0, 0.],
-                ),
-                (
-                    [shape.max_x, shape.max_y, shape.max_z],
-                    [1.0, 0., 0.],
-                    [1.0, 1.0],
-                ),
-                (
-                    [shape.max_x, shape.min_y, shape.max_z],
-                    [1.0, 0., 0.],
-                    [0., 1.0],
-                ),
-            ]);
-        }
-        if left {
-            let current_indices_count = vertices.len() as u32;
-            indices.extend_from_slice(&[
-                current_indices_count,
-                current_indices_count + 1,
-                current_indices_count + 2,
-                current_indices_count + 2,
-                current_indices_count + 3,
-                current_indices_count,
-            ]);
-            vertices.extend_from_slice(&[
-                (
-                    [shape.min_x, shape.min_y, shape.max_z],
-                    [-1.0, 0., 0.],
-                    [1.0, 0.],
-                ),
-                (
-                    [shape.min_x, shape.max_y, shape.max_z],
-                    [-1.0, 0., 0.],
-                    [0., 0.],
-                ),
-                (
-                    [shape.min_x, shape.max_y, shape.min_z],
-                    [-1.0, 0., 0.],
-                    [0., 1.0],
-                ),
-                (
-                    [shape.min_x, shape.min_y, shape.min_z],
-                    [-1.0, 0., 0.],
-                    [1.0, 1.0],
-                ),
-            ]);
-        }
-        if top {
-            let current_indices_count = vertices.len() as u32;
-            indices.extend_from_slice(&[
-                current_indices_count,
-                current_indices_count + 1,
-                current_indices_count + 2,
-                current_indices_count + 2,
-                current_indices_count + 3,
-                current_indices_count,
-            ]);
-            vertices.extend_from_slice(&[
-                (
-                    [shape.max_x, shape.max_y, shape.min_z],
-                    [0., 1.0, 0.],
-                    [1.0, 0.],
-                ),
-                (
-                    [shape.min_x, shape.max_y, shape.min_z],
-                    [0., 1.0, 0.],
-                    [0., 0.],
-                ),
-                (
-                    [shape.min_x, shape.max_y, shape.max_z],
-                    [0., 1.0, 0.],
-                    [0., 1.0],
-                ),
-                (
-                    [shape.max_x, shape.max_y, shape.max_z],
-                    [0., 1.0, 0.],
-                    [1.0, 1.0],
-                ),
-            ]);
-        }
-        if bottom {
-            let current_indices_count = vertices.len() as u32;
-            indices.extend_from_slice(&[
-                current_indices_count,
-                current_indices_count + 1,
-                current_indices_count + 2,
-                current_indices_count + 2,
-                current_indices_count + 3,
-                current_indices_count,
-            ]);
-            vertices.extend_from_slice(&[
-                (
-                    [shape.max_x, shape.min_y, shape.max_z],
-                    [0., -1.0, 0.],
-                    [0., 0.],
-                ),
-                (
-                    [shape.min_x, shape.min_y, shape.max_z],
-                    [0., -1.0, 0.],
-                    [1.0, 0.],
-                ),
-                (
-                    [shape.min_x, shape.min_y, shape.min_z],
-                    [0., -1.0, 0.],
-                    [1.0, 1.0],
-                ),
-                (
-                    [shape.max_x, shape.min_y, shape.min_z],
-                    [0., -1.0, 0.],
-                    [0., 1.0],
-                ),
-            ]);
+            vertices.extend_from_slice(&face_vertices(face));
         }
 
         let positions: Vec<_> = vertices.iter().map(|(p, _, _)| *p).collect();
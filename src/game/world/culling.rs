@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::primitives::Aabb,
+    utils::{HashMap, HashSet},
+};
+
+use super::{CHUNK_SIZE, FACE_DIRECTIONS, VOXEL_SIZE};
+
+/// Bounds of a chunk in world units
+pub fn chunk_aabb(chunk_position: IVec3) -> Aabb {
+    let minimum = (chunk_position * CHUNK_SIZE).as_vec3() * VOXEL_SIZE;
+    let maximum = ((chunk_position + IVec3::ONE) * CHUNK_SIZE).as_vec3() * VOXEL_SIZE;
+    // voxels are centered on their coordinate, so the chunk starts half a voxel early
+    Aabb::from_min_max(minimum - VOXEL_SIZE / 2.0, maximum - VOXEL_SIZE / 2.0)
+}
+
+/// Index into `FACE_DIRECTIONS` of the face pointing the other way
+pub fn opposite_face(face_index: usize) -> usize {
+    face_index ^ 1
+}
+
+/// Which faces of a chunk can see which other faces through the air inside it.
+/// Each bit `a * 6 + b` is set when face `a` and face `b` (indices into `FACE_DIRECTIONS`) are connected.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkConnectivity(pub u64);
+
+impl Default for ChunkConnectivity {
+    /// Until a chunk has been looked at assume every face sees every other face
+    fn default() -> Self {
+        ChunkConnectivity::ALL
+    }
+}
+
+impl ChunkConnectivity {
+    pub const NONE: ChunkConnectivity = ChunkConnectivity(0);
+    pub const ALL: ChunkConnectivity = ChunkConnectivity((1 << 36) - 1);
+
+    pub fn connects(&self, from_face: usize, to_face: usize) -> bool {
+        self.0 & (1 << (from_face * 6 + to_face)) != 0
+    }
+
+    fn connect(&mut self, from_face: usize, to_face: usize) {
+        self.0 |= 1 << (from_face * 6 + to_face);
+        self.0 |= 1 << (to_face * 6 + from_face);
+    }
+
+    /// Flood fills the air inside a chunk, every air pocket connects all the chunk faces it touches.
+    /// `is_opaque` is given positions inside the chunk.
+    pub fn compute(is_opaque: impl Fn(IVec3) -> bool) -> Self {
+        let mut connectivity = ChunkConnectivity::NONE;
+        let mut visited: HashSet<IVec3> = HashSet::new();
+        let in_chunk =
+            |position: IVec3| position.cmpge(IVec3::ZERO).all() && position.cmplt(CHUNK_SIZE).all();
+        // the faces of the chunk a voxel touches
+        let touched_faces = |position: IVec3| {
+            FACE_DIRECTIONS
+                .iter()
+                .enumerate()
+                .filter(move |(_, (_, direction))| !in_chunk(position + *direction))
+                .map(|(face_index, _)| face_index)
+        };
+
+        for x in 0..CHUNK_SIZE.x {
+            for y in 0..CHUNK_SIZE.y {
+                for z in 0..CHUNK_SIZE.z {
+                    let start = IVec3::new(x, y, z);
+                    // only pockets reaching the border matter, so start from border voxels
+                    if touched_faces(start).next().is_none()
+                        || visited.contains(&start)
+                        || is_opaque(start)
+                    {
+                        continue;
+                    }
+                    let mut faces = 0u8;
+                    let mut queue = VecDeque::from([start]);
+                    visited.insert(start);
+                    while let Some(position) = queue.pop_front() {
+                        for face_index in touched_faces(position) {
+                            faces |= 1 << face_index;
+                        }
+                        for (_, direction) in FACE_DIRECTIONS {
+                            let neighbour = position + direction;
+                            if in_chunk(neighbour)
+                                && !is_opaque(neighbour)
+                                && visited.insert(neighbour)
+                            {
+                                queue.push_back(neighbour);
+                            }
+                        }
+                    }
+                    for from_face in 0..6 {
+                        for to_face in 0..6 {
+                            if faces & (1 << from_face) != 0 && faces & (1 << to_face) != 0 {
+                                connectivity.connect(from_face, to_face);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        connectivity
+    }
+}
+
+/// Chunks that can be seen from the chunk the camera is in, walking outwards through connected faces.
+/// A walk never turns back towards the camera and only enters chunks accepted by `in_frustum`.
+pub fn visible_chunks(
+    camera_chunk: IVec3,
+    connectivity: &HashMap<IVec3, ChunkConnectivity>,
+    in_frustum: impl Fn(IVec3) -> bool,
+) -> HashSet<IVec3> {
+    let mut visible = HashSet::new();
+    if !connectivity.contains_key(&camera_chunk) {
+        return visible;
+    }
+    // chunk, face it was entered through and the directions walked so far
+    let mut queue = VecDeque::from([(camera_chunk, None::<usize>, 0u8)]);
+    visible.insert(camera_chunk);
+    while let Some((chunk_position, entered_through, directions)) = queue.pop_front() {
+        let chunk_connectivity = connectivity[&chunk_position];
+        for (face_index, (_, direction)) in FACE_DIRECTIONS.iter().enumerate() {
+            // walking back the way we came can never reveal anything new
+            if directions & (1 << opposite_face(face_index)) != 0 {
+                continue;
+            }
+            if let Some(entered_through) = entered_through {
+                if !chunk_connectivity.connects(entered_through, face_index) {
+                    continue;
+                }
+            }
+            let neighbour = chunk_position + *direction;
+            if !connectivity.contains_key(&neighbour)
+                || visible.contains(&neighbour)
+                || !in_frustum(neighbour)
+            {
+                continue;
+            }
+            visible.insert(neighbour);
+            queue.push_back((
+                neighbour,
+                Some(opposite_face(face_index)),
+                directions | (1 << face_index),
+            ));
+        }
+    }
+    visible
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOP: usize = 0;
+    const BOTTOM: usize = 1;
+    const LEFT: usize = 2;
+    const RIGHT: usize = 3;
+    const FRONT: usize = 4;
+
+    #[test]
+    fn open_chunks_connect_every_face() {
+        assert_eq!(
+            ChunkConnectivity::compute(|_| false),
+            ChunkConnectivity::ALL
+        );
+    }
+
+    #[test]
+    fn sealed_chunks_connect_nothing() {
+        assert_eq!(
+            ChunkConnectivity::compute(|_| true),
+            ChunkConnectivity::NONE
+        );
+    }
+
+    #[test]
+    fn a_tunnel_only_connects_its_two_ends() {
+        let middle = CHUNK_SIZE / 2;
+        // solid apart from a tunnel along x through the middle
+        let connectivity =
+            ChunkConnectivity::compute(|position| position.y != middle.y || position.z != middle.z);
+        assert!(connectivity.connects(LEFT, RIGHT));
+        assert!(connectivity.connects(RIGHT, LEFT));
+        assert!(!connectivity.connects(LEFT, TOP));
+        assert!(!connectivity.connects(TOP, BOTTOM));
+        assert!(!connectivity.connects(FRONT, RIGHT));
+    }
+
+    #[test]
+    fn a_floor_splits_the_chunk_in_two() {
+        let middle = CHUNK_SIZE.y / 2;
+        let connectivity = ChunkConnectivity::compute(|position| position.y == middle);
+        assert!(!connectivity.connects(TOP, BOTTOM));
+        assert!(connectivity.connects(TOP, LEFT));
+        assert!(connectivity.connects(BOTTOM, RIGHT));
+    }
+
+    #[test]
+    fn sealed_chunks_hide_what_is_behind_them() {
+        let mut connectivity = HashMap::new();
+        for x in 0..4 {
+            connectivity.insert(IVec3::new(x, 0, 0), ChunkConnectivity::ALL);
+        }
+        connectivity.insert(IVec3::new(2, 0, 0), ChunkConnectivity::NONE);
+        let visible = visible_chunks(IVec3::ZERO, &connectivity, |_| true);
+        // the sealed chunk itself is seen, nothing past it is
+        assert!(visible.contains(&IVec3::new(1, 0, 0)));
+        assert!(visible.contains(&IVec3::new(2, 0, 0)));
+        assert!(!visible.contains(&IVec3::new(3, 0, 0)));
+    }
+
+    #[test]
+    fn chunks_outside_the_frustum_are_not_walked_into() {
+        let connectivity: HashMap<IVec3, ChunkConnectivity> = (-2..=2)
+            .map(|x| (IVec3::new(x, 0, 0), ChunkConnectivity::ALL))
+            .collect();
+        let visible = visible_chunks(IVec3::ZERO, &connectivity, |chunk| chunk.x >= 0);
+        assert_eq!(visible.len(), 3);
+        assert!(!visible.contains(&IVec3::new(-1, 0, 0)));
+    }
+}
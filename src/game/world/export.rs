@@ -0,0 +1,155 @@
+use std::{fmt::Write, fs, io, path::Path};
+
+use super::{components::BlockType, meshing::ChunkMeshBuilder};
+
+/// Builds an OBJ file out of chunk meshes, with a material per block type.
+/// Vertex colors, which carry the baked light and occlusion, are written after each position.
+#[derive(Debug, Default)]
+pub struct ObjWriter {
+    obj: String,
+    /// vertices written so far, OBJ indices count from 1 across the whole file
+    vertex_count: u32,
+    materials: Vec<BlockType>,
+}
+
+impl ObjWriter {
+    /// Adds a mesh as a named object, empty meshes are left out
+    pub fn push_mesh(&mut self, name: &str, mesh: &ChunkMeshBuilder) {
+        if mesh.is_empty() {
+            return;
+        }
+        // writing to a string can not fail
+        let _ = writeln!(self.obj, "o {name}");
+        for (position, color) in mesh.positions.iter().zip(&mesh.colors) {
+            let [x, y, z] = position;
+            let [red, green, blue, _] = color;
+            let _ = writeln!(self.obj, "v {x} {y} {z} {red} {green} {blue}");
+        }
+        for [u, v] in &mesh.uvs {
+            let _ = writeln!(self.obj, "vt {u} {v}");
+        }
+        for [x, y, z] in &mesh.normals {
+            let _ = writeln!(self.obj, "vn {x} {y} {z}");
+        }
+
+        // group the triangles by block, keeping their order inside each group
+        let mut triangles: Vec<(BlockType, &[u32])> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| (mesh.blocks[triangle[0] as usize / 4], triangle))
+            .collect();
+        triangles.sort_by_key(|(block, _)| *block as u8);
+        let mut current_block = None;
+        for (block, triangle) in triangles {
+            if current_block != Some(block) {
+                let _ = writeln!(self.obj, "usemtl {}", block.name());
+                if !self.materials.contains(&block) {
+                    self.materials.push(block);
+                }
+                current_block = Some(block);
+            }
+            let _ = write!(self.obj, "f");
+            for index in triangle {
+                let index = index + self.vertex_count + 1;
+                let _ = write!(self.obj, " {index}/{index}/{index}");
+            }
+            let _ = writeln!(self.obj);
+        }
+        self.vertex_count += mesh.positions.len() as u32;
+    }
+
+    /// The OBJ file, `mtl_file_name` is the material library it refers to
+    pub fn to_obj(&self, mtl_file_name: &str) -> String {
+        format!("mtllib {mtl_file_name}\n{}", self.obj)
+    }
+
+    /// The material library, a flat colored material for each block type used
+    pub fn to_mtl(&self) -> String {
+        let mut materials = self.materials.clone();
+        materials.sort_by_key(|block| *block as u8);
+        let mut mtl = String::new();
+        for block in materials {
+            let [red, green, blue, alpha] = block.color().as_rgba_f32();
+            let _ = writeln!(mtl, "newmtl {}", block.name());
+            let _ = writeln!(mtl, "Kd {red} {green} {blue}");
+            let _ = writeln!(mtl, "d {alpha}");
+        }
+        mtl
+    }
+
+    /// Saves the OBJ file at `path` and its material library next to it
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mtl_path = path.with_extension("mtl");
+        let mtl_file_name = mtl_path
+            .file_name()
+            .map_or("world.mtl".into(), |name| name.to_string_lossy());
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, self.to_obj(&mtl_file_name))?;
+        fs::write(&mtl_path, self.to_mtl())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::game::world::{
+        components::Voxel, meshing::build_chunk_meshes, FACE_DIRECTIONS, MAX_LIGHT_LEVEL,
+    };
+
+    /// A stone voxel at the origin with water next to it along +x, both showing every face
+    fn two_blocks() -> ObjWriter {
+        let voxel = |block| Voxel {
+            solid: true,
+            block,
+            mask: FACE_DIRECTIONS
+                .iter()
+                .fold(0, |mask, (face, _)| mask | face),
+            sky_light: MAX_LIGHT_LEVEL,
+            ..default()
+        };
+        let stone = voxel(BlockType::Stone);
+        let water = voxel(BlockType::Water);
+        let voxel_at = |position: IVec3| match position.to_array() {
+            [0, 0, 0] => Some(&stone),
+            [1, 0, 0] => Some(&water),
+            _ => None,
+        };
+        let chunk_meshes = build_chunk_meshes(IVec3::ZERO, 0, voxel_at);
+        let mut writer = ObjWriter::default();
+        writer.push_mesh("chunk_0_0_0", &chunk_meshes.opaque);
+        writer.push_mesh("chunk_0_0_0_transparent", &chunk_meshes.transparent);
+        writer
+    }
+
+    #[test]
+    fn export_matches_the_checked_in_files() {
+        let writer = two_blocks();
+        let obj = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/two_blocks.obj"
+        ));
+        let mtl = include_str!(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/two_blocks.mtl"
+        ));
+        assert_eq!(writer.to_obj("two_blocks.mtl"), obj);
+        assert_eq!(writer.to_mtl(), mtl);
+    }
+
+    #[test]
+    fn indices_continue_across_objects() {
+        let obj = two_blocks().to_obj("two_blocks.mtl");
+        let vertices = obj.lines().filter(|line| line.starts_with("v ")).count();
+        let highest_index = obj
+            .lines()
+            .filter_map(|line| line.strip_prefix("f "))
+            .flat_map(|face| face.split(' '))
+            .filter_map(|corner| corner.split('/').next()?.parse::<usize>().ok())
+            .max();
+        assert_eq!(highest_index, Some(vertices));
+    }
+}
@@ -0,0 +1,363 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    caves,
+    components::BlockType,
+    noise::{fractal_noise_2d, hash, hash_to_unit},
+    to_chunk_space, CHUNK_SIZE,
+};
+
+/// Water fills every column up to this height
+pub const SEA_LEVEL: i32 = 5;
+/// Lowest and highest the terrain surface gets
+pub const TERRAIN_HEIGHT_RANGE: (i32, i32) = (2, 9);
+/// Width in voxels of the hills of the heightmap
+pub const TERRAIN_SCALE: f32 = 24.0;
+/// How deep the dirt under grass goes
+pub const DIRT_DEPTH: i32 = 3;
+/// Chance of a stone voxel being glowstone instead
+pub const GLOWSTONE_CHANCE: f32 = 0.01;
+
+/// Height of the topmost solid voxel of the column at `x`, `z`
+pub fn surface_height(seed: u64, x: i32, z: i32) -> i32 {
+    let noise = fractal_noise_2d(seed, Vec2::new(x as f32, z as f32) / TERRAIN_SCALE, 4);
+    let (lowest, highest) = TERRAIN_HEIGHT_RANGE;
+    lowest + (noise * (highest - lowest + 1) as f32) as i32
+}
+
+/// The block the base terrain has at a world voxel position, `None` for air
+pub fn terrain_block(seed: u64, position: IVec3) -> Option<BlockType> {
+    let surface = surface_height(seed, position.x, position.z);
+    if position.y > surface {
+        return (position.y <= SEA_LEVEL).then_some(BlockType::Water);
+    }
+    let depth = surface - position.y;
+    // shores and sea beds are sand
+    if surface <= SEA_LEVEL + 1 && depth < DIRT_DEPTH {
+        return Some(BlockType::Sand);
+    }
+    Some(match depth {
+        0 => BlockType::Grass,
+        depth if depth <= DIRT_DEPTH => BlockType::Dirt,
+        _ if hash_to_unit(seed, position) < GLOWSTONE_CHANCE => BlockType::Glowstone,
+        _ => BlockType::Stone,
+    })
+}
+
+/// Which generator shapes the base terrain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TerrainShape {
+    /// rolling hills from a 2D heightmap
+    #[default]
+    Heightmap,
+    /// 3D noise with overhangs, carved through by caves
+    Caves,
+}
+
+impl TerrainShape {
+    pub const ALL: [TerrainShape; 2] = [TerrainShape::Heightmap, TerrainShape::Caves];
+
+    /// Name the terrain is saved under
+    pub fn name(&self) -> &'static str {
+        match self {
+            TerrainShape::Heightmap => "heightmap",
+            TerrainShape::Caves => "caves",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        TerrainShape::ALL
+            .into_iter()
+            .find(|terrain| terrain.name() == name)
+    }
+
+    /// Height of the topmost solid voxel of the column at `x`, `z`
+    pub fn surface_height(&self, seed: u64, x: i32, z: i32) -> i32 {
+        match self {
+            TerrainShape::Heightmap => surface_height(seed, x, z),
+            TerrainShape::Caves => caves::surface_height(seed, x, z),
+        }
+    }
+
+    /// The block at a world voxel position, cave terrain is looked at before its caves are carved
+    pub fn block(&self, seed: u64, position: IVec3) -> Option<BlockType> {
+        match self {
+            TerrainShape::Heightmap => terrain_block(seed, position),
+            TerrainShape::Caves => caves::base_block(seed, position),
+        }
+    }
+
+    /// The base terrain of a chunk, before decoration
+    pub fn generate_chunk(&self, seed: u64, chunk_position: IVec3) -> ChunkBlocks {
+        match self {
+            TerrainShape::Heightmap => {
+                let origin = chunk_position * CHUNK_SIZE;
+                let mut blocks = ChunkBlocks::new();
+                for x in 0..CHUNK_SIZE.x {
+                    for y in 0..CHUNK_SIZE.y {
+                        for z in 0..CHUNK_SIZE.z {
+                            let local_position = IVec3::new(x, y, z);
+                            if let Some(block) = terrain_block(seed, origin + local_position) {
+                                blocks.insert(local_position, block);
+                            }
+                        }
+                    }
+                }
+                blocks
+            }
+            TerrainShape::Caves => caves::generate_chunk(seed, chunk_position),
+        }
+    }
+}
+
+/// Something placed on top of the base terrain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Tree,
+    Boulder,
+    /// a broken stone ring with a light in the middle
+    Ruin,
+}
+
+impl Feature {
+    /// The blocks of the feature standing on the voxel above the surface at `origin`
+    pub fn blocks(&self, origin: IVec3, rng: &mut StdRng) -> Vec<(IVec3, BlockType)> {
+        let mut blocks = vec![];
+        match self {
+            Feature::Tree => {
+                let trunk_height = rng.gen_range(4..=5);
+                let top = origin + IVec3::Y * (trunk_height - 1);
+                for dx in -2..=2i32 {
+                    for dy in -1..=2i32 {
+                        for dz in -2..=2i32 {
+                            // rounded crown, narrower at the top
+                            let radius = if dy > 0 { 1 } else { 2 };
+                            if dx.abs() > radius
+                                || dz.abs() > radius
+                                || (dx.abs() == radius && dz.abs() == radius && radius > 1)
+                            {
+                                continue;
+                            }
+                            blocks.push((top + IVec3::new(dx, dy, dz), BlockType::Leaves));
+                        }
+                    }
+                }
+                for y in 0..trunk_height {
+                    blocks.push((origin + IVec3::Y * y, BlockType::Log));
+                }
+            }
+            Feature::Boulder => {
+                // sunk one voxel into the ground
+                let center = origin - IVec3::Y;
+                for dx in -1..=1i32 {
+                    for dy in -1..=1i32 {
+                        for dz in -1..=1i32 {
+                            let offset = IVec3::new(dx, dy, dz);
+                            if offset.length_squared() > 2 {
+                                continue;
+                            }
+                            let block = if rng.gen_bool(0.3) {
+                                BlockType::Gravel
+                            } else {
+                                BlockType::Stone
+                            };
+                            blocks.push((center + offset, block));
+                        }
+                    }
+                }
+            }
+            Feature::Ruin => {
+                for dx in -2..=2i32 {
+                    for dz in -2..=2i32 {
+                        if dx.abs() < 2 && dz.abs() < 2 {
+                            continue;
+                        }
+                        let wall_height = rng.gen_range(0..=2);
+                        for y in 0..wall_height {
+                            blocks.push((origin + IVec3::new(dx, y, dz), BlockType::Stone));
+                        }
+                    }
+                }
+                blocks.push((origin, BlockType::Glowstone));
+            }
+        }
+        blocks
+    }
+}
+
+/// Whether a decoration write of `new` replaces `existing`. Every write goes through here,
+/// so the outcome is the same whatever order chunks are generated and decorated in.
+fn replaces(new: BlockType, existing: Option<BlockType>) -> bool {
+    let rank = |block: Option<BlockType>| match block {
+        None => (0, 0),
+        Some(BlockType::Leaves) => (1, 0),
+        Some(BlockType::Log) => (2, 0),
+        Some(block) => (3, block as u8),
+    };
+    rank(Some(new)) > rank(existing)
+}
+
+/// Blocks of a generated chunk by position inside the chunk, positions missing are air
+pub type ChunkBlocks = HashMap<IVec3, BlockType>;
+
+/// Generates chunks from a seed: base terrain first, then decorations.
+/// Decorations are picked per chunk from the seed alone, and writes into chunks
+/// that have not been generated yet wait here until they are.
+pub struct WorldGenerator {
+    pub seed: u64,
+    pub terrain: TerrainShape,
+    chunks: HashMap<IVec3, ChunkBlocks>,
+    /// decoration writes waiting for their chunk to be generated
+    pending: HashMap<IVec3, Vec<(IVec3, BlockType)>>,
+    /// chunks generated so far, including ones already taken
+    generated: HashSet<IVec3>,
+}
+
+impl WorldGenerator {
+    pub fn new(seed: u64, terrain: TerrainShape) -> Self {
+        WorldGenerator {
+            seed,
+            terrain,
+            chunks: HashMap::new(),
+            pending: HashMap::new(),
+            generated: HashSet::new(),
+        }
+    }
+
+    /// Generates the chunk at `chunk_position` and decorates it, does nothing if it already was
+    pub fn generate_chunk(&mut self, chunk_position: IVec3) {
+        if !self.generated.insert(chunk_position) {
+            return;
+        }
+        let mut blocks = self.terrain.generate_chunk(self.seed, chunk_position);
+        for (local_position, block) in self.pending.remove(&chunk_position).unwrap_or_default() {
+            place_block(&mut blocks, local_position, block);
+        }
+        self.chunks.insert(chunk_position, blocks);
+        self.decorate_chunk(chunk_position);
+    }
+
+    /// Picks and places the features rooted in a chunk, they can reach into the chunks around it
+    fn decorate_chunk(&mut self, chunk_position: IVec3) {
+        let origin = chunk_position * CHUNK_SIZE;
+        let mut rng = StdRng::seed_from_u64(hash(self.seed, chunk_position));
+        let mut features = vec![];
+        for _ in 0..rng.gen_range(0..=3) {
+            features.push(Feature::Tree);
+        }
+        if rng.gen_bool(0.5) {
+            features.push(Feature::Boulder);
+        }
+        if rng.gen_bool(0.125) {
+            features.push(Feature::Ruin);
+        }
+        for feature in features {
+            let x = origin.x + rng.gen_range(0..CHUNK_SIZE.x);
+            let z = origin.z + rng.gen_range(0..CHUNK_SIZE.z);
+            let surface = self.terrain.surface_height(self.seed, x, z);
+            let feature_origin = IVec3::new(x, surface + 1, z);
+            // the blocks are always rolled so one skipped feature does not change the rest
+            let blocks = feature.blocks(feature_origin, &mut rng);
+            // every feature belongs to the one chunk its origin is in
+            let on_land =
+                self.terrain.block(self.seed, IVec3::new(x, surface, z)) == Some(BlockType::Grass);
+            if to_chunk_space(feature_origin).0 != chunk_position || !on_land {
+                continue;
+            }
+            for (position, block) in blocks {
+                self.place(position, block);
+            }
+        }
+    }
+
+    /// Writes a decoration block at a world voxel position, or keeps it for later
+    /// when its chunk has not been generated yet
+    fn place(&mut self, position: IVec3, block: BlockType) {
+        let (chunk_position, local_position) = to_chunk_space(position);
+        match self.chunks.get_mut(&chunk_position) {
+            Some(blocks) => place_block(blocks, local_position, block),
+            None if !self.generated.contains(&chunk_position) => self
+                .pending
+                .entry(chunk_position)
+                .or_default()
+                .push((local_position, block)),
+            // the chunk was already taken out, its voxels live in the world now
+            None => {}
+        }
+    }
+
+    /// Removes a finished chunk from the generator. Once taken, decorations from
+    /// chunks generated later can no longer reach into it, so generate its neighbours first.
+    pub fn take_chunk(&mut self, chunk_position: IVec3) -> Option<ChunkBlocks> {
+        self.chunks.remove(&chunk_position)
+    }
+}
+
+fn place_block(blocks: &mut ChunkBlocks, local_position: IVec3, block: BlockType) {
+    if replaces(block, blocks.get(&local_position).copied()) {
+        blocks.insert(local_position, block);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The chunks at `chunk_positions`, generated in that order
+    fn generate(seed: u64, chunk_positions: &[IVec3]) -> Vec<ChunkBlocks> {
+        let mut generator = WorldGenerator::new(seed, TerrainShape::Heightmap);
+        for chunk_position in chunk_positions {
+            generator.generate_chunk(*chunk_position);
+        }
+        chunk_positions
+            .iter()
+            .map(|chunk_position| generator.take_chunk(*chunk_position).unwrap())
+            .collect()
+    }
+
+    /// Whether a tree rooted in chunk `a` reaches into chunk `b`
+    fn tree_crosses(seed: u64, a: IVec3, b: IVec3) -> bool {
+        let alone = &generate(seed, &[b])[0];
+        let after_a = &generate(seed, &[a, b])[1];
+        after_a.iter().any(|(local_position, block)| {
+            matches!(block, BlockType::Log | BlockType::Leaves)
+                && alone.get(local_position) != Some(block)
+        })
+    }
+
+    #[test]
+    fn tree_across_a_chunk_edge_is_the_same_in_either_order() {
+        let a = IVec3::ZERO;
+        let b = IVec3::X;
+        let seed = (0..200)
+            .find(|seed| tree_crosses(*seed, a, b))
+            .expect("no seed puts a tree across the chunk edge");
+
+        let a_then_b = generate(seed, &[a, b]);
+        let mut b_then_a = generate(seed, &[b, a]);
+        b_then_a.reverse();
+        assert!(a_then_b == b_then_a);
+    }
+
+    #[test]
+    fn generation_order_never_changes_the_world() {
+        let chunk_positions = [
+            IVec3::new(0, 0, 0),
+            IVec3::new(1, 0, 0),
+            IVec3::new(0, 0, 1),
+            IVec3::new(1, 0, 1),
+        ];
+        let mut reversed = chunk_positions;
+        reversed.reverse();
+        for seed in 0..10 {
+            let forwards = generate(seed, &chunk_positions);
+            let mut backwards = generate(seed, &reversed);
+            backwards.reverse();
+            assert!(forwards == backwards, "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::{access::VoxelAccess, FACE_DIRECTIONS, MAX_LIGHT_LEVEL};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    /// light falling in from the top of the world
+    Sky,
+    /// light emitted by blocks
+    Block,
+}
+
+impl LightChannel {
+    pub fn get(&self, access: &impl VoxelAccess, position: IVec3) -> u8 {
+        match (self, access.voxel(position)) {
+            (LightChannel::Sky, Some(voxel)) => voxel.sky_light,
+            (LightChannel::Block, Some(voxel)) => voxel.block_light,
+            (_, None) => 0,
+        }
+    }
+
+    pub fn set(&self, access: &mut impl VoxelAccess, position: IVec3, level: u8) {
+        if let Some(voxel) = access.voxel_mut(position) {
+            match self {
+                LightChannel::Sky => voxel.sky_light = level,
+                LightChannel::Block => voxel.block_light = level,
+            }
+        }
+    }
+
+    /// The light a voxel receives from a neighbour at `level` when it spreads in `direction`.
+    /// Full sky light falls straight down without getting dimmer.
+    fn spread(&self, level: u8, direction: IVec3) -> u8 {
+        if *self == LightChannel::Sky && level == MAX_LIGHT_LEVEL && direction == IVec3::NEG_Y {
+            MAX_LIGHT_LEVEL
+        } else {
+            level.saturating_sub(1)
+        }
+    }
+}
+
+fn is_transparent(access: &impl VoxelAccess, position: IVec3) -> bool {
+    access
+        .voxel(position)
+        .is_some_and(|voxel| voxel.is_transparent())
+}
+
+/// Whether a voxel sees the sky directly, that is there is nothing above it in the world
+fn is_sky_source(access: &impl VoxelAccess, position: IVec3) -> bool {
+    is_transparent(access, position) && access.voxel(position + IVec3::Y).is_none()
+}
+
+/// Breadth first flood fill of light outwards from every position in `queue`,
+/// each position should already hold the light level it spreads
+pub fn propagate_light(
+    access: &mut impl VoxelAccess,
+    channel: LightChannel,
+    mut queue: VecDeque<IVec3>,
+) {
+    while let Some(position) = queue.pop_front() {
+        let level = channel.get(access, position);
+        if level == 0 {
+            continue;
+        }
+        for (_, direction) in FACE_DIRECTIONS {
+            let neighbour = position + direction;
+            if !is_transparent(access, neighbour) {
+                continue;
+            }
+            let spread = channel.spread(level, direction);
+            if channel.get(access, neighbour) < spread {
+                channel.set(access, neighbour, spread);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Darkens all light that came from `position` and returns the positions
+/// lit by other sources that border the darkened area, these need to be propagated again
+pub fn remove_light(
+    access: &mut impl VoxelAccess,
+    channel: LightChannel,
+    position: IVec3,
+) -> VecDeque<IVec3> {
+    let mut relight = VecDeque::new();
+    let mut queue = VecDeque::new();
+    let level = channel.get(access, position);
+    channel.set(access, position, 0);
+    queue.push_back((position, level));
+
+    while let Some((position, level)) = queue.pop_front() {
+        for (_, direction) in FACE_DIRECTIONS {
+            let neighbour = position + direction;
+            let neighbour_level = channel.get(access, neighbour);
+            if neighbour_level == 0 {
+                continue;
+            }
+            // anything dimmer than us, or fed straight down by us, was lit by us
+            if neighbour_level < level || channel.spread(level, direction) == neighbour_level {
+                channel.set(access, neighbour, 0);
+                queue.push_back((neighbour, neighbour_level));
+            } else {
+                relight.push_back(neighbour);
+            }
+        }
+    }
+    relight
+}
+
+/// Lights every voxel in `positions` from scratch, used when a world is first generated
+pub fn light_voxels(access: &mut impl VoxelAccess, positions: impl Iterator<Item = IVec3>) {
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+    for position in positions {
+        if is_sky_source(access, position) {
+            LightChannel::Sky.set(access, position, MAX_LIGHT_LEVEL);
+            sky_queue.push_back(position);
+        }
+        let emission = access
+            .voxel(position)
+            .map_or(0, |voxel| voxel.light_emission());
+        if emission > 0 {
+            LightChannel::Block.set(access, position, emission);
+            block_queue.push_back(position);
+        }
+    }
+    propagate_light(access, LightChannel::Sky, sky_queue);
+    propagate_light(access, LightChannel::Block, block_queue);
+}
+
+/// Fixes up the light around a voxel that has just been changed
+pub fn update_light(access: &mut impl VoxelAccess, position: IVec3) {
+    for channel in [LightChannel::Sky, LightChannel::Block] {
+        let mut relight = remove_light(access, channel, position);
+        let source = match channel {
+            LightChannel::Sky if is_sky_source(access, position) => MAX_LIGHT_LEVEL,
+            LightChannel::Sky => 0,
+            LightChannel::Block => access
+                .voxel(position)
+                .map_or(0, |voxel| voxel.light_emission()),
+        };
+        if source > 0 {
+            channel.set(access, position, source);
+            relight.push_back(position);
+        }
+        // light from the neighbours now flows back into a voxel that was cleared
+        if is_transparent(access, position) {
+            for (_, direction) in FACE_DIRECTIONS {
+                relight.push_back(position + direction);
+            }
+        }
+        propagate_light(access, channel, relight);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+
+    use super::*;
+    use crate::game::world::{
+        access::VoxelAccess,
+        components::{BlockType, Voxel},
+        CHUNK_SIZE,
+    };
+
+    /// An air world of `size` voxels starting at the origin, lit from scratch
+    fn air_world(size: IVec3) -> HashMap<IVec3, Voxel> {
+        let mut world = HashMap::new();
+        for x in 0..size.x {
+            for y in 0..size.y {
+                for z in 0..size.z {
+                    world.insert(IVec3::new(x, y, z), Voxel::default());
+                }
+            }
+        }
+        let positions: Vec<IVec3> = world.keys().copied().collect();
+        light_voxels(&mut world, positions.into_iter());
+        world
+    }
+
+    fn block_light(world: &HashMap<IVec3, Voxel>, position: IVec3) -> u8 {
+        world[&position].block_light
+    }
+
+    fn sky_light(world: &HashMap<IVec3, Voxel>, position: IVec3) -> u8 {
+        world[&position].sky_light
+    }
+
+    #[test]
+    fn open_air_is_fully_sky_lit() {
+        let world = air_world(IVec3::new(3, 4, 3));
+        assert!(world
+            .values()
+            .all(|voxel| voxel.sky_light == MAX_LIGHT_LEVEL));
+        assert!(world.values().all(|voxel| voxel.block_light == 0));
+    }
+
+    #[test]
+    fn placing_and_removing_a_light_source() {
+        let mut world = air_world(IVec3::new(9, 3, 3));
+        let source = IVec3::new(4, 1, 1);
+        world.set_block(source, Some(BlockType::Glowstone), 0);
+
+        assert_eq!(block_light(&world, source), MAX_LIGHT_LEVEL);
+        for distance in 1..=4 {
+            assert_eq!(
+                block_light(&world, source + IVec3::X * distance),
+                MAX_LIGHT_LEVEL - distance as u8
+            );
+            assert_eq!(
+                block_light(&world, source - IVec3::X * distance),
+                MAX_LIGHT_LEVEL - distance as u8
+            );
+        }
+
+        world.set_block(source, None, 0);
+        assert!(world.values().all(|voxel| voxel.block_light == 0));
+    }
+
+    #[test]
+    fn removing_one_of_two_sources_keeps_the_other_lit() {
+        let mut world = air_world(IVec3::new(9, 1, 1));
+        let (first, second) = (IVec3::new(1, 0, 0), IVec3::new(7, 0, 0));
+        world.set_block(first, Some(BlockType::Glowstone), 0);
+        world.set_block(second, Some(BlockType::Glowstone), 0);
+        world.set_block(first, None, 0);
+
+        assert_eq!(block_light(&world, second), MAX_LIGHT_LEVEL);
+        for x in 0..9 {
+            let distance = (x - second.x).unsigned_abs() as u8;
+            assert_eq!(
+                block_light(&world, IVec3::new(x, 0, 0)),
+                MAX_LIGHT_LEVEL - distance
+            );
+        }
+    }
+
+    #[test]
+    fn covering_and_uncovering_a_sky_column() {
+        let mut world = air_world(IVec3::new(3, 6, 3));
+        let top = IVec3::new(1, 5, 1);
+        world.set_block(top, Some(BlockType::Stone), 0);
+
+        assert_eq!(sky_light(&world, top), 0);
+        // the column below is lit sideways by its neighbours instead of straight down
+        for y in 0..5 {
+            assert_eq!(sky_light(&world, IVec3::new(1, y, 1)), MAX_LIGHT_LEVEL - 1);
+        }
+        assert_eq!(sky_light(&world, IVec3::new(0, 0, 1)), MAX_LIGHT_LEVEL);
+
+        world.set_block(top, None, 0);
+        assert!(world
+            .values()
+            .all(|voxel| voxel.sky_light == MAX_LIGHT_LEVEL));
+    }
+
+    #[test]
+    fn covering_a_whole_world_darkens_it() {
+        let mut world = air_world(IVec3::new(2, 3, 2));
+        for x in 0..2 {
+            for z in 0..2 {
+                world.set_block(IVec3::new(x, 2, z), Some(BlockType::Stone), 0);
+            }
+        }
+        assert!(world.values().all(|voxel| voxel.sky_light == 0));
+    }
+
+    #[test]
+    fn light_crosses_chunk_borders() {
+        // two chunks side by side along x
+        let mut world = air_world(IVec3::new(CHUNK_SIZE.x * 2, 1, 1));
+        let border = CHUNK_SIZE.x;
+        let source = IVec3::new(border - 1, 0, 0);
+        world.set_block(source, Some(BlockType::Glowstone), 0);
+
+        assert_eq!(
+            block_light(&world, IVec3::new(border, 0, 0)),
+            MAX_LIGHT_LEVEL - 1
+        );
+        assert_eq!(
+            block_light(&world, IVec3::new(border + 3, 0, 0)),
+            MAX_LIGHT_LEVEL - 4
+        );
+
+        // a wall on the far side of the border stops the light there
+        world.set_block(IVec3::new(border + 1, 0, 0), Some(BlockType::Stone), 0);
+        assert_eq!(
+            block_light(&world, IVec3::new(border, 0, 0)),
+            MAX_LIGHT_LEVEL - 1
+        );
+        assert_eq!(block_light(&world, IVec3::new(border + 2, 0, 0)), 0);
+
+        world.set_block(source, None, 0);
+        assert!(world.values().all(|voxel| voxel.block_light == 0));
+    }
+}
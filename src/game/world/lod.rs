@@ -0,0 +1,228 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    components::{BlockType, Voxel},
+    meshing::ChunkMeshes,
+    CHUNK_SIZE, FACE_DIRECTIONS, MAX_LIGHT_LEVEL, VOXEL_SIZE,
+};
+
+/// Camera distances in world units past which a chunk drops to the next level of detail,
+/// level 0 is full detail and every level after halves the resolution
+pub const LOD_DISTANCES: [f32; 3] = [4.0, 8.0, 16.0];
+
+/// Level of detail for a chunk whose center is `distance` away from the camera
+pub fn lod_for_distance(distance: f32) -> u8 {
+    LOD_DISTANCES
+        .iter()
+        .take_while(|lod_distance| distance >= **lod_distance)
+        .count() as u8
+}
+
+/// How many voxels along each axis are merged into one cell at a level of detail
+pub fn lod_factor(lod: u8) -> i32 {
+    1 << lod
+}
+
+/// One cell of a downsampled chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LodCell {
+    /// the most common block in the cell, `None` when most of the cell is air
+    pub block: Option<BlockType>,
+    /// the brightest light of the air inside the cell
+    pub light: u8,
+}
+
+/// A chunk downsampled so `factor` voxels along each axis become a single cell
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodGrid {
+    pub factor: i32,
+    pub size: IVec3,
+    pub cells: Vec<LodCell>,
+}
+
+impl LodGrid {
+    /// Downsamples a chunk, `voxel_at` gives the voxel at a position inside the chunk.
+    /// A cell is solid when at least half of its voxels are.
+    pub fn downsample<'a>(factor: i32, voxel_at: impl Fn(IVec3) -> Option<&'a Voxel>) -> Self {
+        let size = CHUNK_SIZE / factor;
+        let mut cells = Vec::with_capacity((size.x * size.y * size.z) as usize);
+        for x in 0..size.x {
+            for y in 0..size.y {
+                for z in 0..size.z {
+                    let cell_origin = IVec3::new(x, y, z) * factor;
+                    let mut solid_count = 0;
+                    let mut block_counts: HashMap<BlockType, i32> = HashMap::new();
+                    let mut light = 0;
+                    for dx in 0..factor {
+                        for dy in 0..factor {
+                            for dz in 0..factor {
+                                let Some(voxel) = voxel_at(cell_origin + IVec3::new(dx, dy, dz))
+                                else {
+                                    continue;
+                                };
+                                if voxel.solid {
+                                    solid_count += 1;
+                                    *block_counts.entry(voxel.block).or_default() += 1;
+                                } else {
+                                    light = light.max(voxel.light());
+                                }
+                            }
+                        }
+                    }
+                    let block = if solid_count * 2 >= factor * factor * factor {
+                        block_counts
+                            .into_iter()
+                            // break ties on the block so the result does not depend on hash order
+                            .max_by_key(|(block, count)| (*count, *block as u8))
+                            .map(|(block, _)| block)
+                    } else {
+                        None
+                    };
+                    cells.push(LodCell { block, light });
+                }
+            }
+        }
+        LodGrid {
+            factor,
+            size,
+            cells,
+        }
+    }
+
+    pub fn cell(&self, position: IVec3) -> Option<&LodCell> {
+        if position.cmplt(IVec3::ZERO).any() || position.cmpge(self.size).any() {
+            return None;
+        }
+        let index = (position.x * self.size.y + position.y) * self.size.z + position.z;
+        self.cells.get(index as usize)
+    }
+
+    /// Adds the faces of every solid cell to `meshes`, `chunk_origin` is the chunk position in world voxel space.
+    /// Faces on the chunk border are always kept, they act as skirts hiding the cracks
+    /// between chunks meshed at different levels of detail.
+    pub fn push_to_mesh(&self, meshes: &mut ChunkMeshes, chunk_origin: IVec3) {
+        let scale = self.factor as f32;
+        for x in 0..self.size.x {
+            for y in 0..self.size.y {
+                for z in 0..self.size.z {
+                    let position = IVec3::new(x, y, z);
+                    let Some(block) = self.cell(position).and_then(|cell| cell.block) else {
+                        continue;
+                    };
+                    // the cell covers voxels `factor` wide, its center sits half way across them
+                    let center = ((chunk_origin + position * self.factor).as_vec3()
+                        + Vec3::splat((scale - 1.0) / 2.0))
+                        * VOXEL_SIZE;
+                    for (face, direction) in FACE_DIRECTIONS {
+                        let light = match self.cell(position + direction) {
+                            // same culling rules as full detail voxels
+                            Some(LodCell {
+                                block: Some(neighbour_block),
+                                ..
+                            }) if !neighbour_block.is_transparent()
+                                || *neighbour_block == block =>
+                            {
+                                continue
+                            }
+                            Some(neighbour) => neighbour.light,
+                            None => MAX_LIGHT_LEVEL,
+                        };
+                        meshes
+                            .builder_for(block)
+                            .push_face(face, center, scale, block, light, [3; 4]);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn solid(block: BlockType) -> Voxel {
+        Voxel {
+            solid: true,
+            block,
+            ..default()
+        }
+    }
+
+    fn air(light: u8) -> Voxel {
+        Voxel {
+            sky_light: light,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn lod_steps_up_at_each_distance() {
+        assert_eq!(lod_for_distance(0.0), 0);
+        assert_eq!(lod_for_distance(3.9), 0);
+        assert_eq!(lod_for_distance(4.0), 1);
+        assert_eq!(lod_for_distance(8.0), 2);
+        assert_eq!(lod_for_distance(15.9), 2);
+        assert_eq!(lod_for_distance(1000.0), LOD_DISTANCES.len() as u8);
+        assert_eq!(lod_factor(0), 1);
+        assert_eq!(lod_factor(2), 4);
+    }
+
+    #[test]
+    fn downsampling_shrinks_the_grid_by_the_factor() {
+        let stone = solid(BlockType::Stone);
+        let grid = LodGrid::downsample(2, |_| Some(&stone));
+        assert_eq!(grid.size, CHUNK_SIZE / 2);
+        assert_eq!(
+            grid.cells.len(),
+            (grid.size.x * grid.size.y * grid.size.z) as usize
+        );
+        assert!(grid
+            .cells
+            .iter()
+            .all(|cell| cell.block == Some(BlockType::Stone)));
+    }
+
+    #[test]
+    fn cells_take_the_most_common_block_when_half_solid() {
+        let (dirt, stone, lit_air) = (solid(BlockType::Dirt), solid(BlockType::Stone), air(9));
+        // in every 2x2x2 cell: three dirt, one stone and four air lit at 9
+        let grid = LodGrid::downsample(2, |position| {
+            Some(match (position.x % 2, position.y % 2, position.z % 2) {
+                (0, 0, 0) => &stone,
+                (_, 0, _) => &dirt,
+                _ => &lit_air,
+            })
+        });
+        let cell = grid.cell(IVec3::ZERO).unwrap();
+        assert_eq!(cell.block, Some(BlockType::Dirt));
+        assert_eq!(cell.light, 9);
+    }
+
+    #[test]
+    fn cells_mostly_of_air_are_air() {
+        let (stone, dark_air) = (solid(BlockType::Stone), air(0));
+        let grid = LodGrid::downsample(2, |position| {
+            Some(if position == IVec3::ZERO {
+                &stone
+            } else {
+                &dark_air
+            })
+        });
+        assert_eq!(grid.cell(IVec3::ZERO).unwrap().block, None);
+        assert_eq!(grid.cell(IVec3::new(-1, 0, 0)), None);
+        assert_eq!(grid.cell(grid.size), None);
+    }
+
+    #[test]
+    fn a_solid_lod_chunk_only_meshes_its_border() {
+        let stone = solid(BlockType::Stone);
+        let grid = LodGrid::downsample(4, |_| Some(&stone));
+        let mut meshes = ChunkMeshes::default();
+        grid.push_to_mesh(&mut meshes, IVec3::ZERO);
+        let size = grid.size;
+        let border_faces = 2 * (size.x * size.y + size.y * size.z + size.x * size.z);
+        assert_eq!(meshes.opaque.indices.len(), border_faces as usize * 6);
+        assert!(meshes.transparent.is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+use super::{
+    components::{face_vertices, BlockType, Voxel},
+    lod::{lod_factor, LodGrid},
+    CHUNK_SIZE, FACE_DIRECTIONS, FACE_MASK_DEFAULT, MAX_LIGHT_LEVEL, VOXEL_SIZE,
+};
+
+/// How bright a face looks at a light level, each level is 80% as bright as the one above
+pub fn light_brightness(level: u8) -> f32 {
+    0.8f32.powi(i32::from(MAX_LIGHT_LEVEL - level.min(MAX_LIGHT_LEVEL)))
+}
+
+/// Ambient occlusion level of a face corner from the three voxels in front of it that touch the corner,
+/// 0 is fully occluded and 3 is fully open
+pub fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u8 {
+    if side1 && side2 {
+        return 0;
+    }
+    3 - (side1 as u8 + side2 as u8 + corner as u8)
+}
+
+/// How bright a face corner looks at an ambient occlusion level
+pub fn ao_brightness(ao: u8) -> f32 {
+    [0.4, 0.6, 0.8, 1.0][usize::from(ao.min(3))]
+}
+
+/// Ambient occlusion of each vertex of `face` of the voxel at `position`, in `face_vertices` order.
+/// `is_occluder` tells whether the voxel at a world voxel position casts occlusion.
+pub fn face_ao(face: u8, position: IVec3, is_occluder: impl Fn(IVec3) -> bool) -> [u8; 4] {
+    let (_, normal) = FACE_DIRECTIONS
+        .into_iter()
+        .find(|(mask, _)| *mask == face)
+        .expect("not a single face");
+    let front = position + normal;
+    face_vertices(face).map(|(vertex, _, _)| {
+        // which way the corner points along each axis, ignoring the face normal axis
+        let corner = Vec3::from(vertex).signum().as_ivec3() * (IVec3::ONE - normal.abs());
+        let (side1, side2) = if normal.x != 0 {
+            (IVec3::new(0, corner.y, 0), IVec3::new(0, 0, corner.z))
+        } else if normal.y != 0 {
+            (IVec3::new(corner.x, 0, 0), IVec3::new(0, 0, corner.z))
+        } else {
+            (IVec3::new(corner.x, 0, 0), IVec3::new(0, corner.y, 0))
+        };
+        vertex_ao(
+            is_occluder(front + side1),
+            is_occluder(front + side2),
+            is_occluder(front + corner),
+        )
+    })
+}
+
+/// The meshes of one chunk, opaque blocks and transparent blocks are drawn by separate passes
+#[derive(Default)]
+pub struct ChunkMeshes {
+    pub opaque: ChunkMeshBuilder,
+    pub transparent: ChunkMeshBuilder,
+}
+
+impl ChunkMeshes {
+    /// The mesh faces of `block` belong in
+    pub fn builder_for(&mut self, block: BlockType) -> &mut ChunkMeshBuilder {
+        if block.is_transparent() {
+            &mut self.transparent
+        } else {
+            &mut self.opaque
+        }
+    }
+}
+
+/// Collects the visible faces of every voxel in a chunk into a single mesh
+#[derive(Default)]
+pub struct ChunkMeshBuilder {
+    pub(super) positions: Vec<[f32; 3]>,
+    pub(super) normals: Vec<[f32; 3]>,
+    pub(super) uvs: Vec<[f32; 2]>,
+    pub(super) colors: Vec<[f32; 4]>,
+    pub(super) indices: Vec<u32>,
+    /// block of each face, every face is 4 vertices so vertex `i` belongs to `blocks[i / 4]`
+    pub(super) blocks: Vec<BlockType>,
+}
+
+impl ChunkMeshBuilder {
+    /// Adds one face of a `block` voxel centered on `offset` and grown `scale` times, lit at `light` level
+    /// and darkened at each corner by its ambient occlusion level
+    pub fn push_face(
+        &mut self,
+        face: u8,
+        offset: Vec3,
+        scale: f32,
+        block: BlockType,
+        light: u8,
+        ao: [u8; 4],
+    ) {
+        let current_indices_count = self.positions.len() as u32;
+        // split the quad along the brighter diagonal so occlusion is interpolated the same way on every face
+        let order = if ao[0] + ao[2] < ao[1] + ao[3] {
+            [1, 2, 3, 3, 0, 1]
+        } else {
+            [0, 1, 2, 2, 3, 0]
+        };
+        self.indices
+            .extend(order.iter().map(|index| current_indices_count + index));
+        self.blocks.push(block);
+        let brightness = light_brightness(light);
+        let [red, green, blue, alpha] = block.color().as_rgba_f32();
+        for ((position, normal, uv), ao) in face_vertices(face).into_iter().zip(ao) {
+            let brightness = brightness * ao_brightness(ao);
+            self.positions
+                .push((Vec3::from(position) * scale + offset).into());
+            self.normals.push(normal);
+            self.uvs.push(uv);
+            self.colors.push([
+                red * brightness,
+                green * brightness,
+                blue * brightness,
+                alpha,
+            ]);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Reorders the faces so the ones furthest from `eye` are drawn first,
+    /// transparent faces only blend correctly over what is behind them when drawn in this order
+    pub fn sort_back_to_front(&mut self, eye: Vec3) {
+        // every face is 4 vertices and 6 indices, found through its indices as they may have been sorted already
+        let mut faces: Vec<(f32, &[u32])> = self
+            .indices
+            .chunks_exact(6)
+            .map(|face_indices| {
+                let first_vertex = face_indices[0] as usize / 4 * 4;
+                let center = self.positions[first_vertex..first_vertex + 4]
+                    .iter()
+                    .map(|position| Vec3::from(*position))
+                    .sum::<Vec3>()
+                    / 4.0;
+                (center.distance_squared(eye), face_indices)
+            })
+            .collect();
+        faces.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        self.indices = faces
+            .into_iter()
+            .flat_map(|(_, face_indices)| face_indices.iter().copied())
+            .collect();
+    }
+
+    pub fn build(self) -> Mesh {
+        Mesh::new(PrimitiveTopology::TriangleList)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, self.positions)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, self.colors)
+            .with_indices(Some(Indices::U32(self.indices)))
+    }
+}
+
+/// Meshes a chunk at a level of detail, `chunk_origin` is the chunk position in world voxel space.
+/// `voxel_at` looks voxels up by world voxel position, faces come from the masks set by `update_chunk`.
+pub fn build_chunk_meshes<'a>(
+    chunk_origin: IVec3,
+    lod: u8,
+    voxel_at: impl Fn(IVec3) -> Option<&'a Voxel>,
+) -> ChunkMeshes {
+    let mut chunk_meshes = ChunkMeshes::default();
+    if lod > 0 {
+        // far away chunks are meshed from a downsampled copy of their voxels
+        let lod_grid = LodGrid::downsample(lod_factor(lod), |local_position| {
+            voxel_at(chunk_origin + local_position)
+        });
+        lod_grid.push_to_mesh(&mut chunk_meshes, chunk_origin);
+        return chunk_meshes;
+    }
+    // light level of the voxel a face looks into, the sky is fully lit
+    let light_at =
+        |world_position: IVec3| voxel_at(world_position).map_or(MAX_LIGHT_LEVEL, Voxel::light);
+    // opaque voxels cast ambient occlusion onto the faces next to them
+    let is_occluder = |world_position: IVec3| {
+        voxel_at(world_position).is_some_and(|voxel| !voxel.is_transparent())
+    };
+    for x in 0..CHUNK_SIZE.x {
+        for y in 0..CHUNK_SIZE.y {
+            for z in 0..CHUNK_SIZE.z {
+                let world_position = chunk_origin + IVec3::new(x, y, z);
+                let Some(voxel) = voxel_at(world_position) else {
+                    continue;
+                };
+                if !voxel.solid || voxel.mask == FACE_MASK_DEFAULT {
+                    continue;
+                }
+                for (face, direction) in FACE_DIRECTIONS {
+                    if voxel.mask & face == face {
+                        chunk_meshes.builder_for(voxel.block).push_face(
+                            face,
+                            world_position.as_vec3() * VOXEL_SIZE,
+                            1.0,
+                            voxel.block,
+                            light_at(world_position + direction),
+                            face_ao(face, world_position, is_occluder),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    chunk_meshes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::world::{FACE_MASK_TOP, VOXEL_SIZE};
+
+    #[test]
+    fn vertex_ao_corner_values() {
+        assert_eq!(vertex_ao(false, false, false), 3);
+        assert_eq!(vertex_ao(false, false, true), 2);
+        assert_eq!(vertex_ao(true, false, false), 2);
+        assert_eq!(vertex_ao(false, true, true), 1);
+        // two sides close the corner off whatever is in it
+        assert_eq!(vertex_ao(true, true, false), 0);
+        assert_eq!(vertex_ao(true, true, true), 0);
+    }
+
+    #[test]
+    fn face_ao_is_open_without_occluders() {
+        assert_eq!(face_ao(FACE_MASK_TOP, IVec3::ZERO, |_| false), [3; 4]);
+    }
+
+    #[test]
+    fn face_ao_darkens_the_corners_next_to_an_occluder() {
+        // a block on top of the voxel's right neighbour shades the right edge of the top face
+        let occluder = IVec3::new(1, 1, 0);
+        let ao = face_ao(FACE_MASK_TOP, IVec3::ZERO, |position| position == occluder);
+        for ((vertex, _, _), ao) in face_vertices(FACE_MASK_TOP).into_iter().zip(ao) {
+            let expected = if vertex[0] > 0.0 { 2 } else { 3 };
+            assert_eq!(ao, expected, "vertex {vertex:?}");
+        }
+    }
+
+    #[test]
+    fn face_ao_fully_occludes_an_enclosed_corner() {
+        let occluders = [IVec3::new(1, 1, 0), IVec3::new(0, 1, 1)];
+        let ao = face_ao(FACE_MASK_TOP, IVec3::ZERO, |position| {
+            occluders.contains(&position)
+        });
+        for ((vertex, _, _), ao) in face_vertices(FACE_MASK_TOP).into_iter().zip(ao) {
+            let expected = match (vertex[0] > 0.0, vertex[2] > 0.0) {
+                (true, true) => 0,
+                (true, false) | (false, true) => 2,
+                (false, false) => 3,
+            };
+            assert_eq!(ao, expected, "vertex {vertex:?}");
+        }
+    }
+
+    #[test]
+    fn face_ao_darkens_a_corner_with_only_its_diagonal_occluded() {
+        let occluder = IVec3::new(1, 1, 1);
+        let ao = face_ao(FACE_MASK_TOP, IVec3::ZERO, |position| position == occluder);
+        for ((vertex, _, _), ao) in face_vertices(FACE_MASK_TOP).into_iter().zip(ao) {
+            let expected = if vertex[0] > 0.0 && vertex[2] > 0.0 {
+                2
+            } else {
+                3
+            };
+            assert_eq!(ao, expected, "vertex {vertex:?}");
+        }
+    }
+
+    fn face_indices(ao: [u8; 4]) -> Vec<u32> {
+        let mut builder = ChunkMeshBuilder::default();
+        builder.push_face(
+            FACE_MASK_TOP,
+            Vec3::ZERO,
+            1.0,
+            BlockType::Stone,
+            MAX_LIGHT_LEVEL,
+            ao,
+        );
+        builder.indices
+    }
+
+    #[test]
+    fn quads_split_along_the_brighter_diagonal() {
+        assert_eq!(face_indices([3, 3, 3, 3]), vec![0, 1, 2, 2, 3, 0]);
+        // corners 0 and 2 darker than 1 and 3, the split flips to the 1-3 diagonal
+        assert_eq!(face_indices([0, 3, 0, 3]), vec![1, 2, 3, 3, 0, 1]);
+        assert_eq!(face_indices([3, 0, 3, 0]), vec![0, 1, 2, 2, 3, 0]);
+    }
+
+    #[test]
+    fn occlusion_darkens_vertex_colors() {
+        let mut builder = ChunkMeshBuilder::default();
+        builder.push_face(
+            FACE_MASK_TOP,
+            Vec3::ZERO,
+            VOXEL_SIZE,
+            BlockType::Stone,
+            MAX_LIGHT_LEVEL,
+            [3, 2, 1, 0],
+        );
+        let brightness: Vec<f32> = builder.colors.iter().map(|color| color[0]).collect();
+        assert!(brightness.windows(2).all(|pair| pair[0] > pair[1]));
+    }
+
+    fn voxel(block: BlockType) -> Voxel {
+        Voxel {
+            solid: true,
+            block,
+            mask: FACE_DIRECTIONS
+                .iter()
+                .fold(0, |mask, (face, _)| mask | face),
+            sky_light: MAX_LIGHT_LEVEL,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn transparent_blocks_go_in_their_own_mesh() {
+        let (stone, glass, water) = (
+            voxel(BlockType::Stone),
+            voxel(BlockType::Glass),
+            voxel(BlockType::Water),
+        );
+        let chunk_meshes = build_chunk_meshes(IVec3::ZERO, 0, |position| match position {
+            IVec3 { x: 0, y: 0, z: 0 } => Some(&stone),
+            IVec3 { x: 2, y: 0, z: 0 } => Some(&glass),
+            IVec3 { x: 4, y: 0, z: 0 } => Some(&water),
+            _ => None,
+        });
+        assert_eq!(chunk_meshes.opaque.blocks, vec![BlockType::Stone; 6]);
+        assert_eq!(chunk_meshes.transparent.blocks.len(), 12);
+        assert!(chunk_meshes
+            .transparent
+            .blocks
+            .iter()
+            .all(|block| block.is_transparent()));
+    }
+
+    #[test]
+    fn an_empty_chunk_has_empty_meshes() {
+        let chunk_meshes = build_chunk_meshes(IVec3::ZERO, 0, |_| None);
+        assert!(chunk_meshes.opaque.is_empty());
+        assert!(chunk_meshes.transparent.is_empty());
+    }
+
+    /// Center of each face in the order the indices draw them
+    fn face_centers(builder: &ChunkMeshBuilder) -> Vec<Vec3> {
+        builder
+            .indices
+            .chunks_exact(6)
+            .map(|face_indices| {
+                let mut vertices: Vec<u32> = face_indices.to_vec();
+                vertices.sort();
+                vertices.dedup();
+                vertices
+                    .iter()
+                    .map(|index| Vec3::from(builder.positions[*index as usize]))
+                    .sum::<Vec3>()
+                    / 4.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn transparent_faces_are_sorted_back_to_front() {
+        let mut builder = ChunkMeshBuilder::default();
+        for x in 0..4 {
+            builder.push_face(
+                FACE_MASK_TOP,
+                Vec3::new(x as f32, 0.0, 0.0),
+                1.0,
+                BlockType::Glass,
+                MAX_LIGHT_LEVEL,
+                [3; 4],
+            );
+        }
+        let eye = Vec3::new(-5.0, 1.0, 0.0);
+        builder.sort_back_to_front(eye);
+        let distances: Vec<f32> = face_centers(&builder)
+            .iter()
+            .map(|center| center.distance(eye))
+            .collect();
+        assert!(distances.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(builder.indices.len(), 24);
+
+        // from the other side the order flips
+        let eye = Vec3::new(10.0, 1.0, 0.0);
+        builder.sort_back_to_front(eye);
+        let distances: Vec<f32> = face_centers(&builder)
+            .iter()
+            .map(|center| center.distance(eye))
+            .collect();
+        assert!(distances.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+}
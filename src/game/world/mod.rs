@@ -4,9 +4,24 @@ use crate::AppState;
 
 use self::systems::*;
 
-use super::SimulationState;
+use crate::events::{ExportMeshEvent, SetBlockEvent};
 
+use super::{
+    tick::{systems::advance_world_tick, WorldTickSet},
+    SimulationState,
+};
+
+pub mod access;
+pub mod caves;
 pub mod components;
+pub mod culling;
+pub mod export;
+pub mod generation;
+pub mod lighting;
+pub mod lod;
+pub mod meshing;
+pub mod noise;
+pub mod raycast;
 pub mod resources;
 pub mod systems;
 
@@ -29,11 +44,90 @@ pub const FACE_MASK_RIGHT: u8 = 0b000100;
 pub const FACE_MASK_FRONT: u8 = 0b000010;
 pub const FACE_MASK_BACK: u8 = 0b000001;
 
+/// Each face mask bit and the direction of the neighbouring voxel it faces
+pub const FACE_DIRECTIONS: [(u8, IVec3); 6] = [
+    (FACE_MASK_TOP, IVec3::Y),
+    (FACE_MASK_BOTTOM, IVec3::NEG_Y),
+    (FACE_MASK_LEFT, IVec3::NEG_X),
+    (FACE_MASK_RIGHT, IVec3::X),
+    (FACE_MASK_FRONT, IVec3::Z),
+    (FACE_MASK_BACK, IVec3::NEG_Z),
+];
+
+/// Size of a chunk in blocks, matching the axes used by `spawn_world`
+pub const CHUNK_SIZE: IVec3 = IVec3::new(
+    CHUNK_WIDTH_IN_BLOCKS as i32,
+    CHUNK_DEPTH_IN_BLOCKS as i32,
+    CHUNK_HEIGHT_IN_BLOCKS as i32,
+);
+
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// Directory next to the game `/export` writes its OBJ files to
+pub const EXPORT_DIRECTORY_NAME: &str = "exports";
+
+/// Edge length of a voxel in world units
+pub const VOXEL_SIZE: f32 = 0.1;
+
+/// Splits a world voxel position into its chunk position and the position inside that chunk
+pub fn to_chunk_space(world_position: IVec3) -> (IVec3, IVec3) {
+    (
+        world_position.div_euclid(CHUNK_SIZE),
+        world_position.rem_euclid(CHUNK_SIZE),
+    )
+}
+
+/// Voxel storage, generation, lighting and block edits, everything a headless server needs
 pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(AppState::Game), (spawn_world, spawn_light))
-            .add_systems(Update, (update_chunk, mesh_chunk).chain());
+        app.add_event::<SetBlockEvent>()
+            // sent by `/export` wherever commands run, only read where chunks are meshed
+            .add_event::<ExportMeshEvent>()
+            .init_resource::<resources::WorldSeed>()
+            .init_resource::<resources::WorldTerrain>()
+            .init_resource::<resources::NeighbourUpdates>()
+            .add_systems(OnEnter(AppState::Game), spawn_world)
+            .add_systems(
+                Update,
+                (
+                    light_world.run_if(resource_added::<resources::VoxelWorld>()),
+                    set_blocks,
+                )
+                    .chain()
+                    // there is no world outside of a game, like on the multiplayer screen
+                    .run_if(resource_exists::<resources::VoxelWorld>()),
+            )
+            .add_systems(
+                FixedUpdate,
+                advance_neighbour_updates
+                    .after(advance_world_tick)
+                    .in_set(WorldTickSet::Begin),
+            );
+    }
+}
+
+/// Meshing, level of detail and culling of the chunks, and exporting their meshes
+pub struct WorldRenderPlugin;
+
+impl Plugin for WorldRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(AppState::Game), spawn_cube_mesh)
+            .add_systems(
+                Update,
+                (
+                    select_chunk_lod,
+                    resort_transparent_chunks,
+                    update_chunk.run_if(in_state(SimulationState::Running)),
+                    update_chunk_connectivity,
+                    mesh_chunk.run_if(in_state(SimulationState::Running)),
+                    cull_chunks,
+                    export_meshes,
+                )
+                    .chain()
+                    .after(set_blocks)
+                    .run_if(resource_exists::<resources::VoxelWorld>()),
+            );
     }
 }
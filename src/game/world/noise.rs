@@ -0,0 +1,83 @@
+use bevy::prelude::*;
+
+/// Mixes a seed and a lattice position into a well spread 64 bit hash
+pub fn hash(seed: u64, position: IVec3) -> u64 {
+    let mut hash = seed ^ 0x9e37_79b9_7f4a_7c15;
+    for coordinate in position.to_array() {
+        hash ^= coordinate as u32 as u64;
+        // splitmix64 finalizer
+        hash = hash.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        hash ^= hash >> 31;
+    }
+    hash
+}
+
+/// A hash turned into a number in `0.0..1.0`
+pub fn hash_to_unit(seed: u64, position: IVec3) -> f32 {
+    (hash(seed, position) >> 40) as f32 / (1u64 << 24) as f32
+}
+
+fn smooth(t: f32) -> f32 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Smoothly interpolated random values on an integer lattice, in `0.0..1.0`
+pub fn value_noise_2d(seed: u64, position: Vec2) -> f32 {
+    let cell = position.floor();
+    let t = position - cell;
+    let cell = cell.as_ivec2();
+    let corner = |x: i32, z: i32| hash_to_unit(seed, IVec3::new(cell.x + x, 0, cell.y + z));
+    let (tx, tz) = (smooth(t.x), smooth(t.y));
+    let near = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let far = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+    near + (far - near) * tz
+}
+
+/// Several octaves of `value_noise_2d`, each twice the frequency and half the strength of the last.
+/// Stays in `0.0..1.0`.
+pub fn fractal_noise_2d(seed: u64, position: Vec2, octaves: u32) -> f32 {
+    let mut total = 0.0;
+    let mut strength = 1.0;
+    let mut strength_sum = 0.0;
+    let mut frequency = 1.0;
+    for octave in 0..octaves {
+        total += value_noise_2d(seed.wrapping_add(octave as u64), position * frequency) * strength;
+        strength_sum += strength;
+        strength *= 0.5;
+        frequency *= 2.0;
+    }
+    total / strength_sum
+}
+
+/// Smoothly interpolated random values on a 3D integer lattice, in `0.0..1.0`
+pub fn value_noise_3d(seed: u64, position: Vec3) -> f32 {
+    let cell = position.floor();
+    let t = position - cell;
+    let cell = cell.as_ivec3();
+    let corner = |x: i32, y: i32, z: i32| hash_to_unit(seed, cell + IVec3::new(x, y, z));
+    let (tx, ty, tz) = (smooth(t.x), smooth(t.y), smooth(t.z));
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    let plane = |y: i32| {
+        let near = lerp(corner(0, y, 0), corner(1, y, 0), tx);
+        let far = lerp(corner(0, y, 1), corner(1, y, 1), tx);
+        lerp(near, far, tz)
+    };
+    lerp(plane(0), plane(1), ty)
+}
+
+/// Several octaves of `value_noise_3d`, like `fractal_noise_2d`
+pub fn fractal_noise_3d(seed: u64, position: Vec3, octaves: u32) -> f32 {
+    let mut total = 0.0;
+    let mut strength = 1.0;
+    let mut strength_sum = 0.0;
+    let mut frequency = 1.0;
+    for octave in 0..octaves {
+        total += value_noise_3d(seed.wrapping_add(octave as u64), position * frequency) * strength;
+        strength_sum += strength;
+        strength *= 0.5;
+        frequency *= 2.0;
+    }
+    total / strength_sum
+}
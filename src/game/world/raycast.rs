@@ -0,0 +1,69 @@
+use bevy::prelude::*;
+
+use super::VOXEL_SIZE;
+
+/// The first solid voxel a ray runs into
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// world voxel position of the voxel that was hit
+    pub position: IVec3,
+    /// which face was hit, pointing out of the voxel, zero when the ray starts inside it
+    pub normal: IVec3,
+    /// along the ray, in voxels
+    pub distance: f32,
+}
+
+/// The world voxel position of the voxel a point in world units is inside, voxels are centered on their position
+pub fn voxel_at_point(point: Vec3) -> IVec3 {
+    (point / VOXEL_SIZE).round().as_ivec3()
+}
+
+/// Steps through every voxel a ray passes, in order, and returns the first one `is_solid` accepts.
+/// `origin` is in world units, `max_distance` in voxels.
+pub fn raycast(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    is_solid: impl Fn(IVec3) -> bool,
+) -> Option<RaycastHit> {
+    let direction = direction.try_normalize()?;
+    // in voxel units with the voxel edges on whole numbers
+    let start = origin / VOXEL_SIZE + Vec3::splat(0.5);
+    let mut position = start.floor().as_ivec3();
+    let step = direction.signum().as_ivec3();
+    // distance along the ray to cross one voxel on each axis, and to the first edge crossed
+    let delta = direction.recip().abs();
+    let next_edge = |axis: usize| {
+        if direction[axis] > 0.0 {
+            (position[axis] as f32 + 1.0 - start[axis]) * delta[axis]
+        } else {
+            (start[axis] - position[axis] as f32) * delta[axis]
+        }
+    };
+    let mut edge = Vec3::new(next_edge(0), next_edge(1), next_edge(2));
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.0;
+    while distance <= max_distance {
+        if is_solid(position) {
+            return Some(RaycastHit {
+                position,
+                normal,
+                distance,
+            });
+        }
+        // step across whichever edge is closest, axes the ray runs along never get crossed
+        let axis = if edge.x < edge.y && edge.x < edge.z {
+            0
+        } else if edge.y < edge.z {
+            1
+        } else {
+            2
+        };
+        distance = edge[axis];
+        edge[axis] += delta[axis];
+        position[axis] += step[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+    None
+}
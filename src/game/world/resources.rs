@@ -1,4 +1,10 @@
-use bevy::{math::IVec3, prelude::*, utils::HashMap};
+use bevy::{
+    math::IVec3,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use super::{generation::TerrainShape, to_chunk_space, CHUNK_SIZE};
 
 pub struct Chunk {
     pub entity_id: Entity,
@@ -8,11 +14,103 @@ pub struct Chunk {
 #[derive(Resource)]
 pub struct VoxelWorld {
     pub chunks: HashMap<IVec3, Chunk>,
-    pub mesh_cache: HashMap<u8, Handle<Mesh>>,
 }
 
+impl VoxelWorld {
+    /// The block entity at a world voxel position, if that chunk is loaded
+    pub fn voxel_entity(&self, world_position: IVec3) -> Option<Entity> {
+        let (chunk_position, local_position) = to_chunk_space(world_position);
+        self.chunks
+            .get(&chunk_position)?
+            .blocks
+            .get(&local_position)
+            .copied()
+    }
+
+    /// World voxel position of every voxel in every loaded chunk
+    pub fn voxel_positions(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.chunks.iter().flat_map(|(chunk_position, chunk)| {
+            chunk
+                .blocks
+                .keys()
+                .map(move |local_position| *chunk_position * CHUNK_SIZE + *local_position)
+        })
+    }
+}
+
+#[cfg(test)]
+impl VoxelWorld {
+    /// Spawns a world of one chunk at the origin, holding only the air voxels inside `size`
+    pub fn spawn_test_world(world: &mut World, size: IVec3) {
+        let chunk_id = world
+            .spawn(super::components::Chunk { updated: false })
+            .id();
+        let mut blocks = HashMap::new();
+        for x in 0..size.x {
+            for y in 0..size.y {
+                for z in 0..size.z {
+                    let voxel_id = world.spawn(super::components::Voxel::default()).id();
+                    blocks.insert(IVec3::new(x, y, z), voxel_id);
+                }
+            }
+        }
+        let mut voxel_world = VoxelWorld {
+            chunks: HashMap::new(),
+        };
+        voxel_world.chunks.insert(
+            IVec3::ZERO,
+            Chunk {
+                entity_id: chunk_id,
+                blocks,
+            },
+        );
+        world.insert_resource(voxel_world);
+    }
+}
+
+/// Voxels next to a block that changed. Changes collected during one world tick
+/// are handed out on the next, so every system reacting to them sees the same list.
+#[derive(Resource, Debug, Default)]
+pub struct NeighbourUpdates {
+    pending: HashSet<IVec3>,
+    current: Vec<IVec3>,
+}
+
+impl NeighbourUpdates {
+    pub fn push(&mut self, position: IVec3) {
+        self.pending.insert(position);
+    }
+
+    /// Voxels whose neighbour changed before this world tick, in position order
+    pub fn current(&self) -> &[IVec3] {
+        &self.current
+    }
+
+    /// Starts a new world tick, handing out everything collected during the last one
+    pub fn advance(&mut self) {
+        self.current = self.pending.drain().collect();
+        self.current.sort_by_key(|position| position.to_array());
+    }
+}
+
+/// Seed every random part of the world is derived from
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldSeed(pub u64);
+
+impl Default for WorldSeed {
+    fn default() -> Self {
+        WorldSeed(rand::random())
+    }
+}
+
+/// Generator the world terrain is shaped by
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WorldTerrain(pub TerrainShape);
+
 #[derive(Resource)]
 pub struct CubeMesh {
     pub mesh_handle: Handle<Mesh>,
     pub material_handle: Handle<StandardMaterial>,
+    /// blended material for water, glass and leaves
+    pub transparent_material_handle: Handle<StandardMaterial>,
 }
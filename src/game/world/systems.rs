@@ -1,33 +1,26 @@
-use bevy::{prelude::*, utils::HashMap};
-use rand::random;
-
-use crate::game::SimulationState;
+use crate::events::{ExportMeshEvent, SetBlockEvent};
+use bevy::{
+    prelude::*,
+    render::primitives::{Aabb, Frustum},
+    utils::HashMap,
+};
 
 use super::{
+    access::{VoxelAccess, WorldVoxels},
     components,
+    culling::{chunk_aabb, visible_chunks, ChunkConnectivity},
+    export::ObjWriter,
+    generation::WorldGenerator,
+    lighting,
+    lod::lod_for_distance,
+    meshing::{build_chunk_meshes, ChunkMeshes},
     resources::{self, CubeMesh, VoxelWorld},
-    CHUNK_DEPTH_IN_BLOCKS, CHUNK_HEIGHT_IN_BLOCKS, CHUNK_WIDTH_IN_BLOCKS, FACE_MASK_BACK,
-    FACE_MASK_BOTTOM, FACE_MASK_DEFAULT, FACE_MASK_FRONT, FACE_MASK_LEFT, FACE_MASK_RIGHT,
-    FACE_MASK_TOP, WORLD_DEPTH_IN_CHUNKS, WORLD_HEIGHT_IN_CHUNKS, WORLD_WIDTH_IN_CHUNKS,
+    to_chunk_space, CHUNK_DEPTH_IN_BLOCKS, CHUNK_HEIGHT_IN_BLOCKS, CHUNK_SIZE,
+    CHUNK_WIDTH_IN_BLOCKS, FACE_MASK_BACK, FACE_MASK_BOTTOM, FACE_MASK_DEFAULT, FACE_MASK_FRONT,
+    FACE_MASK_LEFT, FACE_MASK_RIGHT, FACE_MASK_TOP, VOXEL_SIZE, WORLD_DEPTH_IN_CHUNKS,
+    WORLD_HEIGHT_IN_CHUNKS, WORLD_WIDTH_IN_CHUNKS,
 };
 
-pub fn spawn_light(mut commands: Commands) {
-    let light = (
-        PointLightBundle {
-            point_light: PointLight {
-                intensity: 600.0,
-                color: Color::WHITE.as_lcha(),
-                ..default()
-            },
-            transform: Transform::from_xyz(0.0, 5.0, 0.0),
-            ..default()
-        },
-        Name::new("World Light"),
-    );
-
-    commands.spawn(light);
-}
-
 type VoxelBundle = (
     components::ChunkCoordinate,
     components::Voxel,
@@ -37,31 +30,45 @@ type VoxelBundle = (
 
 pub fn spawn_world(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    world_seed: Res<resources::WorldSeed>,
+    world_terrain: Res<resources::WorldTerrain>,
 ) {
+    // generate every chunk before taking any out, so decorations can reach across chunk borders
+    let mut generator = WorldGenerator::new(world_seed.0, world_terrain.0);
+    for x in 0..WORLD_WIDTH_IN_CHUNKS {
+        for y in 0..WORLD_DEPTH_IN_CHUNKS {
+            for z in 0..WORLD_HEIGHT_IN_CHUNKS {
+                generator.generate_chunk(IVec3::new(x.into(), y.into(), z.into()));
+            }
+        }
+    }
+
     // takes an chunk position in chunk space and creates blocks inside it
-    let spawn_chunk = |chunk_x: u16, chunk_y: u16, chunk_z: u16| -> Vec<VoxelBundle> {
+    let mut spawn_chunk = |chunk_x: u16, chunk_y: u16, chunk_z: u16| -> Vec<VoxelBundle> {
+        let chunk_blocks = generator
+            .take_chunk(IVec3::new(chunk_x.into(), chunk_y.into(), chunk_z.into()))
+            .unwrap_or_default();
         let mut blocks: Vec<VoxelBundle> = vec![];
         for x in 0..CHUNK_WIDTH_IN_BLOCKS {
             for y in 0..CHUNK_DEPTH_IN_BLOCKS {
                 for z in 0..CHUNK_HEIGHT_IN_BLOCKS {
                     // figure out the transform for the block
                     let voxel_world_position = Vec3::new(
-                        f32::from(x + (chunk_x * CHUNK_WIDTH_IN_BLOCKS)) * 0.1,
-                        f32::from(y + (chunk_y * CHUNK_DEPTH_IN_BLOCKS)) * 0.1,
-                        f32::from(z + (chunk_z * CHUNK_HEIGHT_IN_BLOCKS)) * 0.1,
+                        f32::from(x + (chunk_x * CHUNK_WIDTH_IN_BLOCKS)) * VOXEL_SIZE,
+                        f32::from(y + (chunk_y * CHUNK_DEPTH_IN_BLOCKS)) * VOXEL_SIZE,
+                        f32::from(z + (chunk_z * CHUNK_HEIGHT_IN_BLOCKS)) * VOXEL_SIZE,
                     );
                     // create a nice name for bevy inspector
                     let name = format!("Block ({x}, {y}, {z})");
-                    // randomly make the block solid
-                    // TODO use perlin nosie?
-                    let is_solid = random::<bool>();
+                    let block = chunk_blocks
+                        .get(&IVec3::new(x.into(), y.into(), z.into()))
+                        .copied();
 
                     blocks.push((
                         components::ChunkCoordinate(x, y, z),
                         components::Voxel {
-                            solid: is_solid,
+                            solid: block.is_some(),
+                            block: block.unwrap_or_default(),
                             ..default()
                         },
                         components::WorldCoordinate::from_vec3(voxel_world_position),
@@ -75,7 +82,6 @@ pub fn spawn_world(
 
     let mut voxel_world = resources::VoxelWorld {
         chunks: HashMap::new(),
-        mesh_cache: HashMap::new(),
     };
 
     // for each chunk in
@@ -90,11 +96,18 @@ pub fn spawn_world(
                     .spawn((
                         SpatialBundle::default(),
                         components::Chunk { updated: true },
+                        components::ChunkLod::default(),
+                        ChunkConnectivity::default(),
                         components::WorldCoordinate::from_xyz(x as f32, y as f32, z as f32),
                         Name::new(name),
                     ))
                     // and add children
                     .with_children(|parent| {
+                        parent.spawn((
+                            SpatialBundle::default(),
+                            components::TransparentChunkMesh,
+                            Name::new("Transparent Mesh"),
+                        ));
                         for (chunk_coordinate, voxel, world_coordinate, name) in
                             spawn_chunk(x, y, z)
                         {
@@ -119,22 +132,181 @@ pub fn spawn_world(
             }
         }
     }
+    commands.insert_resource(voxel_world);
+}
+
+/// Creates the mesh and materials shared by everything drawn out of voxels
+pub fn spawn_cube_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
     commands.insert_resource(resources::CubeMesh {
-        mesh_handle: meshes.add(Mesh::from(shape::Cube::new(0.1))),
-        material_handle: materials.add(Color::SEA_GREEN.into()),
+        mesh_handle: meshes.add(Mesh::from(shape::Cube::new(VOXEL_SIZE))),
+        // blocks are colored through their vertex colors
+        material_handle: materials.add(Color::WHITE.into()),
+        transparent_material_handle: materials.add(StandardMaterial {
+            alpha_mode: AlphaMode::Blend,
+            ..Color::WHITE.into()
+        }),
     });
-    commands.insert_resource(voxel_world);
+}
+
+/// Lights a freshly spawned world, sky light falls in from the top and block light spreads from emissive blocks
+pub fn light_world(mut voxels: WorldVoxels) {
+    let positions: Vec<IVec3> = voxels.voxel_world.voxel_positions().collect();
+    lighting::light_voxels(&mut voxels, positions.into_iter());
+    // the whole world gets meshed after spawning anyway
+    voxels.take_touched_chunks();
+}
+
+/// Applies `SetBlockEvent`s to the world, relights around them and flags the chunks they touch for remeshing
+pub fn set_blocks(mut set_block_events: EventReader<SetBlockEvent>, mut voxels: WorldVoxels) {
+    for event in set_block_events.read() {
+        voxels.set_block(event.position, event.block, event.state);
+    }
+    voxels.update_touched_chunks();
+}
+
+/// Hands the neighbour changes of the last world tick to this one
+pub fn advance_neighbour_updates(mut neighbour_updates: ResMut<resources::NeighbourUpdates>) {
+    neighbour_updates.advance();
+}
+
+/// Picks the level of detail of each chunk from how far its center is from the camera
+pub fn select_chunk_lod(
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut chunk_query: Query<(
+        &mut components::Chunk,
+        &mut components::ChunkLod,
+        &components::WorldCoordinate,
+    )>,
+) {
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    for (mut chunk, mut chunk_lod, chunk_world_coordinate) in chunk_query.iter_mut() {
+        let chunk_center =
+            (chunk_world_coordinate.into_translation() + 0.5) * CHUNK_SIZE.as_vec3() * VOXEL_SIZE;
+        let lod = lod_for_distance(camera_transform.translation().distance(chunk_center));
+        if chunk_lod.0 != lod {
+            chunk_lod.0 = lod;
+            chunk.updated = true;
+        }
+    }
+}
+
+/// Works out which faces of each updated chunk can see each other, used by `cull_chunks`
+pub fn update_chunk_connectivity(
+    voxel_world: Res<VoxelWorld>,
+    mut chunk_query: Query<(
+        &components::Chunk,
+        &components::WorldCoordinate,
+        &mut ChunkConnectivity,
+    )>,
+    voxel_query: Query<&components::Voxel>,
+) {
+    for (chunk, chunk_world_coordinate, mut connectivity) in chunk_query.iter_mut() {
+        if !chunk.updated {
+            continue;
+        }
+        let chunk_origin = chunk_world_coordinate.into_translation().as_ivec3() * CHUNK_SIZE;
+        *connectivity = ChunkConnectivity::compute(|local_position| {
+            voxel_world
+                .voxel_entity(chunk_origin + local_position)
+                .and_then(|entity| voxel_query.get(entity).ok())
+                .is_some_and(|voxel| !voxel.is_transparent())
+        });
+    }
+}
+
+/// Hides chunks outside the camera frustum and chunks walled off from the camera by solid terrain
+pub fn cull_chunks(
+    camera_query: Query<(&GlobalTransform, &Frustum), With<Camera3d>>,
+    mut chunk_query: Query<
+        (
+            &components::WorldCoordinate,
+            &ChunkConnectivity,
+            &mut Visibility,
+        ),
+        With<components::Chunk>,
+    >,
+) {
+    let Ok((camera_transform, frustum)) = camera_query.get_single() else {
+        return;
+    };
+    let in_frustum = |chunk_position: IVec3| {
+        frustum.intersects_obb(&chunk_aabb(chunk_position), &Default::default(), true, true)
+    };
+    let connectivity: HashMap<IVec3, ChunkConnectivity> = chunk_query
+        .iter()
+        .map(|(chunk_world_coordinate, connectivity, _)| {
+            (
+                chunk_world_coordinate.into_translation().as_ivec3(),
+                *connectivity,
+            )
+        })
+        .collect();
+    let camera_voxel = (camera_transform.translation() / VOXEL_SIZE)
+        .round()
+        .as_ivec3();
+    let camera_chunk = to_chunk_space(camera_voxel).0;
+
+    let visible = visible_chunks(camera_chunk, &connectivity, in_frustum);
+    for (chunk_world_coordinate, _, mut visibility) in chunk_query.iter_mut() {
+        let chunk_position = chunk_world_coordinate.into_translation().as_ivec3();
+        // from outside the world there is nothing to walk through, so fall back to the frustum alone
+        let is_visible = if connectivity.contains_key(&camera_chunk) {
+            visible.contains(&chunk_position)
+        } else {
+            in_frustum(chunk_position)
+        };
+        let new_visibility = if is_visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        // only write on change so the rest of bevy does not see every chunk as modified
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+    }
+}
+
+/// Remeshes chunks with transparent faces once the camera moves into another chunk,
+/// so their faces get sorted back to front from the new point of view
+pub fn resort_transparent_chunks(
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
+    mut last_camera_chunk: Local<Option<IVec3>>,
+    mut chunk_query: Query<(&mut components::Chunk, &Children)>,
+    transparent_query: Query<(), (With<components::TransparentChunkMesh>, With<Handle<Mesh>>)>,
+) {
+    let Ok(camera_transform) = camera_query.get_single() else {
+        return;
+    };
+    let camera_voxel = (camera_transform.translation() / VOXEL_SIZE)
+        .round()
+        .as_ivec3();
+    let camera_chunk = to_chunk_space(camera_voxel).0;
+    if *last_camera_chunk == Some(camera_chunk) {
+        return;
+    }
+    *last_camera_chunk = Some(camera_chunk);
+    for (mut chunk, children) in chunk_query.iter_mut() {
+        if children
+            .iter()
+            .any(|child| transparent_query.contains(*child))
+        {
+            chunk.updated = true;
+        }
+    }
 }
 
 pub fn update_chunk(
     voxel_world: Res<resources::VoxelWorld>,
     chunk_query: Query<(&components::Chunk, &components::WorldCoordinate, &Children)>,
     mut block_query: Query<(&components::ChunkCoordinate, &mut components::Voxel)>,
-    simulation_state: Res<State<SimulationState>>,
 ) {
-    if *simulation_state.get() == SimulationState::Paused {
-        return;
-    }
     for (chunk, chunk_world_coordinate, children) in chunk_query.iter() {
         if !chunk.updated {
             continue;
@@ -161,8 +333,8 @@ pub fn update_chunk(
                         chunk_resource.blocks.get(&IVec3 { x: x + 1, y, z })
                     {
                         if let Ok((_, adjacent_voxel)) = block_query.get(*next_block_entity) {
-                            // but its not solid
-                            if !adjacent_voxel.solid {
+                            // but we can see it from here
+                            if voxel.shows_face_towards(adjacent_voxel) {
                                 mask |= FACE_MASK_RIGHT;
                             }
                         }
@@ -175,8 +347,8 @@ pub fn update_chunk(
                         chunk_resource.blocks.get(&IVec3 { x: x - 1, y, z })
                     {
                         if let Ok((_, adjacent_voxel)) = block_query.get(*prev_block_entity) {
-                            // but its not solid
-                            if !adjacent_voxel.solid {
+                            // but we can see it from here
+                            if voxel.shows_face_towards(adjacent_voxel) {
                                 mask |= FACE_MASK_LEFT;
                             }
                         }
@@ -189,7 +361,7 @@ pub fn update_chunk(
                         chunk_resource.blocks.get(&IVec3 { x, y: y + 1, z })
                     {
                         if let Ok((_, adjacent_voxel)) = block_query.get(*prev_block_entity) {
-                            if !adjacent_voxel.solid {
+                            if voxel.shows_face_towards(adjacent_voxel) {
                                 mask |= FACE_MASK_TOP;
                             }
                         }
@@ -200,7 +372,7 @@ pub fn update_chunk(
                         chunk_resource.blocks.get(&IVec3 { x, y: y - 1, z })
                     {
                         if let Ok((_, adjacent_voxel)) = block_query.get(*next_block_entity) {
-                            if !adjacent_voxel.solid {
+                            if voxel.shows_face_towards(adjacent_voxel) {
                                 mask |= FACE_MASK_BOTTOM;
                             }
                         }
@@ -212,7 +384,7 @@ pub fn update_chunk(
                         chunk_resource.blocks.get(&IVec3 { x, y, z: z + 1 })
                     {
                         if let Ok((_, adjacent_voxel)) = block_query.get(*prev_block_entity) {
-                            if !adjacent_voxel.solid {
+                            if voxel.shows_face_towards(adjacent_voxel) {
                                 mask |= FACE_MASK_FRONT;
                             }
                         }
@@ -223,7 +395,7 @@ pub fn update_chunk(
                         chunk_resource.blocks.get(&IVec3 { x, y, z: z - 1 })
                     {
                         if let Ok((_, adjacent_voxel)) = block_query.get(*next_block_entity) {
-                            if !adjacent_voxel.solid {
+                            if voxel.shows_face_towards(adjacent_voxel) {
                                 mask |= FACE_MASK_BACK;
                             }
                         }
@@ -240,52 +412,112 @@ pub fn update_chunk(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn mesh_chunk(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     cube_mesh: Res<CubeMesh>,
-    // mut materials: ResMut<Assets<StandardMaterial>>,
-    mut voxel_world: ResMut<VoxelWorld>,
-    mut chunk_query: Query<(&mut components::Chunk, &Children)>,
-    voxel_query: Query<(&components::WorldCoordinate, &components::Voxel)>,
-    simulation_state: Res<State<SimulationState>>,
+    voxel_world: Res<VoxelWorld>,
+    mut chunk_query: Query<(
+        Entity,
+        &mut components::Chunk,
+        &components::ChunkLod,
+        &components::WorldCoordinate,
+        &Children,
+    )>,
+    voxel_query: Query<&components::Voxel>,
+    transparent_query: Query<(), With<components::TransparentChunkMesh>>,
+    camera_query: Query<&GlobalTransform, With<Camera3d>>,
 ) {
-    if *simulation_state.get() == SimulationState::Paused {
-        return;
-    }
-    // TODO reuse material
-    // let material_handle = materials.add(Color::SEA_GREEN.into());
+    let eye = camera_query
+        .get_single()
+        .map_or(Vec3::ZERO, |camera_transform| {
+            camera_transform.translation()
+        });
+    let voxel_at = |world_position: IVec3| {
+        voxel_world
+            .voxel_entity(world_position)
+            .and_then(|entity| voxel_query.get(entity).ok())
+    };
     // for each chunk
-    for (mut chunk, children) in chunk_query.iter_mut() {
+    for (chunk_entity, mut chunk, chunk_lod, chunk_world_coordinate, children) in
+        chunk_query.iter_mut()
+    {
         // if the chunk was not updated
         if !chunk.updated {
             // skip this loop
             continue;
         }
-        // for each voxel in the chunk
-        for entity in children.iter() {
-            // get the voxel from the ecs
-            if let Ok((world_coordinate, voxel)) = voxel_query.get(*entity) {
-                if voxel.solid || voxel.mask != FACE_MASK_DEFAULT {
-                    // add an updated mesh
-                    // TODO remove the old mesh?
-                    if !voxel_world.mesh_cache.contains_key(&voxel.mask) {
-                        voxel_world
-                            .mesh_cache
-                            .insert(voxel.mask, meshes.add(Mesh::from(voxel)));
-                    }
-                    let mesh = voxel_world.mesh_cache.get(&voxel.mask).unwrap().clone();
-                    let material = cube_mesh.material_handle.clone();
-                    commands.entity(*entity).insert(PbrBundle {
-                        mesh,
-                        transform: world_coordinate.into_transform(),
-                        material,
-                        ..default()
-                    });
-                }
+        let chunk_origin = chunk_world_coordinate.into_translation().as_ivec3() * CHUNK_SIZE;
+        let chunk_meshes = build_chunk_meshes(chunk_origin, chunk_lod.0, voxel_at);
+        // replace the old chunk mesh, the bounds are recalculated once the aabb is gone
+        commands.entity(chunk_entity).remove::<Aabb>().insert((
+            meshes.add(chunk_meshes.opaque.build()),
+            cube_mesh.material_handle.clone(),
+        ));
+        let ChunkMeshes {
+            mut transparent, ..
+        } = chunk_meshes;
+        for transparent_entity in children
+            .iter()
+            .filter(|child| transparent_query.contains(**child))
+        {
+            let mut transparent_commands = commands.entity(*transparent_entity);
+            transparent_commands.remove::<(Aabb, Handle<Mesh>)>();
+            if !transparent.is_empty() {
+                transparent.sort_back_to_front(eye);
+                transparent_commands.insert((
+                    meshes.add(std::mem::take(&mut transparent).build()),
+                    cube_mesh.transparent_material_handle.clone(),
+                ));
             }
         }
         // chunk has now been updated
         chunk.updated = false;
     }
 }
+
+/// Writes the meshes of every loaded chunk, at the level of detail they are drawn at, to OBJ files
+pub fn export_meshes(
+    mut export_mesh_events: EventReader<ExportMeshEvent>,
+    voxel_world: Res<VoxelWorld>,
+    chunk_query: Query<
+        (&components::ChunkLod, &components::WorldCoordinate),
+        With<components::Chunk>,
+    >,
+    voxel_query: Query<&components::Voxel>,
+) {
+    let voxel_at = |world_position: IVec3| {
+        voxel_world
+            .voxel_entity(world_position)
+            .and_then(|entity| voxel_query.get(entity).ok())
+    };
+    for event in export_mesh_events.read() {
+        let mut chunks: Vec<_> = chunk_query
+            .iter()
+            .map(|(chunk_lod, chunk_world_coordinate)| {
+                (
+                    chunk_world_coordinate.into_translation().as_ivec3(),
+                    chunk_lod.0,
+                )
+            })
+            .collect();
+        // a stable order keeps exports of the same world identical
+        chunks.sort_by_key(|(chunk_position, _)| chunk_position.to_array());
+
+        let mut writer = ObjWriter::default();
+        for (chunk_position, lod) in chunks {
+            let chunk_meshes = build_chunk_meshes(chunk_position * CHUNK_SIZE, lod, voxel_at);
+            let name = format!(
+                "chunk_{}_{}_{}",
+                chunk_position.x, chunk_position.y, chunk_position.z
+            );
+            writer.push_mesh(&name, &chunk_meshes.opaque);
+            writer.push_mesh(&format!("{name}_transparent"), &chunk_meshes.transparent);
+        }
+        match writer.save(&event.path) {
+            Ok(()) => info!("Exported world mesh to {}", event.path.display()),
+            Err(error) => error!("Could not export world mesh: {error}"),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use bevy::prelude::*;
+
+pub mod bytes;
+#[cfg(feature = "chunk_debug")]
+pub mod chunk_debug;
+pub mod client;
+pub mod create_world;
+pub mod debug_overlay;
+pub mod events;
+pub mod game;
+mod main_menu;
+pub mod multiplayer;
+pub mod network;
+mod options;
+pub mod server;
+mod systems;
+
+#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum AppState {
+    MainMenu,
+    #[default]
+    Game,
+    Death,
+    Options,
+    CreateWorld,
+    LoadWorld,
+    Multiplayer,
+}
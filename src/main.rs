@@ -1,30 +1,38 @@
-use bevy::prelude::*;
-use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use game::GamePlugin;
-use systems::*;
+use std::process;
 
-mod create_world;
-pub mod events;
-mod game;
-mod main_menu;
-mod options;
-mod systems;
+use bevy::prelude::*;
+use voxel_game::{
+    client::{resources::ClientSettings, ClientPlugin},
+    create_world::CreateWorldPlugin,
+    debug_overlay::DebugOverlayPlugin,
+    game::{GamePlugin, GameRenderPlugin},
+    multiplayer::MultiplayerPlugin,
+    AppState,
+};
 
 fn main() {
-    App::new()
-        .add_plugins((DefaultPlugins, GamePlugin, WorldInspectorPlugin::new()))
-        .add_state::<AppState>()
-        .run();
-}
+    let client_settings = ClientSettings::parse(std::env::args().skip(1)).unwrap_or_else(|error| {
+        eprintln!("{error}\n{}", ClientSettings::USAGE);
+        process::exit(2);
+    });
 
-#[derive(States, Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
-pub enum AppState {
-    MainMenu,
-    #[default]
-    Game,
-    Death,
-    Options,
-    CreateWorld,
-    LoadWorld,
-    Multiplayer,
+    let mut app = App::new();
+    app.add_plugins((
+        DefaultPlugins,
+        GamePlugin,
+        GameRenderPlugin,
+        ClientPlugin,
+        MultiplayerPlugin,
+        CreateWorldPlugin,
+        DebugOverlayPlugin,
+    ))
+    .add_state::<AppState>();
+    #[cfg(feature = "chunk_debug")]
+    app.add_plugins(voxel_game::chunk_debug::ChunkDebugPlugin);
+    if client_settings.open_server_browser {
+        app.insert_resource(State::new(AppState::Multiplayer));
+    } else if client_settings.open_create_world {
+        app.insert_resource(State::new(AppState::CreateWorld));
+    }
+    app.insert_resource(client_settings).run();
 }
@@ -0,0 +1,38 @@
+mod systems;
+
+pub mod resources;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy_egui::EguiPlugin;
+
+use crate::AppState;
+
+use self::{resources::*, systems::*};
+
+/// File saved servers are kept in, next to the game
+pub const SERVER_LIST_FILE_NAME: &str = "servers.txt";
+
+/// How often the servers on the multiplayer screen are asked for their status again
+pub const STATUS_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The multiplayer screen: saved and local network servers with their status, and direct connect
+pub struct MultiplayerPlugin;
+
+impl Plugin for MultiplayerPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<EguiPlugin>() {
+            app.add_plugins(EguiPlugin);
+        }
+        app.init_resource::<ServerListPath>()
+            .add_systems(OnEnter(AppState::Multiplayer), open_server_browser)
+            .add_systems(
+                Update,
+                (query_servers, server_browser_ui).chain().run_if(
+                    in_state(AppState::Multiplayer).and_then(resource_exists::<ServerBrowser>()),
+                ),
+            )
+            .add_systems(OnExit(AppState::Multiplayer), close_server_browser);
+    }
+}
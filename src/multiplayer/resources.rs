@@ -0,0 +1,305 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::network::{
+    discovery::{status_query, ServerStatus},
+    DISCOVERY_PORT, MAX_PACKET_SIZE,
+};
+
+use super::SERVER_LIST_FILE_NAME;
+
+/// A server the player saved to the list
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedServer {
+    pub name: String,
+    /// as typed in, resolved every time it is queried
+    pub address: String,
+}
+
+impl SavedServer {
+    pub fn resolve(&self) -> Option<SocketAddr> {
+        self.address.to_socket_addrs().ok()?.next()
+    }
+}
+
+/// Saved servers, stored as `server=<address> <name>` lines
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct ServerList {
+    pub servers: Vec<SavedServer>,
+}
+
+impl ServerList {
+    /// Reads a list written by `to_text`, lines that are unreadable are skipped
+    pub fn from_text(text: &str) -> Self {
+        let mut list = ServerList::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "server" => {
+                    let value = value.trim();
+                    let (address, name) = value.split_once(' ').unwrap_or((value, value));
+                    if !address.is_empty() {
+                        list.servers.push(SavedServer {
+                            name: name.trim().to_string(),
+                            address: address.to_string(),
+                        });
+                    }
+                }
+                _ => warn!("Unknown server list key {key}"),
+            }
+        }
+        list
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for server in &self.servers {
+            // names are one line, addresses have no spaces
+            let name = server.name.replace(['\n', '\r'], " ");
+            let _ = writeln!(text, "server={} {name}", server.address.trim());
+        }
+        text
+    }
+
+    /// The saved list, empty when nothing has been saved yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(text) => Ok(ServerList::from_text(&text)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(ServerList::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(directory) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, self.to_text())
+    }
+}
+
+/// Where the server list is saved to
+#[derive(Resource, Debug, Clone)]
+pub struct ServerListPath(pub PathBuf);
+
+impl Default for ServerListPath {
+    fn default() -> Self {
+        ServerListPath(PathBuf::from(SERVER_LIST_FILE_NAME))
+    }
+}
+
+/// A server that answered a status query
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueriedServer {
+    pub status: ServerStatus,
+    /// round trip of the status query
+    pub ping: Duration,
+    /// found by broadcasting on the local network rather than from the saved list
+    pub on_lan: bool,
+}
+
+/// Queries saved and local network servers for their status, while the multiplayer screen is open
+#[derive(Resource, Debug)]
+pub struct ServerBrowser {
+    socket: UdpSocket,
+    /// every query in a round shares a nonce, answers to older rounds are ignored
+    nonce: u64,
+    query_sent: Duration,
+    /// answers by the address to join the server on
+    pub servers: HashMap<SocketAddr, QueriedServer>,
+    /// what is typed in the direct connect box
+    pub address_input: String,
+    pub name_input: String,
+    pub error: Option<String>,
+}
+
+impl ServerBrowser {
+    pub fn open() -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(ServerBrowser {
+            socket,
+            nonce: 0,
+            query_sent: Duration::ZERO,
+            servers: HashMap::new(),
+            address_input: String::new(),
+            name_input: String::new(),
+            error: None,
+        })
+    }
+
+    /// Whether the last round of queries was sent more than `interval` ago
+    pub fn refresh_due(&self, now: Duration, interval: Duration) -> bool {
+        now.saturating_sub(self.query_sent) >= interval
+    }
+
+    /// Starts a new round of queries to the saved servers and the local network
+    pub fn refresh(&mut self, server_list: &ServerList, now: Duration) {
+        self.nonce += 1;
+        self.query_sent = now;
+        let query = status_query(self.nonce);
+        let targets = server_list
+            .servers
+            .iter()
+            .filter_map(SavedServer::resolve)
+            .chain([
+                SocketAddr::from((Ipv4Addr::BROADCAST, DISCOVERY_PORT)),
+                // broadcasts do not reach servers on this machine everywhere
+                SocketAddr::from((Ipv4Addr::LOCALHOST, DISCOVERY_PORT)),
+            ]);
+        for target in targets {
+            // an unreachable server just does not answer
+            let _ = self.socket.send_to(&query, target);
+        }
+    }
+
+    /// Takes in the answers that arrived since the last call
+    pub fn receive(&mut self, now: Duration) {
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        loop {
+            let (length, source) = match self.socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                Err(_) => continue,
+            };
+            let Ok((nonce, status)) = ServerStatus::from_bytes(&buffer[..length]) else {
+                continue;
+            };
+            if nonce != self.nonce {
+                continue;
+            }
+            let on_lan = source.port() == DISCOVERY_PORT;
+            let address = SocketAddr::new(source.ip(), status.port);
+            let on_lan = on_lan
+                || self
+                    .servers
+                    .get(&address)
+                    .is_some_and(|server| server.on_lan);
+            self.servers.insert(
+                address,
+                QueriedServer {
+                    status,
+                    ping: now.saturating_sub(self.query_sent),
+                    on_lan,
+                },
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{discovery::read_status_query, PROTOCOL_VERSION};
+
+    fn server(name: &str, address: &str) -> SavedServer {
+        SavedServer {
+            name: name.to_string(),
+            address: address.to_string(),
+        }
+    }
+
+    #[test]
+    fn server_lists_round_trip_through_text() {
+        let list = ServerList {
+            servers: vec![
+                server("Home", "127.0.0.1:25575"),
+                server("Friends' world", "play.example.com:4000"),
+            ],
+        };
+        let text = list.to_text();
+        assert_eq!(
+            text,
+            "server=127.0.0.1:25575 Home\nserver=play.example.com:4000 Friends' world\n"
+        );
+        assert_eq!(ServerList::from_text(&text), list);
+    }
+
+    #[test]
+    fn odd_server_lines_are_read_as_well_as_they_can_be() {
+        let list = ServerList::from_text("server=10.0.0.1:1\nserver=\ncolour=blue\nnonsense\n");
+        assert_eq!(list.servers, vec![server("10.0.0.1:1", "10.0.0.1:1")]);
+        // names stay on their line
+        let list = ServerList {
+            servers: vec![server("two\nlines", "10.0.0.1:1")],
+        };
+        assert_eq!(list.to_text(), "server=10.0.0.1:1 two lines\n");
+    }
+
+    #[test]
+    fn server_lists_save_and_load() {
+        let directory =
+            std::env::temp_dir().join(format!("voxel_game_server_list_{}", std::process::id()));
+        let path = directory.join(SERVER_LIST_FILE_NAME);
+        assert_eq!(ServerList::load(&path).unwrap(), ServerList::default());
+        let list = ServerList {
+            servers: vec![server("Home", "127.0.0.1:25575")],
+        };
+        list.save(&path).unwrap();
+        let loaded = ServerList::load(&path);
+        fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(loaded.unwrap(), list);
+    }
+
+    #[test]
+    fn saved_servers_answering_show_up_in_the_browser() {
+        let server_socket = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        server_socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let address = server_socket.local_addr().unwrap();
+        let list = ServerList {
+            servers: vec![server("Local", &address.to_string())],
+        };
+        let mut browser = ServerBrowser::open().unwrap();
+        browser.refresh(&list, Duration::from_millis(100));
+
+        let mut buffer = [0; MAX_PACKET_SIZE];
+        let (length, client) = server_socket.recv_from(&mut buffer).unwrap();
+        let nonce = read_status_query(&buffer[..length]).unwrap();
+        let status = ServerStatus {
+            protocol_version: PROTOCOL_VERSION,
+            motd: "Hello".to_string(),
+            player_count: 1,
+            max_players: 4,
+            port: address.port(),
+        };
+        // an answer to an older round is ignored
+        server_socket
+            .send_to(&status.to_bytes(nonce - 1), client)
+            .unwrap();
+        server_socket
+            .send_to(&status.to_bytes(nonce), client)
+            .unwrap();
+
+        for _ in 0..100 {
+            browser.receive(Duration::from_millis(150));
+            if !browser.servers.is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            browser.servers.get(&address),
+            Some(&QueriedServer {
+                status,
+                ping: Duration::from_millis(50),
+                on_lan: false,
+            })
+        );
+        assert_eq!(browser.servers.len(), 1);
+    }
+}
@@ -0,0 +1,220 @@
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use bevy::{
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+use bevy_egui::{egui, EguiContexts};
+
+use crate::{client::resources::ClientSettings, AppState};
+
+use super::{
+    resources::{SavedServer, ServerBrowser, ServerList, ServerListPath},
+    STATUS_REFRESH_INTERVAL,
+};
+
+/// Loads the saved servers and asks them and the local network for their status
+pub fn open_server_browser(
+    mut commands: Commands,
+    server_list_path: Res<ServerListPath>,
+    time: Res<Time<Real>>,
+) {
+    let server_list = ServerList::load(&server_list_path.0).unwrap_or_else(|error| {
+        error!("Failed to load the server list: {error}");
+        ServerList::default()
+    });
+    match ServerBrowser::open() {
+        Ok(mut browser) => {
+            browser.refresh(&server_list, time.elapsed());
+            commands.insert_resource(browser);
+        }
+        Err(error) => error!("Failed to open a socket for the server browser: {error}"),
+    }
+    commands.insert_resource(server_list);
+}
+
+pub fn close_server_browser(mut commands: Commands) {
+    commands.remove_resource::<ServerBrowser>();
+}
+
+/// Takes in status answers and asks again every `STATUS_REFRESH_INTERVAL`
+pub fn query_servers(
+    mut browser: ResMut<ServerBrowser>,
+    server_list: Res<ServerList>,
+    time: Res<Time<Real>>,
+) {
+    let now = time.elapsed();
+    browser.receive(now);
+    if browser.refresh_due(now, STATUS_REFRESH_INTERVAL) {
+        browser.refresh(&server_list, now);
+    }
+}
+
+/// What the player clicked on this frame
+enum BrowserAction {
+    Join(SocketAddr),
+    Save(SavedServer),
+    Remove(usize),
+    Refresh,
+}
+
+/// Status of a server in the list as one line of text
+fn status_text(browser: &ServerBrowser, address: Option<SocketAddr>) -> String {
+    let Some(server) = address.and_then(|address| browser.servers.get(&address)) else {
+        return "no answer".to_string();
+    };
+    let status = &server.status;
+    if !status.is_compatible() {
+        return format!(
+            "{}, incompatible version {}",
+            status.motd, status.protocol_version
+        );
+    }
+    format!(
+        "{}, {}/{} players, {} ms",
+        status.motd,
+        status.player_count,
+        status.max_players,
+        server.ping.as_millis()
+    )
+}
+
+/// Draws the multiplayer screen and carries out what was clicked
+#[allow(clippy::too_many_arguments)]
+pub fn server_browser_ui(
+    mut contexts: EguiContexts,
+    mut browser: ResMut<ServerBrowser>,
+    mut server_list: ResMut<ServerList>,
+    server_list_path: Res<ServerListPath>,
+    mut client_settings: ResMut<ClientSettings>,
+    mut next_state: ResMut<NextState<AppState>>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+    time: Res<Time<Real>>,
+) {
+    // the screen needs the mouse, the camera grabs it again once playing
+    if let Ok(mut window) = window_query.get_single_mut() {
+        if window.cursor.grab_mode != CursorGrabMode::None {
+            window.cursor.grab_mode = CursorGrabMode::None;
+            window.cursor.visible = true;
+        }
+    }
+
+    let mut action = None;
+    egui::Window::new("Multiplayer").show(contexts.ctx_mut(), |ui| {
+        ui.heading("Saved servers");
+        egui::Grid::new("saved_servers")
+            .striped(true)
+            .show(ui, |ui| {
+                for (index, server) in server_list.servers.iter().enumerate() {
+                    let address = server.resolve();
+                    ui.label(&server.name);
+                    ui.label(&server.address);
+                    ui.label(status_text(&browser, address));
+                    if ui
+                        .add_enabled(address.is_some(), egui::Button::new("Join"))
+                        .clicked()
+                    {
+                        action = address.map(BrowserAction::Join);
+                    }
+                    if ui.button("Remove").clicked() {
+                        action = Some(BrowserAction::Remove(index));
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.separator();
+        ui.heading("Local network");
+        let mut lan_servers: Vec<SocketAddr> = browser
+            .servers
+            .iter()
+            .filter(|(_, server)| server.on_lan)
+            .map(|(address, _)| *address)
+            .collect();
+        lan_servers.sort();
+        if lan_servers.is_empty() {
+            ui.label("No servers found");
+        }
+        egui::Grid::new("lan_servers").striped(true).show(ui, |ui| {
+            for address in lan_servers {
+                ui.label(address.to_string());
+                ui.label(status_text(&browser, Some(address)));
+                if ui.button("Join").clicked() {
+                    action = Some(BrowserAction::Join(address));
+                }
+                if ui.button("Save").clicked() {
+                    action = Some(BrowserAction::Save(SavedServer {
+                        name: browser.servers[&address].status.motd.clone(),
+                        address: address.to_string(),
+                    }));
+                }
+                ui.end_row();
+            }
+        });
+
+        ui.separator();
+        ui.heading("Direct connect");
+        ui.horizontal(|ui| {
+            ui.label("Address");
+            ui.text_edit_singleline(&mut browser.address_input);
+        });
+        ui.horizontal(|ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut browser.name_input);
+        });
+        ui.horizontal(|ui| {
+            let address = browser.address_input.trim().to_string();
+            if ui.button("Connect").clicked() {
+                match address
+                    .to_socket_addrs()
+                    .ok()
+                    .and_then(|mut addresses| addresses.next())
+                {
+                    Some(address) => action = Some(BrowserAction::Join(address)),
+                    None => browser.error = Some(format!("Can not find {address}")),
+                }
+            }
+            if ui.button("Save").clicked() && !address.is_empty() {
+                let name = browser.name_input.trim();
+                action = Some(BrowserAction::Save(SavedServer {
+                    name: if name.is_empty() {
+                        address.clone()
+                    } else {
+                        name.to_string()
+                    },
+                    address,
+                }));
+            }
+            if ui.button("Refresh").clicked() {
+                action = Some(BrowserAction::Refresh);
+            }
+        });
+        if let Some(error) = &browser.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    });
+
+    match action {
+        Some(BrowserAction::Join(address)) => {
+            client_settings.server_address = Some(address);
+            next_state.set(AppState::Game);
+        }
+        Some(BrowserAction::Save(server)) => {
+            server_list.servers.push(server);
+            save_server_list(&server_list, &server_list_path, &mut browser);
+            browser.refresh(&server_list, time.elapsed());
+        }
+        Some(BrowserAction::Remove(index)) => {
+            server_list.servers.remove(index);
+            save_server_list(&server_list, &server_list_path, &mut browser);
+        }
+        Some(BrowserAction::Refresh) => browser.refresh(&server_list, time.elapsed()),
+        None => {}
+    }
+}
+
+fn save_server_list(server_list: &ServerList, path: &ServerListPath, browser: &mut ServerBrowser) {
+    if let Err(error) = server_list.save(&path.0) {
+        browser.error = Some(format!("Failed to save the server list: {error}"));
+    }
+}
@@ -0,0 +1,60 @@
+use bevy::prelude::*;
+
+use super::{protocol::Message, MAX_PACKET_SIZE};
+
+/// Room left in a packet for a chunk data payload after the packet and message headers
+pub const CHUNK_PART_SIZE: usize = MAX_PACKET_SIZE - 64;
+
+/// Splits an encoded chunk into `ChunkData` messages small enough for a packet each
+pub fn chunk_messages(position: IVec3, bytes: &[u8]) -> Vec<Message> {
+    let part_count = bytes.len().div_ceil(CHUNK_PART_SIZE).max(1) as u16;
+    (0..part_count)
+        .map(|part| {
+            let start = part as usize * CHUNK_PART_SIZE;
+            let end = (start + CHUNK_PART_SIZE).min(bytes.len());
+            Message::ChunkData {
+                position,
+                part,
+                part_count,
+                payload: bytes[start..end].to_vec(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::game::save::chunk::{encode_chunk, CHUNK_VOLUME};
+
+    #[test]
+    fn small_chunks_are_sent_in_a_single_packet() {
+        let bytes = encode_chunk(&vec![None; CHUNK_VOLUME]);
+        assert_eq!(chunk_messages(IVec3::ZERO, &bytes).len(), 1);
+    }
+
+    #[test]
+    fn large_chunks_are_split_and_put_back_together() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let bytes: Vec<u8> = (0..CHUNK_PART_SIZE * 2 + 10).map(|_| rng.gen()).collect();
+        let messages = chunk_messages(IVec3::ONE, &bytes);
+        assert_eq!(messages.len(), 3);
+        let mut joined = vec![];
+        for (index, message) in messages.into_iter().enumerate() {
+            let Message::ChunkData {
+                part,
+                part_count,
+                payload,
+                ..
+            } = message
+            else {
+                panic!("not chunk data");
+            };
+            assert_eq!((part as usize, part_count), (index, 3));
+            joined.extend(payload);
+        }
+        assert_eq!(joined, bytes);
+    }
+}
@@ -0,0 +1,185 @@
+use std::{collections::BTreeMap, io, time::Duration};
+
+use crate::bytes::{invalid_data, ByteReader};
+
+use super::{protocol::Message, CONNECTION_TIMEOUT, MAX_OUT_OF_ORDER, RESEND_INTERVAL};
+
+/// First bytes of every packet, anything else arriving on the socket is dropped
+pub const PACKET_MAGIC: [u8; 2] = *b"VX";
+
+const RELIABLE_PACKET: u8 = 0;
+const UNRELIABLE_PACKET: u8 = 1;
+const ACK_PACKET: u8 = 2;
+
+/// One end of a client server connection, on top of a socket that may drop, duplicate and reorder packets.
+/// Reliable messages carry a sequence number, they are resent until acked and delivered in order.
+/// Every packet carries a cumulative ack, the sequence number of the next reliable message expected.
+#[derive(Debug)]
+pub struct Connection {
+    next_send_sequence: u32,
+    /// reliable packets sent but not acked yet, with when they were last sent
+    unacked: BTreeMap<u32, (Vec<u8>, Duration)>,
+    next_receive_sequence: u32,
+    /// reliable messages that arrived ahead of one still missing, at most `MAX_OUT_OF_ORDER` of them
+    out_of_order: BTreeMap<u32, Message>,
+    /// packets waiting for the next `flush`
+    outgoing: Vec<Vec<u8>>,
+    ack_pending: bool,
+    last_received: Duration,
+}
+
+impl Connection {
+    pub fn new(now: Duration) -> Self {
+        Connection {
+            next_send_sequence: 0,
+            unacked: BTreeMap::new(),
+            next_receive_sequence: 0,
+            out_of_order: BTreeMap::new(),
+            outgoing: vec![],
+            ack_pending: false,
+            last_received: now,
+        }
+    }
+
+    fn header(&self, kind: u8) -> Vec<u8> {
+        let mut packet = PACKET_MAGIC.to_vec();
+        packet.push(kind);
+        packet.extend_from_slice(&self.next_receive_sequence.to_le_bytes());
+        packet
+    }
+
+    /// Queues a message for the next `flush`
+    pub fn send(&mut self, message: &Message, now: Duration) {
+        let mut packet;
+        if message.is_reliable() {
+            packet = self.header(RELIABLE_PACKET);
+            packet.extend_from_slice(&self.next_send_sequence.to_le_bytes());
+            message.write(&mut packet);
+            self.unacked
+                .insert(self.next_send_sequence, (packet.clone(), now));
+            self.next_send_sequence += 1;
+        } else {
+            packet = self.header(UNRELIABLE_PACKET);
+            message.write(&mut packet);
+        }
+        // the packet carries the latest ack, a separate one is not needed
+        self.ack_pending = false;
+        self.outgoing.push(packet);
+    }
+
+    /// Reads a packet from the other end, returning the messages it lets through in delivery order
+    pub fn receive(&mut self, packet: &[u8], now: Duration) -> io::Result<Vec<Message>> {
+        let mut reader = ByteReader::new(packet);
+        if reader.take(PACKET_MAGIC.len())? != PACKET_MAGIC {
+            return Err(invalid_data("not a game packet"));
+        }
+        let kind = reader.u8()?;
+        let ack = reader.u32()?;
+        self.last_received = now;
+        self.unacked.retain(|sequence, _| *sequence >= ack);
+
+        match kind {
+            ACK_PACKET => Ok(vec![]),
+            UNRELIABLE_PACKET => Ok(vec![Message::read(&mut reader)?]),
+            RELIABLE_PACKET => {
+                let sequence = reader.u32()?;
+                // acked even when it is a duplicate, the ack for it may have been lost
+                self.ack_pending = true;
+                if sequence < self.next_receive_sequence
+                    || sequence - self.next_receive_sequence >= MAX_OUT_OF_ORDER
+                {
+                    return Ok(vec![]);
+                }
+                self.out_of_order
+                    .insert(sequence, Message::read(&mut reader)?);
+                let mut delivered = vec![];
+                while let Some(message) = self.out_of_order.remove(&self.next_receive_sequence) {
+                    delivered.push(message);
+                    self.next_receive_sequence += 1;
+                }
+                Ok(delivered)
+            }
+            _ => Err(invalid_data("unknown packet kind")),
+        }
+    }
+
+    /// Packets to put on the socket now: queued ones, reliable ones due a resend and an ack if one is owed
+    pub fn flush(&mut self, now: Duration) -> Vec<Vec<u8>> {
+        let mut packets = std::mem::take(&mut self.outgoing);
+        for (packet, sent) in self.unacked.values_mut() {
+            if now.saturating_sub(*sent) >= RESEND_INTERVAL {
+                *sent = now;
+                packets.push(packet.clone());
+            }
+        }
+        if std::mem::take(&mut self.ack_pending) {
+            packets.push(self.header(ACK_PACKET));
+        }
+        packets
+    }
+
+    /// Whether every reliable message sent has been acked
+    pub fn is_idle(&self) -> bool {
+        self.unacked.is_empty() && self.outgoing.is_empty()
+    }
+
+    pub fn timed_out(&self, now: Duration) -> bool {
+        now.saturating_sub(self.last_received) >= CONNECTION_TIMEOUT
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(text: &str) -> Message {
+        Message::Chat {
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn reliable_messages_arrive_once_and_in_order() {
+        let mut sender = Connection::new(Duration::ZERO);
+        let mut receiver = Connection::new(Duration::ZERO);
+        for text in ["a", "b", "c"] {
+            sender.send(&chat(text), Duration::ZERO);
+        }
+        let packets = sender.flush(Duration::ZERO);
+        let mut receive = |packet: &Vec<u8>| receiver.receive(packet, Duration::ZERO).unwrap();
+        assert_eq!(receive(&packets[2]), vec![]);
+        assert_eq!(receive(&packets[0]), vec![chat("a")]);
+        assert_eq!(receive(&packets[2]), vec![]);
+        assert_eq!(receive(&packets[1]), vec![chat("b"), chat("c")]);
+        assert_eq!(receive(&packets[1]), vec![]);
+    }
+
+    #[test]
+    fn messages_too_far_ahead_wait_for_their_resend() {
+        let mut sender = Connection::new(Duration::ZERO);
+        let mut receiver = Connection::new(Duration::ZERO);
+        for index in 0..=MAX_OUT_OF_ORDER {
+            sender.send(&chat(&index.to_string()), Duration::ZERO);
+        }
+        let packets = sender.flush(Duration::ZERO);
+        // the first one is lost, everything after it up to the window is held back
+        for packet in &packets[1..] {
+            assert_eq!(receiver.receive(packet, Duration::ZERO).unwrap(), vec![]);
+        }
+        assert_eq!(receiver.out_of_order.len(), MAX_OUT_OF_ORDER as usize - 1);
+
+        let delivered = receiver.receive(&packets[0], Duration::ZERO).unwrap();
+        assert_eq!(delivered.len(), MAX_OUT_OF_ORDER as usize);
+        assert!(receiver.out_of_order.is_empty());
+        // acks from the receiver let the sender drop what arrived, the dropped one is resent
+        for packet in receiver.flush(Duration::ZERO) {
+            sender.receive(&packet, Duration::ZERO).unwrap();
+        }
+        let resent = sender.flush(RESEND_INTERVAL);
+        assert_eq!(resent.len(), 1);
+        assert_eq!(
+            receiver.receive(&resent[0], RESEND_INTERVAL).unwrap(),
+            vec![chat(&MAX_OUT_OF_ORDER.to_string())]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::io;
+
+use crate::bytes::{invalid_data, write_string, ByteReader};
+
+use super::PROTOCOL_VERSION;
+
+/// Starts a status query, sent to a server's game port or broadcast to `DISCOVERY_PORT`
+pub const STATUS_QUERY_MAGIC: [u8; 4] = *b"VXQ?";
+/// Starts a server's answer to a status query
+pub const STATUS_MAGIC: [u8; 4] = *b"VXQ!";
+
+/// Asks any server that hears it for its status, the nonce comes back in the answer to time the round trip
+pub fn status_query(nonce: u64) -> Vec<u8> {
+    let mut bytes = STATUS_QUERY_MAGIC.to_vec();
+    bytes.extend_from_slice(&nonce.to_le_bytes());
+    bytes
+}
+
+/// The nonce of a status query, `None` for any other packet
+pub fn read_status_query(bytes: &[u8]) -> Option<u64> {
+    let mut reader = ByteReader::new(bytes);
+    if reader.take(STATUS_QUERY_MAGIC.len()).ok()? != STATUS_QUERY_MAGIC {
+        return None;
+    }
+    reader.u64().ok()
+}
+
+/// What a server says about itself in answer to a status query
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerStatus {
+    pub protocol_version: u16,
+    /// message of the day, shown in the server browser
+    pub motd: String,
+    pub player_count: u32,
+    pub max_players: u32,
+    /// port clients join the game on, broadcast answers come from the discovery port instead
+    pub port: u16,
+}
+
+impl ServerStatus {
+    /// Whether a client on this build can join the server
+    pub fn is_compatible(&self) -> bool {
+        self.protocol_version == PROTOCOL_VERSION
+    }
+
+    pub fn to_bytes(&self, nonce: u64) -> Vec<u8> {
+        let mut bytes = STATUS_MAGIC.to_vec();
+        bytes.extend_from_slice(&nonce.to_le_bytes());
+        bytes.extend_from_slice(&self.protocol_version.to_le_bytes());
+        write_string(&mut bytes, &self.motd);
+        bytes.extend_from_slice(&self.player_count.to_le_bytes());
+        bytes.extend_from_slice(&self.max_players.to_le_bytes());
+        bytes.extend_from_slice(&self.port.to_le_bytes());
+        bytes
+    }
+
+    /// Reads an answer written by `to_bytes`, with the nonce of the query it answers
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<(u64, Self)> {
+        let mut reader = ByteReader::new(bytes);
+        if reader.take(STATUS_MAGIC.len())? != STATUS_MAGIC {
+            return Err(invalid_data("not a server status"));
+        }
+        let nonce = reader.u64()?;
+        let status = ServerStatus {
+            protocol_version: reader.u16()?,
+            motd: reader.string()?,
+            player_count: reader.u32()?,
+            max_players: reader.u32()?,
+            port: reader.u16()?,
+        };
+        Ok((nonce, status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> ServerStatus {
+        ServerStatus {
+            protocol_version: PROTOCOL_VERSION,
+            motd: "Welcome".to_string(),
+            player_count: 3,
+            max_players: 8,
+            port: 25575,
+        }
+    }
+
+    #[test]
+    fn status_queries_are_magic_and_nonce() {
+        let query = status_query(0x0102_0304_0506_0708);
+        assert_eq!(query, b"VXQ?\x08\x07\x06\x05\x04\x03\x02\x01");
+        assert_eq!(read_status_query(&query), Some(0x0102_0304_0506_0708));
+        // game packets and cut off queries are not status queries
+        assert_eq!(read_status_query(b"VX\x00\x00\x00\x00\x00"), None);
+        assert_eq!(read_status_query(&query[..8]), None);
+    }
+
+    #[test]
+    fn status_bytes_round_trip() {
+        let bytes = status().to_bytes(7);
+        assert_eq!(&bytes[..12], b"VXQ!\x07\x00\x00\x00\x00\x00\x00\x00");
+        assert_eq!(&bytes[12..14], PROTOCOL_VERSION.to_le_bytes());
+        assert_eq!(ServerStatus::from_bytes(&bytes).unwrap(), (7, status()));
+
+        assert!(ServerStatus::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ServerStatus::from_bytes(&status_query(7)).is_err());
+    }
+
+    #[test]
+    fn only_the_same_protocol_is_compatible() {
+        assert!(status().is_compatible());
+        let older = ServerStatus {
+            protocol_version: PROTOCOL_VERSION - 1,
+            ..status()
+        };
+        assert!(!older.is_compatible());
+    }
+}
@@ -0,0 +1,56 @@
+pub mod chunk;
+pub mod connection;
+pub mod discovery;
+pub mod prediction;
+pub mod protocol;
+pub mod simulation;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+
+/// Bumped whenever the wire format changes, peers on different versions refuse each other
+pub const PROTOCOL_VERSION: u16 = 5;
+
+/// Port servers listen on for status queries broadcast over the local network
+pub const DISCOVERY_PORT: u16 = 25576;
+
+/// Largest datagram sent, kept under a typical MTU so packets are never fragmented
+pub const MAX_PACKET_SIZE: usize = 1200;
+
+/// How long a reliable message waits for an ack before it is sent again
+pub const RESEND_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Furthest past the next expected reliable message one is held back for delivery.
+/// Anything later is dropped, the other end resends it once the gap has been filled.
+pub const MAX_OUT_OF_ORDER: u32 = 1024;
+
+/// A peer that has sent nothing for this long is dropped
+pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Longest chat message and player name in bytes, so every message fits in a packet
+pub const MAX_CHAT_LENGTH: usize = 256;
+pub const MAX_NAME_LENGTH: usize = 32;
+/// Longest player key in bytes, clients make theirs `PLAYER_KEY_LENGTH` bytes written as hex
+pub const MAX_KEY_LENGTH: usize = 64;
+pub const PLAYER_KEY_LENGTH: usize = 16;
+
+/// Most block changes sent as a `ChunkDelta`, a chunk with more changes than this is sent whole
+pub const MAX_DELTA_CHANGES: usize = 256;
+
+/// How fast players move, in voxels per second
+pub const PLAYER_SPEED: f32 = 20.0;
+/// Where players start out on a server, in world units
+pub const PLAYER_SPAWN: Vec3 = Vec3::new(-1.0, 2.4, 4.0);
+/// Most inputs a client repeats in each input message, so a few lost packets lose no input
+pub const MAX_SENT_INPUTS: usize = 16;
+/// Most inputs the server holds for a player, enough to ride out a burst of delayed packets
+pub const MAX_BUFFERED_INPUTS: usize = 2 * MAX_SENT_INPUTS;
+/// A predicted position further than this from the server's, in world units, is snapped instead of smoothed
+pub const SNAP_DISTANCE: f32 = 1.0;
+/// How quickly a prediction error is smoothed away, the fraction left after a second is `exp(-rate)`
+pub const CORRECTION_RATE: f32 = 10.0;
+/// World ticks between the world clock being sent to clients
+pub const WORLD_TIME_SYNC_TICKS: u64 = 20;
+/// How far behind the newest position other players are shown, so there is a later one to move towards
+pub const INTERPOLATION_DELAY_TICKS: f64 = 2.0;
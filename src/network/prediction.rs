@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::game::world::VOXEL_SIZE;
+
+use super::{CORRECTION_RATE, MAX_BUFFERED_INPUTS, PLAYER_SPEED, SNAP_DISTANCE};
+
+/// Movement asked for during one world tick, numbered so the server can say which it has applied
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerInput {
+    pub sequence: u32,
+    /// direction to move in, at most one long
+    pub movement: Vec3,
+}
+
+/// Moves a player by one world tick of input at `tick_rate` ticks a second,
+/// run the same on the client and the server
+pub fn step_player(position: Vec3, movement: Vec3, tick_rate: f32) -> Vec3 {
+    position + movement.clamp_length_max(1.0) * PLAYER_SPEED * VOXEL_SIZE / tick_rate
+}
+
+/// A player as the server moves it, the authority on where the player is.
+/// Inputs are applied one a world tick, so a client can not move faster by sending more of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthoritativePlayer {
+    pub position: Vec3,
+    /// newest input applied, inputs up to it are ignored when they arrive again
+    pub input_sequence: Option<u32>,
+    /// world ticks a second the server runs at, the client was told it on login
+    pub tick_rate: f32,
+    /// inputs received but not applied yet, oldest first
+    buffered: VecDeque<PlayerInput>,
+}
+
+impl AuthoritativePlayer {
+    pub fn new(position: Vec3, tick_rate: f32) -> Self {
+        AuthoritativePlayer {
+            position,
+            input_sequence: None,
+            tick_rate,
+            buffered: VecDeque::new(),
+        }
+    }
+
+    /// Buffers the inputs newer than any applied or buffered before, up to `MAX_BUFFERED_INPUTS`.
+    /// Inputs past that are dropped, the client repeats them until they are applied.
+    pub fn receive_inputs(&mut self, inputs: &[PlayerInput]) {
+        for input in inputs {
+            let newest = self
+                .buffered
+                .back()
+                .map(|input| input.sequence)
+                .or(self.input_sequence);
+            if Some(input.sequence) > newest && self.buffered.len() < MAX_BUFFERED_INPUTS {
+                self.buffered.push_back(*input);
+            }
+        }
+    }
+
+    /// Applies the oldest buffered input, call once every world tick
+    pub fn apply_next_input(&mut self) {
+        if let Some(input) = self.buffered.pop_front() {
+            self.position = step_player(self.position, input.movement, self.tick_rate);
+            self.input_sequence = Some(input.sequence);
+        }
+    }
+}
+
+/// The local player as the client predicts it: where the server last put it with the inputs
+/// the server has not applied yet played on top
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    next_sequence: u32,
+    /// inputs not yet acknowledged by the server, oldest first
+    pending: VecDeque<PlayerInput>,
+    predicted: Vec3,
+    /// left over prediction error, shown on top of the prediction and smoothed away
+    correction: Vec3,
+    /// newest input the server has said it applied
+    acknowledged: Option<u32>,
+    /// world ticks a second of the server, inputs move the player as far as they do there
+    tick_rate: f32,
+}
+
+impl Prediction {
+    pub fn new(position: Vec3, tick_rate: f32) -> Self {
+        Prediction {
+            next_sequence: 0,
+            pending: VecDeque::new(),
+            predicted: position,
+            correction: Vec3::ZERO,
+            acknowledged: None,
+            tick_rate,
+        }
+    }
+
+    /// Moves the prediction on by a tick of input, returning the input to send to the server
+    pub fn apply_input(&mut self, movement: Vec3) -> PlayerInput {
+        let input = PlayerInput {
+            sequence: self.next_sequence,
+            movement,
+        };
+        self.next_sequence += 1;
+        self.predicted = step_player(self.predicted, movement, self.tick_rate);
+        self.pending.push_back(input);
+        input
+    }
+
+    /// The newest `count` inputs the server has not acknowledged yet, oldest first
+    pub fn pending_inputs(&self, count: usize) -> Vec<PlayerInput> {
+        let skipped = self.pending.len().saturating_sub(count);
+        self.pending.iter().skip(skipped).copied().collect()
+    }
+
+    /// Takes the server's position after it applied every input up to `input_sequence`
+    /// and replays the newer inputs on top of it
+    pub fn reconcile(&mut self, position: Vec3, input_sequence: Option<u32>) {
+        // states arrive unreliably, one older than what was already reconciled against is stale
+        if input_sequence < self.acknowledged {
+            return;
+        }
+        self.acknowledged = input_sequence;
+        if let Some(input_sequence) = input_sequence {
+            while self
+                .pending
+                .front()
+                .is_some_and(|input| input.sequence <= input_sequence)
+            {
+                self.pending.pop_front();
+            }
+        }
+        let replayed = self.pending.iter().fold(position, |position, input| {
+            step_player(position, input.movement, self.tick_rate)
+        });
+        let error = self.predicted + self.correction - replayed;
+        self.correction = if error.length() > SNAP_DISTANCE {
+            Vec3::ZERO
+        } else {
+            error
+        };
+        self.predicted = replayed;
+    }
+
+    /// Smooths away part of the correction, call every frame
+    pub fn decay_correction(&mut self, seconds: f32) {
+        self.correction *= (-CORRECTION_RATE * seconds).exp();
+    }
+
+    /// Where the player is drawn, the prediction with what is left of the correction
+    pub fn display_position(&self) -> Vec3 {
+        self.predicted + self.correction
+    }
+
+    /// The predicted position without smoothing
+    pub fn predicted_position(&self) -> Vec3 {
+        self.predicted
+    }
+}
+
+/// Recent positions of another player by server tick, shown a little in the past so it moves smoothly
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<(u64, Vec3)>,
+}
+
+impl SnapshotBuffer {
+    /// Snapshots older than this many ticks before the newest one are dropped
+    const KEPT_TICKS: u64 = 40;
+
+    pub fn push(&mut self, tick: u64, position: Vec3) {
+        // snapshots arrive unreliably and can be out of order or repeated
+        let index = self.snapshots.partition_point(|(other, _)| *other < tick);
+        if self
+            .snapshots
+            .get(index)
+            .is_some_and(|(other, _)| *other == tick)
+        {
+            return;
+        }
+        self.snapshots.insert(index, (tick, position));
+        let newest = self.snapshots.back().map_or(tick, |(newest, _)| *newest);
+        while self
+            .snapshots
+            .front()
+            .is_some_and(|(oldest, _)| *oldest + Self::KEPT_TICKS < newest)
+        {
+            self.snapshots.pop_front();
+        }
+    }
+
+    /// Position at a fractional tick, between the snapshots around it.
+    /// Ticks before the oldest or after the newest snapshot are held at that snapshot.
+    pub fn sample(&self, tick: f64) -> Option<Vec3> {
+        let after = self
+            .snapshots
+            .partition_point(|(other, _)| (*other as f64) <= tick);
+        let (Some(before), Some(after)) = (
+            after
+                .checked_sub(1)
+                .and_then(|index| self.snapshots.get(index)),
+            self.snapshots.get(after),
+        ) else {
+            return self
+                .snapshots
+                .get(after.saturating_sub(1))
+                .map(|(_, position)| *position);
+        };
+        let t = (tick - before.0 as f64) / (after.0 - before.0) as f64;
+        Some(before.1.lerp(after.1, t as f32))
+    }
+
+    pub fn newest_tick(&self) -> Option<u64> {
+        self.snapshots.back().map(|(tick, _)| *tick)
+    }
+}
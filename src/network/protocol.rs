@@ -0,0 +1,413 @@
+use std::io;
+
+use bevy::prelude::*;
+
+use crate::{
+    bytes::{invalid_data, write_ivec3, write_string, write_vec3, ByteReader},
+    game::{
+        commands::parser::PermissionLevel,
+        save::chunk::{read_block_change, write_block_change},
+        world::{access::BlockChange, components::BlockType},
+    },
+};
+
+use super::prediction::PlayerInput;
+
+/// Everything a client and server say to each other, one message per packet
+#[derive(Debug, Clone, PartialEq)]
+pub enum Message {
+    /// first thing a client sends, the server disconnects clients on another version
+    Handshake {
+        protocol_version: u16,
+    },
+    /// `key` is the client's own secret, it proves to the server the name belongs to the same player
+    Login {
+        name: String,
+        key: String,
+    },
+    /// with the permission level of the account, so the client only offers commands it may run,
+    /// and the server's world ticks a second, so the client sends inputs as fast as they are applied
+    LoginAccepted {
+        player_id: u32,
+        spawn: Vec3,
+        permission: PermissionLevel,
+        tick_rate: f32,
+    },
+    /// one part of a chunk encoded by `encode_chunk`, chunks can be too big for a single packet
+    ChunkData {
+        position: IVec3,
+        part: u16,
+        part_count: u16,
+        payload: Vec<u8>,
+    },
+    BlockChange {
+        position: IVec3,
+        block: BlockChange,
+    },
+    /// blocks of a loaded chunk that changed, by index into the chunk
+    ChunkDelta {
+        position: IVec3,
+        changes: Vec<(u16, BlockChange)>,
+    },
+    /// the chunk left the client's view, it gets no more deltas until it is sent again
+    UnloadChunk {
+        position: IVec3,
+    },
+    /// sent unreliably, a lost position is replaced by the next one anyway
+    EntityPosition {
+        entity_id: u32,
+        tick: u64,
+        position: Vec3,
+    },
+    /// the newest inputs of the client's player, repeated until the server has applied them
+    PlayerInput {
+        inputs: Vec<PlayerInput>,
+    },
+    /// where the server has the client's own player, after applying inputs up to `input_sequence`
+    PlayerState {
+        input_sequence: Option<u32>,
+        position: Vec3,
+    },
+    /// the server's world clock, sent every few ticks so every client shows the same sky
+    WorldTime {
+        day: u32,
+        time_of_day: f32,
+    },
+    Chat {
+        text: String,
+    },
+    /// the account of the client was given another permission level
+    PermissionChanged {
+        permission: PermissionLevel,
+    },
+    /// an item was dropped in the world, the client shows it where it lies
+    DroppedItem {
+        block: BlockType,
+        position: Vec3,
+    },
+    Disconnect {
+        reason: String,
+    },
+}
+
+impl Message {
+    /// Whether the message has to arrive, and in order with the other reliable messages
+    pub fn is_reliable(&self) -> bool {
+        !matches!(
+            self,
+            Message::EntityPosition { .. }
+                | Message::PlayerInput { .. }
+                | Message::PlayerState { .. }
+                | Message::WorldTime { .. }
+        )
+    }
+
+    pub fn write(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Message::Handshake { protocol_version } => {
+                bytes.push(0);
+                bytes.extend_from_slice(&protocol_version.to_le_bytes());
+            }
+            Message::Login { name, key } => {
+                bytes.push(1);
+                write_string(bytes, name);
+                write_string(bytes, key);
+            }
+            Message::LoginAccepted {
+                player_id,
+                spawn,
+                permission,
+                tick_rate,
+            } => {
+                bytes.push(2);
+                bytes.extend_from_slice(&player_id.to_le_bytes());
+                write_vec3(bytes, *spawn);
+                write_permission(bytes, *permission);
+                bytes.extend_from_slice(&tick_rate.to_le_bytes());
+            }
+            Message::ChunkData {
+                position,
+                part,
+                part_count,
+                payload,
+            } => {
+                bytes.push(3);
+                write_ivec3(bytes, *position);
+                bytes.extend_from_slice(&part.to_le_bytes());
+                bytes.extend_from_slice(&part_count.to_le_bytes());
+                bytes.extend_from_slice(payload);
+            }
+            Message::BlockChange { position, block } => {
+                bytes.push(4);
+                write_ivec3(bytes, *position);
+                write_block_change(bytes, *block);
+            }
+            Message::EntityPosition {
+                entity_id,
+                tick,
+                position,
+            } => {
+                bytes.push(5);
+                bytes.extend_from_slice(&entity_id.to_le_bytes());
+                bytes.extend_from_slice(&tick.to_le_bytes());
+                write_vec3(bytes, *position);
+            }
+            Message::Chat { text } => {
+                bytes.push(6);
+                write_string(bytes, text);
+            }
+            Message::Disconnect { reason } => {
+                bytes.push(7);
+                write_string(bytes, reason);
+            }
+            Message::WorldTime { day, time_of_day } => {
+                bytes.push(8);
+                bytes.extend_from_slice(&day.to_le_bytes());
+                bytes.extend_from_slice(&time_of_day.to_le_bytes());
+            }
+            Message::ChunkDelta { position, changes } => {
+                bytes.push(9);
+                write_ivec3(bytes, *position);
+                bytes.extend_from_slice(&(changes.len() as u16).to_le_bytes());
+                for (index, block) in changes {
+                    bytes.extend_from_slice(&index.to_le_bytes());
+                    write_block_change(bytes, *block);
+                }
+            }
+            Message::UnloadChunk { position } => {
+                bytes.push(10);
+                write_ivec3(bytes, *position);
+            }
+            Message::PlayerInput { inputs } => {
+                bytes.push(11);
+                bytes.push(inputs.len() as u8);
+                for input in inputs {
+                    bytes.extend_from_slice(&input.sequence.to_le_bytes());
+                    write_vec3(bytes, input.movement);
+                }
+            }
+            Message::PlayerState {
+                input_sequence,
+                position,
+            } => {
+                bytes.push(12);
+                match input_sequence {
+                    None => bytes.push(0),
+                    Some(input_sequence) => {
+                        bytes.push(1);
+                        bytes.extend_from_slice(&input_sequence.to_le_bytes());
+                    }
+                }
+                write_vec3(bytes, *position);
+            }
+            Message::PermissionChanged { permission } => {
+                bytes.push(13);
+                write_permission(bytes, *permission);
+            }
+            Message::DroppedItem { block, position } => {
+                bytes.push(14);
+                write_block_change(bytes, Some((*block, 0)));
+                write_vec3(bytes, *position);
+            }
+        }
+    }
+
+    /// Reads a message written by `write`, taking the rest of the reader
+    pub fn read(reader: &mut ByteReader) -> io::Result<Self> {
+        let message = match reader.u8()? {
+            0 => Message::Handshake {
+                protocol_version: reader.u16()?,
+            },
+            1 => Message::Login {
+                name: reader.string()?,
+                key: reader.string()?,
+            },
+            2 => Message::LoginAccepted {
+                player_id: reader.u32()?,
+                spawn: reader.vec3()?,
+                permission: read_permission(reader)?,
+                tick_rate: match reader.f32()? {
+                    tick_rate if tick_rate > 0.0 => tick_rate,
+                    _ => return Err(invalid_data("tick rate is not above zero")),
+                },
+            },
+            3 => Message::ChunkData {
+                position: reader.ivec3()?,
+                part: reader.u16()?,
+                part_count: reader.u16()?,
+                payload: reader.rest().to_vec(),
+            },
+            4 => Message::BlockChange {
+                position: reader.ivec3()?,
+                block: read_block_change(reader)?,
+            },
+            5 => Message::EntityPosition {
+                entity_id: reader.u32()?,
+                tick: reader.u64()?,
+                position: reader.vec3()?,
+            },
+            6 => Message::Chat {
+                text: reader.string()?,
+            },
+            7 => Message::Disconnect {
+                reason: reader.string()?,
+            },
+            8 => Message::WorldTime {
+                day: reader.u32()?,
+                time_of_day: reader.f32()?,
+            },
+            9 => {
+                let position = reader.ivec3()?;
+                let count = reader.u16()?;
+                let changes = (0..count)
+                    .map(|_| Ok((reader.u16()?, read_block_change(reader)?)))
+                    .collect::<io::Result<_>>()?;
+                Message::ChunkDelta { position, changes }
+            }
+            10 => Message::UnloadChunk {
+                position: reader.ivec3()?,
+            },
+            11 => {
+                let count = reader.u8()?;
+                let inputs = (0..count)
+                    .map(|_| {
+                        Ok(PlayerInput {
+                            sequence: reader.u32()?,
+                            movement: reader.vec3()?,
+                        })
+                    })
+                    .collect::<io::Result<_>>()?;
+                Message::PlayerInput { inputs }
+            }
+            12 => {
+                let input_sequence = match reader.u8()? {
+                    0 => None,
+                    _ => Some(reader.u32()?),
+                };
+                Message::PlayerState {
+                    input_sequence,
+                    position: reader.vec3()?,
+                }
+            }
+            13 => Message::PermissionChanged {
+                permission: read_permission(reader)?,
+            },
+            14 => {
+                let (block, _) = read_block_change(reader)?
+                    .ok_or_else(|| invalid_data("dropped item without a block"))?;
+                Message::DroppedItem {
+                    block,
+                    position: reader.vec3()?,
+                }
+            }
+            _ => return Err(invalid_data("unknown message")),
+        };
+        if !reader.is_empty() {
+            return Err(invalid_data("message has trailing data"));
+        }
+        Ok(message)
+    }
+}
+
+fn write_permission(bytes: &mut Vec<u8>, permission: PermissionLevel) {
+    let index = PermissionLevel::ALL
+        .iter()
+        .position(|other| *other == permission);
+    bytes.push(index.unwrap_or_default() as u8);
+}
+
+fn read_permission(reader: &mut ByteReader) -> io::Result<PermissionLevel> {
+    PermissionLevel::ALL
+        .get(reader.u8()? as usize)
+        .copied()
+        .ok_or_else(|| invalid_data("unknown permission level"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(message: &Message) -> Message {
+        let mut bytes = vec![];
+        message.write(&mut bytes);
+        Message::read(&mut ByteReader::new(&bytes)).unwrap()
+    }
+
+    #[test]
+    fn world_time_round_trips_unreliably() {
+        let message = Message::WorldTime {
+            day: 12,
+            time_of_day: 0.625,
+        };
+        assert_eq!(round_trip(&message), message);
+        assert!(!message.is_reliable());
+    }
+
+    #[test]
+    fn permissions_and_items_round_trip() {
+        let messages = [
+            Message::LoginAccepted {
+                player_id: 3,
+                spawn: Vec3::new(-1.0, 2.5, 4.0),
+                permission: PermissionLevel::Operator,
+                tick_rate: 12.5,
+            },
+            Message::PermissionChanged {
+                permission: PermissionLevel::Admin,
+            },
+            Message::DroppedItem {
+                block: BlockType::GoldOre,
+                position: Vec3::new(0.5, 1.0, -2.0),
+            },
+        ];
+        for message in messages {
+            assert_eq!(round_trip(&message), message);
+            assert!(message.is_reliable());
+        }
+        // a permission level past the known ones is refused
+        let mut bytes = vec![];
+        Message::PermissionChanged {
+            permission: PermissionLevel::Admin,
+        }
+        .write(&mut bytes);
+        *bytes.last_mut().unwrap() = PermissionLevel::ALL.len() as u8;
+        assert!(Message::read(&mut ByteReader::new(&bytes)).is_err());
+
+        // so is a tick rate that is not above zero
+        let mut bytes = vec![];
+        Message::LoginAccepted {
+            player_id: 3,
+            spawn: Vec3::ZERO,
+            permission: PermissionLevel::Player,
+            tick_rate: 0.0,
+        }
+        .write(&mut bytes);
+        assert!(Message::read(&mut ByteReader::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn inputs_that_are_not_finite_are_refused() {
+        let input = |movement| Message::PlayerInput {
+            inputs: vec![
+                PlayerInput {
+                    sequence: 0,
+                    movement: Vec3::X,
+                },
+                PlayerInput {
+                    sequence: 1,
+                    movement,
+                },
+            ],
+        };
+        assert_eq!(round_trip(&input(Vec3::NEG_Z)), input(Vec3::NEG_Z));
+        for movement in [
+            Vec3::new(f32::NAN, 0.0, 0.0),
+            Vec3::new(0.0, f32::INFINITY, 0.0),
+            Vec3::new(0.0, 0.0, f32::NEG_INFINITY),
+        ] {
+            let mut bytes = vec![];
+            input(movement).write(&mut bytes);
+            assert!(Message::read(&mut ByteReader::new(&bytes)).is_err());
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::game::tick::WORLD_TICKS_PER_SECOND;
+
+use super::{
+    prediction::{AuthoritativePlayer, PlayerInput, Prediction},
+    MAX_SENT_INPUTS,
+};
+
+/// A one way link that delays, reorders and drops what is sent over it, stepped in world ticks.
+/// Seeded, so a run can be repeated exactly.
+#[derive(Debug)]
+pub struct SimulatedLink<T> {
+    rng: StdRng,
+    latency_ticks: u64,
+    /// extra delay of up to this many ticks, which reorders what is sent
+    jitter_ticks: u64,
+    loss_chance: f64,
+    in_flight: Vec<(u64, T)>,
+}
+
+impl<T> SimulatedLink<T> {
+    pub fn new(seed: u64, latency_ticks: u64, jitter_ticks: u64, loss_chance: f64) -> Self {
+        SimulatedLink {
+            rng: StdRng::seed_from_u64(seed),
+            latency_ticks,
+            jitter_ticks,
+            loss_chance,
+            in_flight: vec![],
+        }
+    }
+
+    pub fn send(&mut self, tick: u64, item: T) {
+        if self.rng.gen_bool(self.loss_chance) {
+            return;
+        }
+        let delay = self.latency_ticks + self.rng.gen_range(0..=self.jitter_ticks);
+        self.in_flight.push((tick + delay, item));
+    }
+
+    /// Everything arriving by `tick`, in the order it arrives
+    pub fn receive(&mut self, tick: u64) -> Vec<T> {
+        let (mut arrived, in_flight): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|(arrival, _)| *arrival <= tick);
+        self.in_flight = in_flight;
+        // stable, items arriving on the same tick keep the order they were sent in
+        arrived.sort_by_key(|(arrival, _)| *arrival);
+        arrived.into_iter().map(|(_, item)| item).collect()
+    }
+}
+
+/// Client prediction against an authoritative server over simulated links, without any sockets.
+/// Used to check the predicted position converges on the server's under latency and loss.
+#[derive(Debug)]
+pub struct PredictionHarness {
+    tick: u64,
+    pub client: Prediction,
+    pub server: AuthoritativePlayer,
+    inputs: SimulatedLink<Vec<PlayerInput>>,
+    states: SimulatedLink<(Vec3, Option<u32>)>,
+}
+
+impl PredictionHarness {
+    /// Both links get the same latency, jitter and loss, seeded from `seed`
+    pub fn new(
+        seed: u64,
+        spawn: Vec3,
+        latency_ticks: u64,
+        jitter_ticks: u64,
+        loss_chance: f64,
+    ) -> Self {
+        let tick_rate = WORLD_TICKS_PER_SECOND as f32;
+        PredictionHarness {
+            tick: 0,
+            client: Prediction::new(spawn, tick_rate),
+            server: AuthoritativePlayer::new(spawn, tick_rate),
+            inputs: SimulatedLink::new(seed, latency_ticks, jitter_ticks, loss_chance),
+            states: SimulatedLink::new(
+                seed.wrapping_add(1),
+                latency_ticks,
+                jitter_ticks,
+                loss_chance,
+            ),
+        }
+    }
+
+    /// Runs one world tick with the client moving in `movement`, as the game does
+    pub fn step(&mut self, movement: Vec3) {
+        self.client.apply_input(movement);
+        let sent = self.client.pending_inputs(MAX_SENT_INPUTS);
+        self.inputs.send(self.tick, sent);
+
+        for inputs in self.inputs.receive(self.tick) {
+            self.server.receive_inputs(&inputs);
+        }
+        self.server.apply_next_input();
+        self.states.send(
+            self.tick,
+            (self.server.position, self.server.input_sequence),
+        );
+
+        for (position, input_sequence) in self.states.receive(self.tick) {
+            self.client.reconcile(position, input_sequence);
+        }
+        self.client
+            .decay_correction((1.0 / WORLD_TICKS_PER_SECOND) as f32);
+        self.tick += 1;
+    }
+
+    /// Distance between where the client shows the player and where the server has it
+    pub fn error(&self) -> f32 {
+        self.client
+            .display_position()
+            .distance(self.server.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::{prediction::step_player, MAX_BUFFERED_INPUTS, SNAP_DISTANCE};
+
+    fn input(sequence: u32) -> PlayerInput {
+        PlayerInput {
+            sequence,
+            movement: Vec3::X,
+        }
+    }
+
+    #[test]
+    fn servers_apply_one_input_a_tick() {
+        let mut player = AuthoritativePlayer::new(Vec3::ZERO, 20.0);
+        let inputs: Vec<_> = (0..4).map(input).collect();
+        player.receive_inputs(&inputs);
+        // repeats of inputs already buffered are not buffered again
+        player.receive_inputs(&inputs);
+        player.apply_next_input();
+        assert_eq!(player.input_sequence, Some(0));
+        assert_eq!(player.position, step_player(Vec3::ZERO, Vec3::X, 20.0));
+        for _ in 0..10 {
+            player.apply_next_input();
+        }
+        assert_eq!(player.input_sequence, Some(3));
+
+        let flood: Vec<_> = (4..1000).map(input).collect();
+        player.receive_inputs(&flood);
+        for _ in 0..1000 {
+            player.apply_next_input();
+        }
+        assert_eq!(player.input_sequence, Some(3 + MAX_BUFFERED_INPUTS as u32));
+    }
+
+    #[test]
+    fn predictions_settle_on_the_server_position() {
+        for seed in 0..8 {
+            let mut harness = PredictionHarness::new(seed, Vec3::ZERO, 3, 2, 0.2);
+            for tick in 0..200 {
+                let angle = tick as f32 * 0.05;
+                harness.step(Vec3::new(angle.cos(), 0.0, angle.sin()));
+                assert!(harness.error() < SNAP_DISTANCE, "seed {seed} tick {tick}");
+            }
+            for _ in 0..60 {
+                harness.step(Vec3::ZERO);
+            }
+            assert!(harness.error() < 1e-3, "seed {seed}: {}", harness.error());
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use std::{
+    fmt::Write,
+    fs, io,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    game::{
+        commands::{parser::PermissionLevel, resources::GameMode},
+        save::resources::SaveDirectory,
+        world::raycast::voxel_at_point,
+    },
+    network::PLAYER_SPAWN,
+};
+
+use super::{
+    BAN_LIST_FILE_NAME, PLAYER_ACCOUNTS_FILE_NAME, SPAWN_PROTECTION_IN_VOXELS, WHITELIST_FILE_NAME,
+};
+
+/// A player the world has seen, found again by name when they log back in
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerAccount {
+    /// sent to clients as their player id, it stays the same across logins
+    pub id: u32,
+    pub name: String,
+    pub permission: PermissionLevel,
+    pub game_mode: GameMode,
+    /// where the player was when they left, they spawn at the world spawn when it is unknown
+    pub position: Option<Vec3>,
+    /// secret the player's client sent on its first login, later logins have to send the same one
+    pub key: Option<String>,
+}
+
+/// Names are matched ignoring case, so nobody can pass for someone else with different capitals
+pub fn same_name(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+/// Every player of a world, stored as `player=<id> <permission> <game mode> <x> <y> <z> <key> <name>` lines.
+/// Lines from before keys were kept have no key, the next login sets it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PlayerAccounts {
+    pub accounts: Vec<PlayerAccount>,
+}
+
+impl PlayerAccounts {
+    /// Reads accounts written by `to_text`, lines that are unreadable are skipped
+    pub fn from_text(text: &str) -> Self {
+        let mut accounts = PlayerAccounts::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            match key.trim() {
+                "player" => match PlayerAccounts::read_account(value.trim()) {
+                    Some(account) if accounts.find(&account.name).is_none() => {
+                        accounts.accounts.push(account);
+                    }
+                    _ => warn!("Skipping player account {value}"),
+                },
+                _ => warn!("Unknown player account key {key}"),
+            }
+        }
+        accounts
+    }
+
+    fn read_account(value: &str) -> Option<PlayerAccount> {
+        let mut words = value.split_whitespace();
+        let id = words.next()?.parse().ok()?;
+        let permission = PermissionLevel::from_name(words.next()?)?;
+        let game_mode = GameMode::from_name(words.next()?)?;
+        // a position of `- - -` is unknown
+        let coordinates: Vec<&str> = (0..3).filter_map(|_| words.next()).collect();
+        let position = match coordinates[..] {
+            [x, y, z] => match (x.parse(), y.parse(), z.parse()) {
+                (Ok(x), Ok(y), Ok(z)) => Some(Vec3::new(x, y, z)).filter(|p| p.is_finite()),
+                _ if coordinates.iter().all(|coordinate| *coordinate == "-") => None,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        // a key of `-` is not set yet
+        let (key, name) = match (words.next()?, words.next()) {
+            ("-", Some(name)) => (None, name),
+            (key, Some(name)) => (Some(key.to_string()), name),
+            (name, None) => (None, name),
+        };
+        if words.next().is_some() {
+            return None;
+        }
+        Some(PlayerAccount {
+            id,
+            name: name.to_string(),
+            permission,
+            game_mode,
+            position,
+            key,
+        })
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for account in &self.accounts {
+            let position = match account.position {
+                Some(position) => format!("{} {} {}", position.x, position.y, position.z),
+                None => "- - -".to_string(),
+            };
+            let _ = writeln!(
+                text,
+                "player={} {} {} {position} {} {}",
+                account.id,
+                account.permission.name(),
+                account.game_mode.name(),
+                account.key.as_deref().unwrap_or("-"),
+                account.name.replace(char::is_whitespace, "_")
+            );
+        }
+        text
+    }
+
+    pub fn find(&self, name: &str) -> Option<&PlayerAccount> {
+        self.accounts
+            .iter()
+            .find(|account| same_name(&account.name, name))
+    }
+
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut PlayerAccount> {
+        self.accounts
+            .iter_mut()
+            .find(|account| same_name(&account.name, name))
+    }
+
+    pub fn get_mut(&mut self, id: u32) -> Option<&mut PlayerAccount> {
+        self.accounts.iter_mut().find(|account| account.id == id)
+    }
+
+    /// The account of a player, made for them on their first login
+    pub fn find_or_create(&mut self, name: &str) -> &mut PlayerAccount {
+        if let Some(index) = self
+            .accounts
+            .iter()
+            .position(|account| same_name(&account.name, name))
+        {
+            return &mut self.accounts[index];
+        }
+        let id = self
+            .accounts
+            .iter()
+            .map(|account| account.id + 1)
+            .max()
+            .unwrap_or(1);
+        self.accounts.push(PlayerAccount {
+            id,
+            name: name.to_string(),
+            permission: PermissionLevel::default(),
+            game_mode: GameMode::default(),
+            position: None,
+            key: None,
+        });
+        self.accounts.last_mut().unwrap()
+    }
+}
+
+/// Who may join when the whitelist is on, stored as an `enabled=<bool>` line and `player=<name>` lines
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Whitelist {
+    pub enabled: bool,
+    pub names: Vec<String>,
+}
+
+impl Whitelist {
+    /// Reads a whitelist written by `to_text`, lines that are unreadable are skipped
+    pub fn from_text(text: &str) -> Self {
+        let mut whitelist = Whitelist::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "enabled" => match value.parse() {
+                    Ok(enabled) => whitelist.enabled = enabled,
+                    Err(_) => warn!("Invalid whitelist enabled value {value}"),
+                },
+                "player" => {
+                    if !value.is_empty() {
+                        whitelist.add(value);
+                    }
+                }
+                _ => warn!("Unknown whitelist key {key}"),
+            }
+        }
+        whitelist
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        let _ = writeln!(text, "enabled={}", self.enabled);
+        for name in &self.names {
+            let _ = writeln!(text, "player={name}");
+        }
+        text
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        !self.enabled || self.names.iter().any(|listed| same_name(listed, name))
+    }
+
+    /// Returns whether the name was not on the list yet
+    pub fn add(&mut self, name: &str) -> bool {
+        if self.names.iter().any(|listed| same_name(listed, name)) {
+            return false;
+        }
+        self.names.push(name.to_string());
+        true
+    }
+
+    /// Returns whether the name was on the list
+    pub fn remove(&mut self, name: &str) -> bool {
+        let count = self.names.len();
+        self.names.retain(|listed| !same_name(listed, name));
+        self.names.len() != count
+    }
+}
+
+/// Players and addresses that may not join, stored as `player=<name> <reason>` and
+/// `address=<ip> <reason>` lines, the reason is optional
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BanList {
+    pub players: Vec<(String, String)>,
+    pub addresses: Vec<(IpAddr, String)>,
+}
+
+impl BanList {
+    /// Reads a ban list written by `to_text`, lines that are unreadable are skipped
+    pub fn from_text(text: &str) -> Self {
+        let mut bans = BanList::default();
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim();
+            let (banned, reason) = value.split_once(' ').unwrap_or((value, ""));
+            let reason = reason.trim().to_string();
+            match key.trim() {
+                "player" if !banned.is_empty() => bans.players.push((banned.to_string(), reason)),
+                "address" => match banned.parse() {
+                    Ok(address) => bans.addresses.push((address, reason)),
+                    Err(_) => warn!("Invalid banned address {banned}"),
+                },
+                _ => warn!("Unknown ban list key {key}"),
+            }
+        }
+        bans
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (name, reason) in &self.players {
+            let _ = writeln!(text, "player={name} {reason}");
+        }
+        for (address, reason) in &self.addresses {
+            let _ = writeln!(text, "address={address} {reason}");
+        }
+        text
+    }
+
+    /// Why a player is banned, by name or by the address they join from
+    pub fn reason(&self, name: &str, address: IpAddr) -> Option<&str> {
+        self.players
+            .iter()
+            .find(|(banned, _)| same_name(banned, name))
+            .map(|(_, reason)| reason.as_str())
+            .or_else(|| {
+                self.addresses
+                    .iter()
+                    .find(|(banned, _)| *banned == address)
+                    .map(|(_, reason)| reason.as_str())
+            })
+    }
+
+    /// Returns whether the name was not banned yet
+    pub fn ban(&mut self, name: &str, reason: &str) -> bool {
+        if self
+            .players
+            .iter()
+            .any(|(banned, _)| same_name(banned, name))
+        {
+            return false;
+        }
+        // names have no spaces in the file, the reason is everything after the first one
+        self.players
+            .push((name.replace(' ', "_"), reason.replace(['\n', '\r'], " ")));
+        true
+    }
+
+    /// Lifts the bans on a name, and on an address when given one. Returns whether anything was banned.
+    pub fn pardon(&mut self, name: &str) -> bool {
+        let count = self.players.len() + self.addresses.len();
+        self.players.retain(|(banned, _)| !same_name(banned, name));
+        if let Ok(address) = name.parse::<IpAddr>() {
+            self.addresses.retain(|(banned, _)| *banned != address);
+        }
+        self.players.len() + self.addresses.len() != count
+    }
+}
+
+/// Everything the server knows about who may play on the world, saved inside the world's save directory
+#[derive(Resource, Debug, Clone, PartialEq, Default)]
+pub struct ServerAccounts {
+    pub players: PlayerAccounts,
+    pub whitelist: Whitelist,
+    pub bans: BanList,
+}
+
+/// Reads a file that is fine to be missing, a new world has none
+fn read_optional(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(String::new()),
+        text => text,
+    }
+}
+
+impl ServerAccounts {
+    fn path(directory: &SaveDirectory, file_name: &str) -> PathBuf {
+        directory.0.join(file_name)
+    }
+
+    pub fn load(directory: &SaveDirectory) -> io::Result<Self> {
+        let read = |file_name| read_optional(&ServerAccounts::path(directory, file_name));
+        Ok(ServerAccounts {
+            players: PlayerAccounts::from_text(&read(PLAYER_ACCOUNTS_FILE_NAME)?),
+            whitelist: Whitelist::from_text(&read(WHITELIST_FILE_NAME)?),
+            bans: BanList::from_text(&read(BAN_LIST_FILE_NAME)?),
+        })
+    }
+
+    pub fn save(&self, directory: &SaveDirectory) -> io::Result<()> {
+        fs::create_dir_all(&directory.0)?;
+        let write =
+            |file_name, text: String| fs::write(ServerAccounts::path(directory, file_name), text);
+        write(PLAYER_ACCOUNTS_FILE_NAME, self.players.to_text())?;
+        write(WHITELIST_FILE_NAME, self.whitelist.to_text())?;
+        write(BAN_LIST_FILE_NAME, self.bans.to_text())
+    }
+
+    /// The account a player logs in to, or why they can not join.
+    /// The first login keeps the key the client sent, every later one has to send the same key.
+    pub fn login(
+        &mut self,
+        name: &str,
+        address: IpAddr,
+        key: &str,
+    ) -> Result<&mut PlayerAccount, String> {
+        if let Some(reason) = self.bans.reason(name, address) {
+            return Err(match reason {
+                "" => "you are banned from this server".to_string(),
+                reason => format!("you are banned from this server: {reason}"),
+            });
+        }
+        if !self.whitelist.allows(name) {
+            return Err("you are not on the whitelist".to_string());
+        }
+        let account = self.players.find_or_create(name);
+        match &account.key {
+            Some(account_key) if account_key != key => {
+                Err(format!("{} belongs to another player", account.name))
+            }
+            Some(_) => Ok(account),
+            // whoever logs in first claims the account, so it can not come with raised permissions
+            None => {
+                account.key = Some(key.to_string());
+                account.permission = PermissionLevel::default();
+                Ok(account)
+            }
+        }
+    }
+}
+
+/// Whether a player with `permission` may change the block at world voxel `position`.
+/// Every player arrives at the spawn, only operators and admins build around it.
+pub fn may_edit_block(permission: PermissionLevel, position: IVec3) -> bool {
+    let spawn = voxel_at_point(PLAYER_SPAWN);
+    permission >= PermissionLevel::Operator
+        || (position.xz() - spawn.xz()).abs().max_element() > SPAWN_PROTECTION_IN_VOXELS
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    fn account(id: u32, name: &str, permission: PermissionLevel) -> PlayerAccount {
+        PlayerAccount {
+            id,
+            name: name.to_string(),
+            permission,
+            game_mode: GameMode::default(),
+            position: None,
+            key: None,
+        }
+    }
+
+    #[test]
+    fn player_accounts_round_trip_through_text() {
+        let accounts = PlayerAccounts {
+            accounts: vec![
+                PlayerAccount {
+                    game_mode: GameMode::Creative,
+                    position: Some(Vec3::new(1.5, -2.0, 30.25)),
+                    key: Some("00ff".to_string()),
+                    ..account(1, "Alice", PermissionLevel::Admin)
+                },
+                account(2, "Bob", PermissionLevel::Player),
+            ],
+        };
+        let text = accounts.to_text();
+        assert_eq!(
+            text,
+            "player=1 admin creative 1.5 -2 30.25 00ff Alice\n\
+             player=2 player survival - - - - Bob\n"
+        );
+        assert_eq!(PlayerAccounts::from_text(&text), accounts);
+    }
+
+    #[test]
+    fn odd_account_lines_are_read_as_well_as_they_can_be() {
+        let accounts = PlayerAccounts::from_text(
+            "player=1 operator spectator - - - Carol\n\
+             player=2 king survival - - - Dave\n\
+             player=3 player survival 1 2 Erin\n\
+             player=4 player survival - - - key carol\n\
+             player=5 player survival - - - key Frank extra\n\
+             colour=blue\n",
+        );
+        // accounts from before keys were saved have none, names are only taken once
+        let mut carol = account(1, "Carol", PermissionLevel::Operator);
+        carol.game_mode = GameMode::Spectator;
+        assert_eq!(accounts.accounts, vec![carol]);
+    }
+
+    #[test]
+    fn whitelists_round_trip_and_match_any_case() {
+        let mut whitelist = Whitelist::default();
+        assert!(whitelist.allows("anyone"));
+        whitelist.enabled = true;
+        assert!(whitelist.add("Alice"));
+        assert!(!whitelist.add("ALICE"));
+        assert!(whitelist.add("Bob"));
+        assert!(whitelist.allows("alice"));
+        assert!(!whitelist.allows("Carol"));
+        assert_eq!(
+            whitelist.to_text(),
+            "enabled=true\nplayer=Alice\nplayer=Bob\n"
+        );
+        assert_eq!(Whitelist::from_text(&whitelist.to_text()), whitelist);
+        assert!(whitelist.remove("bob"));
+        assert!(!whitelist.remove("bob"));
+    }
+
+    #[test]
+    fn ban_lists_round_trip_with_their_reasons() {
+        let address = IpAddr::from(Ipv4Addr::new(10, 0, 0, 7));
+        let mut bans = BanList::default();
+        assert!(bans.ban("Mallory", "griefing\nthe spawn"));
+        assert!(!bans.ban("mallory", "again"));
+        bans.addresses.push((address, String::new()));
+        let text = bans.to_text();
+        assert_eq!(
+            text,
+            "player=Mallory griefing the spawn\naddress=10.0.0.7 \n"
+        );
+        assert_eq!(BanList::from_text(&text), bans);
+
+        let elsewhere = IpAddr::from(Ipv4Addr::LOCALHOST);
+        assert_eq!(
+            bans.reason("MALLORY", elsewhere),
+            Some("griefing the spawn")
+        );
+        assert_eq!(bans.reason("Trent", address), Some(""));
+        assert_eq!(bans.reason("Trent", elsewhere), None);
+        assert!(bans.pardon("10.0.0.7"));
+        assert!(bans.pardon("Mallory"));
+        assert!(!bans.pardon("Mallory"));
+        assert_eq!(bans, BanList::default());
+    }
+
+    #[test]
+    fn names_stay_with_the_key_they_first_logged_in_with() {
+        let address = IpAddr::from(Ipv4Addr::LOCALHOST);
+        let mut accounts = ServerAccounts::default();
+        let id = accounts.login("Alice", address, "aaaa").unwrap().id;
+        assert_eq!(accounts.login("alice", address, "aaaa").unwrap().id, id);
+        assert_eq!(
+            accounts.login("ALICE", address, "bbbb").unwrap_err(),
+            "Alice belongs to another player"
+        );
+
+        // an account made before its player joined takes the first key, but not its permissions
+        accounts.players.find_or_create("Bob").permission = PermissionLevel::Admin;
+        let bob = accounts.login("Bob", address, "bbbb").unwrap();
+        assert_eq!(bob.permission, PermissionLevel::Player);
+        bob.permission = PermissionLevel::Admin;
+        assert_eq!(
+            accounts.login("Bob", address, "bbbb").unwrap().permission,
+            PermissionLevel::Admin
+        );
+        assert!(accounts.login("Bob", address, "aaaa").is_err());
+
+        accounts.bans.ban("Bob", "");
+        assert_eq!(
+            accounts.login("Bob", address, "bbbb").unwrap_err(),
+            "you are banned from this server"
+        );
+        accounts.whitelist.enabled = true;
+        assert_eq!(
+            accounts.login("Carol", address, "cccc").unwrap_err(),
+            "you are not on the whitelist"
+        );
+    }
+
+    #[test]
+    fn only_operators_edit_blocks_around_the_spawn() {
+        let spawn = voxel_at_point(PLAYER_SPAWN);
+        let protected = spawn + IVec3::new(SPAWN_PROTECTION_IN_VOXELS, -20, -3);
+        let outside = spawn + IVec3::new(0, 0, SPAWN_PROTECTION_IN_VOXELS + 1);
+        assert!(!may_edit_block(PermissionLevel::Player, spawn));
+        assert!(!may_edit_block(PermissionLevel::Player, protected));
+        assert!(may_edit_block(PermissionLevel::Player, outside));
+        for permission in [PermissionLevel::Operator, PermissionLevel::Admin] {
+            assert!(may_edit_block(permission, spawn));
+            assert!(may_edit_block(permission, protected));
+        }
+    }
+}
@@ -0,0 +1,293 @@
+mod systems;
+
+pub mod accounts;
+pub mod resources;
+
+use bevy::{app::AppExit, prelude::*};
+
+use crate::{
+    events::SaveWorldEvent,
+    game::{
+        commands::systems::run_commands,
+        tick::{every_n_ticks, WorldTickSet},
+        world::{resources::VoxelWorld, systems::set_blocks},
+    },
+    network::WORLD_TIME_SYNC_TICKS,
+    AppState,
+};
+
+use self::{resources::*, systems::*};
+
+/// Port a server listens on when none is given
+pub const DEFAULT_SERVER_PORT: u16 = 25575;
+
+/// Files inside the world's save directory with who may play on it
+pub const PLAYER_ACCOUNTS_FILE_NAME: &str = "players.txt";
+pub const WHITELIST_FILE_NAME: &str = "whitelist.txt";
+pub const BAN_LIST_FILE_NAME: &str = "bans.txt";
+
+/// Furthest from a player, in voxels, a block they change can be
+pub const BLOCK_REACH_IN_VOXELS: f32 = 64.0;
+/// How far out from the spawn, along x and z, only operators and admins may change blocks
+pub const SPAWN_PROTECTION_IN_VOXELS: i32 = 16;
+
+/// How many chunks out from the chunk a player is in, along x and z, get streamed to them
+pub const VIEW_DISTANCE_IN_CHUNKS: i32 = 2;
+
+/// Runs the world for a headless server, add it next to `MinimalPlugins` and `GamePlugin`
+pub struct ServerPlugin;
+
+impl Plugin for ServerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ServerSettings>()
+            .init_resource::<ReplicatedChunks>()
+            .add_systems(
+                Startup,
+                (bind_server_socket, spawn_console_reader, load_accounts),
+            )
+            .add_systems(Update, stop_after_ticks.run_if(in_state(AppState::Game)))
+            .add_systems(
+                Update,
+                (
+                    read_console,
+                    (run_player_commands, run_account_commands)
+                        .after(receive_packets)
+                        .distributive_run_if(resource_exists::<NetworkServer>()),
+                    send_command_feedback.after(run_commands),
+                )
+                    .chain()
+                    .run_if(in_state(AppState::Game)),
+            )
+            .add_systems(
+                Update,
+                (
+                    receive_packets
+                        .before(set_blocks)
+                        .run_if(resource_exists::<VoxelWorld>()),
+                    replicate_chunks
+                        .after(set_blocks)
+                        .run_if(resource_exists::<VoxelWorld>()),
+                    replicate_dropped_items.after(run_commands),
+                    remember_players,
+                    answer_discovery,
+                    disconnect_clients.after(stop_after_ticks),
+                    flush_connections,
+                )
+                    .chain()
+                    .run_if(resource_exists::<NetworkServer>()),
+            )
+            .add_systems(
+                Last,
+                save_accounts.run_if(on_event::<AppExit>().or_else(on_event::<SaveWorldEvent>())),
+            )
+            .add_systems(
+                FixedUpdate,
+                send_world_time
+                    .after(WorldTickSet::Begin)
+                    .run_if(resource_exists::<NetworkServer>())
+                    .run_if(every_n_ticks(WORLD_TIME_SYNC_TICKS)),
+            )
+            .add_systems(
+                FixedUpdate,
+                (
+                    move_players.in_set(WorldTickSet::Entities),
+                    send_player_states.after(WorldTickSet::Entities),
+                )
+                    .run_if(resource_exists::<NetworkServer>()),
+            );
+    }
+}
+
+#[cfg(test)]
+impl ServerPlugin {
+    /// The server binary's app on any free port, with time stepping a world tick each update
+    pub fn test_app(
+        directory: &crate::game::save::resources::SaveDirectory,
+        stop_after_ticks: Option<u64>,
+    ) -> App {
+        use crate::game::{tick::WORLD_TICKS_PER_SECOND, GamePlugin};
+        use bevy::time::TimeUpdateStrategy;
+        use std::time::Duration;
+
+        let tick_length = Duration::from_secs_f64(1.0 / WORLD_TICKS_PER_SECOND);
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, GamePlugin, ServerPlugin))
+            .add_state::<AppState>()
+            .insert_resource(Time::<Fixed>::from_duration(tick_length))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(tick_length))
+            .insert_resource(ServerSettings {
+                port: 0,
+                stop_after_ticks,
+                ..default()
+            })
+            .insert_resource(directory.clone());
+        app
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::{accounts::ServerAccounts, *};
+    use crate::{
+        events::{CommandEvent, CommandFeedbackEvent, CommandSender, SetBlockEvent},
+        game::{
+            commands::parser::{parse_command, PermissionLevel},
+            save::resources::SaveDirectory,
+            tick::resources::WorldTick,
+            world::{
+                access::{BlockChange, VoxelAccess, WorldVoxels},
+                components::BlockType,
+                CHUNK_SIZE,
+            },
+        },
+    };
+
+    /// Updates the app like its runner would until it asks to exit, returns how many updates that took
+    fn run_until_exit(app: &mut App) -> u32 {
+        for updates in 1..1000 {
+            app.update();
+            if !app.world.resource::<Events<AppExit>>().is_empty() {
+                return updates;
+            }
+        }
+        panic!("the server did not stop");
+    }
+
+    fn block_at(app: &mut App, position: IVec3) -> BlockChange {
+        app.world.run_system_once(move |voxels: WorldVoxels| {
+            voxels.voxel(position).unwrap().block_change()
+        })
+    }
+
+    #[test]
+    fn server_stops_after_its_ticks_and_keeps_edits() {
+        let directory = SaveDirectory(
+            std::env::temp_dir().join(format!("voxel_game_server_{}", std::process::id())),
+        );
+        let mut app = ServerPlugin::test_app(&directory, Some(20));
+        app.update();
+        // glass on top of the ground in one column
+        let ground = (0..CHUNK_SIZE.y)
+            .rev()
+            .find(|y| block_at(&mut app, IVec3::new(1, *y, 1)).is_some())
+            .unwrap();
+        assert!(ground + 1 < CHUNK_SIZE.y);
+        let edited = IVec3::new(1, ground + 1, 1);
+        app.world.send_event(SetBlockEvent {
+            position: edited,
+            block: Some(BlockType::Glass),
+            state: 0,
+        });
+        let updates = run_until_exit(&mut app);
+        assert_eq!(app.world.resource::<WorldTick>().0, 20);
+        // a tick each update once the world is up
+        assert_eq!(updates, 20);
+
+        let mut reloaded = ServerPlugin::test_app(&directory, None);
+        reloaded.update();
+        reloaded.update();
+        let block = block_at(&mut reloaded, edited);
+        fs::remove_dir_all(&directory.0).unwrap();
+        assert_eq!(block, Some((BlockType::Glass, 0)));
+    }
+
+    /// Runs one command through the account commands, returns what they answered
+    fn run_command(
+        app: &mut App,
+        sender: CommandSender,
+        permission: PermissionLevel,
+        line: &str,
+    ) -> String {
+        let command = parse_command(line, permission).unwrap();
+        app.world.send_event(CommandEvent {
+            sender,
+            permission,
+            origin: None,
+            command,
+        });
+        app.update();
+        let events = app.world.resource::<Events<CommandFeedbackEvent>>();
+        let mut reader = events.get_reader();
+        let feedback = reader.read(events).last().unwrap();
+        assert_eq!(feedback.recipient, sender);
+        feedback.text.clone()
+    }
+
+    #[test]
+    fn account_commands_only_act_on_lower_permissions() {
+        let directory = SaveDirectory(
+            std::env::temp_dir().join(format!("voxel_game_accounts_{}", std::process::id())),
+        );
+        let mut accounts = ServerAccounts::default();
+        for (name, permission) in [
+            ("Alice", PermissionLevel::Admin),
+            ("Oscar", PermissionLevel::Operator),
+            ("Pat", PermissionLevel::Player),
+        ] {
+            let account = accounts.players.find_or_create(name);
+            account.permission = permission;
+            account.key = Some(format!("{name}key"));
+        }
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_event::<CommandEvent>()
+            .add_event::<CommandFeedbackEvent>()
+            .insert_resource(NetworkServer::bind(0).unwrap())
+            .insert_resource(directory.clone())
+            .insert_resource(accounts)
+            .add_systems(Update, run_account_commands);
+
+        let admin = (CommandSender::Player(1), PermissionLevel::Admin);
+        let operator = (CommandSender::Player(2), PermissionLevel::Operator);
+        let console = (CommandSender::Console, PermissionLevel::Admin);
+        let expected = [
+            (operator, "/kick Alice", "You can not remove Alice"),
+            (operator, "/ban Oscar", "You can not remove Oscar"),
+            (operator, "/kick Pat", "Pat is not playing"),
+            (operator, "/ban Pat cheating", "Banned Pat"),
+            (operator, "/pardon Pat", "Unbanned Pat"),
+            (admin, "/whitelist add Pat", "Added Pat to the whitelist"),
+            (
+                admin,
+                "/op Alice player",
+                "You can not change the permissions of Alice",
+            ),
+            (admin, "/op Oscar admin", "Oscar is now admin"),
+            (
+                admin,
+                "/op Oscar player",
+                "You can not change the permissions of Oscar",
+            ),
+            (console, "/op Oscar player", "Oscar is now player"),
+            (console, "/kick Alice", "Alice is not playing"),
+            (
+                admin,
+                "/op Newcomer operator",
+                "Newcomer has to join before they can be made operator",
+            ),
+            (admin, "/op Pat operator", "Pat is now operator"),
+        ];
+        for ((sender, permission), line, text) in expected {
+            assert_eq!(
+                run_command(&mut app, sender, permission, line),
+                text,
+                "{line}"
+            );
+        }
+
+        // every change is saved as it is made
+        let saved = ServerAccounts::load(&directory).unwrap();
+        fs::remove_dir_all(&directory.0).unwrap();
+        assert_eq!(&saved, app.world.resource::<ServerAccounts>());
+        let permission = |name| saved.players.find(name).unwrap().permission;
+        assert_eq!(permission("Oscar"), PermissionLevel::Player);
+        assert_eq!(permission("Pat"), PermissionLevel::Operator);
+        assert!(saved.players.find("Newcomer").is_none());
+        assert!(saved.whitelist.allows("pat"));
+        assert!(saved.bans.players.is_empty());
+    }
+}
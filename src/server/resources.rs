@@ -0,0 +1,232 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr, UdpSocket},
+    path::PathBuf,
+    sync::{mpsc::Receiver, Mutex},
+    time::Duration,
+};
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    bytes::truncate,
+    game::{
+        commands::{parser::PermissionLevel, resources::GameMode},
+        tick::WORLD_TICKS_PER_SECOND,
+        world::access::BlockChange,
+    },
+    network::{
+        connection::Connection, discovery::ServerStatus, prediction::AuthoritativePlayer,
+        protocol::Message, DISCOVERY_PORT, MAX_CHAT_LENGTH, PROTOCOL_VERSION,
+    },
+};
+
+use super::{accounts::same_name, DEFAULT_SERVER_PORT};
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct ServerSettings {
+    pub port: u16,
+    /// shut down once the world has run this many ticks, for test runs
+    pub stop_after_ticks: Option<u64>,
+    /// message of the day, shown in the server browser
+    pub motd: String,
+    pub max_players: u32,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            port: DEFAULT_SERVER_PORT,
+            stop_after_ticks: None,
+            motd: "A voxel game server".to_string(),
+            max_players: 8,
+        }
+    }
+}
+
+/// Everything the server binary is configured with on the command line
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerArgs {
+    /// save directory of the world, it is created if it does not exist
+    pub world: Option<PathBuf>,
+    pub tick_rate: f64,
+    pub settings: ServerSettings,
+}
+
+impl Default for ServerArgs {
+    fn default() -> Self {
+        ServerArgs {
+            world: None,
+            tick_rate: WORLD_TICKS_PER_SECOND,
+            settings: ServerSettings::default(),
+        }
+    }
+}
+
+impl ServerArgs {
+    pub const USAGE: &'static str =
+        "usage: server [--world <path>] [--port <port>] [--tick-rate <ticks per second>] [--ticks <count>] [--motd <text>] [--max-players <count>]";
+
+    /// Parses the arguments after the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut server_args = ServerArgs::default();
+        let mut args = args.into_iter();
+        while let Some(flag) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {flag}"))
+            };
+            match flag.as_str() {
+                "--world" => server_args.world = Some(PathBuf::from(value()?)),
+                "--port" => {
+                    let port = value()?;
+                    server_args.settings.port =
+                        port.parse().map_err(|_| format!("invalid port {port}"))?;
+                }
+                "--tick-rate" => {
+                    let tick_rate = value()?;
+                    server_args.tick_rate = tick_rate
+                        .parse()
+                        .ok()
+                        .filter(|tick_rate: &f64| *tick_rate > 0.0)
+                        .ok_or_else(|| format!("invalid tick rate {tick_rate}"))?;
+                }
+                "--ticks" => {
+                    let ticks = value()?;
+                    server_args.settings.stop_after_ticks = Some(
+                        ticks
+                            .parse()
+                            .map_err(|_| format!("invalid tick count {ticks}"))?,
+                    );
+                }
+                "--motd" => server_args.settings.motd = value()?,
+                "--max-players" => {
+                    let max_players = value()?;
+                    server_args.settings.max_players = max_players
+                        .parse()
+                        .map_err(|_| format!("invalid player count {max_players}"))?;
+                }
+                _ => return Err(format!("unknown argument {flag}")),
+            }
+        }
+        Ok(server_args)
+    }
+}
+
+/// A client known to the server, from its first packet until it disconnects or times out
+#[derive(Debug)]
+pub struct RemoteClient {
+    pub connection: Connection,
+    pub handshake_done: bool,
+    /// set once the client has logged in, only logged in clients get world updates
+    pub login: Option<(u32, String)>,
+    /// the client is dropped once everything sent to it has been acked
+    pub closing: bool,
+    /// the client's player once logged in, chunks around it are streamed to the client
+    pub player: Option<AuthoritativePlayer>,
+    /// chunks the client has been sent and gets deltas for
+    pub loaded_chunks: HashSet<IVec3>,
+    /// dropped items the client has been told about
+    pub known_items: HashSet<Entity>,
+    pub permission: PermissionLevel,
+    pub game_mode: GameMode,
+}
+
+impl RemoteClient {
+    /// A client on the connection its handshake arrived on
+    pub fn new(connection: Connection) -> Self {
+        RemoteClient {
+            connection,
+            handshake_done: false,
+            login: None,
+            closing: false,
+            player: None,
+            loaded_chunks: HashSet::new(),
+            known_items: HashSet::new(),
+            permission: PermissionLevel::default(),
+            game_mode: GameMode::default(),
+        }
+    }
+
+    pub fn player_id(&self) -> Option<u32> {
+        self.login.as_ref().map(|(player_id, _)| *player_id)
+    }
+}
+
+/// The socket clients talk to the server on, and everyone connected to it
+#[derive(Resource, Debug)]
+pub struct NetworkServer {
+    pub socket: UdpSocket,
+    pub clients: HashMap<SocketAddr, RemoteClient>,
+    /// answers status queries broadcast over the local network, only one server on a machine gets it
+    pub discovery_socket: Option<UdpSocket>,
+}
+
+impl NetworkServer {
+    /// Binds a non blocking socket on every interface
+    pub fn bind(port: u16) -> io::Result<Self> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        socket.set_nonblocking(true)?;
+        let discovery_socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, DISCOVERY_PORT))
+            .and_then(|socket| socket.set_nonblocking(true).map(|_| socket));
+        if let Err(error) = &discovery_socket {
+            warn!("Not answering server discovery on port {DISCOVERY_PORT}: {error}");
+        }
+        Ok(NetworkServer {
+            socket,
+            clients: HashMap::new(),
+            discovery_socket: discovery_socket.ok(),
+        })
+    }
+
+    pub fn player_count(&self) -> u32 {
+        self.clients
+            .values()
+            .filter(|client| client.login.is_some())
+            .count() as u32
+    }
+
+    /// What the server says about itself to status queries
+    pub fn status(&self, settings: &ServerSettings) -> ServerStatus {
+        ServerStatus {
+            protocol_version: PROTOCOL_VERSION,
+            motd: truncate(&settings.motd, MAX_CHAT_LENGTH).to_string(),
+            player_count: self.player_count(),
+            max_players: settings.max_players,
+            port: settings.port,
+        }
+    }
+
+    /// The logged in client playing under a name
+    pub fn client_by_name_mut(&mut self, name: &str) -> Option<&mut RemoteClient> {
+        self.clients.values_mut().find(|client| {
+            client
+                .login
+                .as_ref()
+                .is_some_and(|(_, login_name)| same_name(login_name, name))
+        })
+    }
+
+    /// Sends a message to every logged in client except `except`
+    pub fn broadcast(&mut self, message: &Message, except: Option<SocketAddr>, now: Duration) {
+        for (address, client) in &mut self.clients {
+            if client.login.is_some() && !client.closing && Some(*address) != except {
+                client.connection.send(message, now);
+            }
+        }
+    }
+}
+
+/// The blocks of every chunk as clients were last told about them, changes are found by comparing against it
+#[derive(Resource, Debug, Default)]
+pub struct ReplicatedChunks {
+    /// `CHUNK_VOLUME` blocks per chunk in `chunk_index` order
+    pub blocks: HashMap<IVec3, Vec<BlockChange>>,
+}
+
+/// Lines typed into the server's stdin, read on their own thread
+#[derive(Resource, Debug)]
+pub struct ConsoleInput(pub Mutex<Receiver<String>>);
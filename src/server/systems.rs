@@ -0,0 +1,815 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{mpsc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use bevy::{app::AppExit, prelude::*, utils::HashMap};
+
+use crate::{
+    bytes::truncate,
+    events::{CommandEvent, CommandFeedbackEvent, CommandSender, SetBlockEvent},
+    game::{
+        commands::parser::{parse_command, Command, PermissionLevel, WhitelistChange},
+        falling::components::DroppedItem,
+        save::{
+            chunk::{chunk_index, encode_chunk, CHUNK_VOLUME},
+            resources::SaveDirectory,
+        },
+        sky::resources::WorldTime,
+        tick::resources::WorldTick,
+        world::{
+            access::BlockChange,
+            components::{ChunkCoordinate, Voxel},
+            resources::VoxelWorld,
+            to_chunk_space, VOXEL_SIZE,
+        },
+    },
+    network::{
+        chunk::chunk_messages, connection::Connection, discovery::read_status_query,
+        prediction::AuthoritativePlayer, protocol::Message, MAX_CHAT_LENGTH, MAX_DELTA_CHANGES,
+        MAX_KEY_LENGTH, MAX_NAME_LENGTH, MAX_PACKET_SIZE, MAX_SENT_INPUTS, PLAYER_SPAWN,
+        PROTOCOL_VERSION,
+    },
+};
+
+use super::{
+    accounts::{may_edit_block, ServerAccounts},
+    resources::{ConsoleInput, NetworkServer, RemoteClient, ReplicatedChunks, ServerSettings},
+    BLOCK_REACH_IN_VOXELS, VIEW_DISTANCE_IN_CHUNKS,
+};
+
+/// Shuts the server down once it has run the ticks it was asked to, the world is saved on the way out
+pub fn stop_after_ticks(
+    settings: Res<ServerSettings>,
+    world_tick: Res<WorldTick>,
+    mut app_exit_events: EventWriter<AppExit>,
+) {
+    if settings
+        .stop_after_ticks
+        .is_some_and(|ticks| world_tick.0 >= ticks)
+    {
+        info!("Stopping server after {} ticks", world_tick.0);
+        app_exit_events.send(AppExit);
+    }
+}
+
+/// Reads who may play on the world, a world that has never been hosted starts with nobody
+pub fn load_accounts(mut commands: Commands, save_directory: Res<SaveDirectory>) {
+    let accounts = ServerAccounts::load(&save_directory).unwrap_or_else(|error| {
+        error!("Failed to load player accounts: {error}");
+        ServerAccounts::default()
+    });
+    commands.insert_resource(accounts);
+}
+
+/// Writes the accounts next to the world, together with the world saves
+pub fn save_accounts(accounts: Res<ServerAccounts>, save_directory: Res<SaveDirectory>) {
+    if let Err(error) = accounts.save(&save_directory) {
+        error!("Failed to save player accounts: {error}");
+    }
+}
+
+/// Keeps the accounts of logged in players up to date, so they come back where they left
+pub fn remember_players(server: Res<NetworkServer>, mut accounts: ResMut<ServerAccounts>) {
+    for client in server.clients.values() {
+        let (Some(player_id), Some(player)) = (client.player_id(), &client.player) else {
+            continue;
+        };
+        if let Some(account) = accounts.players.get_mut(player_id) {
+            account.position = Some(player.position);
+            account.game_mode = client.game_mode;
+        }
+    }
+}
+
+/// Opens the server socket on the configured port, the server runs without networking if that fails
+pub fn bind_server_socket(mut commands: Commands, settings: Res<ServerSettings>) {
+    match NetworkServer::bind(settings.port) {
+        Ok(server) => commands.insert_resource(server),
+        Err(error) => error!("Failed to listen on port {}: {error}", settings.port),
+    }
+}
+
+/// Reads every packet waiting on the server socket and handles the messages they deliver
+#[allow(clippy::too_many_arguments)]
+pub fn receive_packets(
+    mut server: ResMut<NetworkServer>,
+    settings: Res<ServerSettings>,
+    mut accounts: ResMut<ServerAccounts>,
+    replicated_chunks: Res<ReplicatedChunks>,
+    time: Res<Time<Real>>,
+    fixed_time: Res<Time<Fixed>>,
+    mut set_block_events: EventWriter<SetBlockEvent>,
+    mut command_events: EventWriter<CommandEvent>,
+) {
+    let now = time.elapsed();
+    let tick_rate = (1.0 / fixed_time.timestep().as_secs_f64()) as f32;
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    loop {
+        let (length, address) = match server.socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+            // a client going away can show up as an error on the next receive, it is not fatal
+            Err(_) => continue,
+        };
+        // status queries come from the server browser, they are answered without a connection
+        if let Some(nonce) = read_status_query(&buffer[..length]) {
+            let status = server.status(&settings);
+            let _ = server.socket.send_to(&status.to_bytes(nonce), address);
+            continue;
+        }
+        let messages = match server.clients.get_mut(&address) {
+            Some(client) => client.connection.receive(&buffer[..length], now),
+            // an address only gets a client once it opens with a handshake, anything else from it is dropped
+            None => {
+                let mut connection = Connection::new(now);
+                match connection.receive(&buffer[..length], now) {
+                    Ok(messages) if matches!(messages.first(), Some(Message::Handshake { .. })) => {
+                        server
+                            .clients
+                            .insert(address, RemoteClient::new(connection));
+                        Ok(messages)
+                    }
+                    _ => continue,
+                }
+            }
+        };
+        let Ok(messages) = messages else {
+            continue;
+        };
+        for message in messages {
+            handle_message(
+                &mut server,
+                &settings,
+                &mut accounts,
+                &replicated_chunks,
+                address,
+                message,
+                now,
+                tick_rate,
+                &mut set_block_events,
+                &mut command_events,
+            );
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_message(
+    server: &mut NetworkServer,
+    settings: &ServerSettings,
+    accounts: &mut ServerAccounts,
+    replicated_chunks: &ReplicatedChunks,
+    address: SocketAddr,
+    message: Message,
+    now: Duration,
+    tick_rate: f32,
+    set_block_events: &mut EventWriter<SetBlockEvent>,
+    command_events: &mut EventWriter<CommandEvent>,
+) {
+    let server_full = server.player_count() >= settings.max_players;
+    let Some(client) = server.clients.get_mut(&address) else {
+        return;
+    };
+    if client.closing {
+        return;
+    }
+    match message {
+        Message::Handshake { protocol_version } => {
+            if protocol_version == PROTOCOL_VERSION {
+                client.handshake_done = true;
+            } else {
+                let reason = format!(
+                    "server is on protocol version {PROTOCOL_VERSION}, client is on {protocol_version}"
+                );
+                client.connection.send(&Message::Disconnect { reason }, now);
+                client.closing = true;
+            }
+        }
+        Message::Login { .. } if server_full && client.login.is_none() => {
+            let reason = "server is full".to_string();
+            client.connection.send(&Message::Disconnect { reason }, now);
+            client.closing = true;
+        }
+        Message::Login { name, key } if client.handshake_done && client.login.is_none() => {
+            // names are single words so commands and the account files can take them
+            let name = truncate(&name, MAX_NAME_LENGTH)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join("_");
+            // keys are stored as a word in the account file
+            let valid_key = !key.is_empty()
+                && key.len() <= MAX_KEY_LENGTH
+                && key
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric());
+            let refusal = if name.is_empty() {
+                Some("invalid name".to_string())
+            } else if !valid_key {
+                Some("invalid player key".to_string())
+            } else if server.client_by_name_mut(&name).is_some() {
+                Some(format!("{name} is already playing"))
+            } else {
+                accounts.login(&name, address.ip(), &key).err()
+            };
+            let client = server.clients.get_mut(&address).unwrap();
+            if let Some(reason) = refusal {
+                info!("Refused {name} from {address}: {reason}");
+                client.connection.send(&Message::Disconnect { reason }, now);
+                client.closing = true;
+                return;
+            }
+            let account = accounts.players.find_or_create(&name);
+            let spawn = account.position.unwrap_or(PLAYER_SPAWN);
+            client.login = Some((account.id, name.clone()));
+            client.permission = account.permission;
+            client.game_mode = account.game_mode;
+            client.player = Some(AuthoritativePlayer::new(spawn, tick_rate));
+            let message = Message::LoginAccepted {
+                player_id: account.id,
+                spawn,
+                permission: account.permission,
+                tick_rate,
+            };
+            client.connection.send(&message, now);
+            info!("{name} joined from {address}");
+        }
+        Message::BlockChange { position, block } if client.login.is_some() => {
+            let in_reach = client.player.as_ref().is_some_and(|player| {
+                (position.as_vec3() + 0.5).distance(player.position / VOXEL_SIZE)
+                    <= BLOCK_REACH_IN_VOXELS
+            });
+            let allowed = in_reach
+                && may_edit_block(client.permission, position)
+                && client.game_mode.can_edit_blocks()
+                && block.map_or(true, |(block, state)| {
+                    client.game_mode.can_place(block) && block.is_valid_state(state)
+                });
+            if allowed {
+                set_block_events.send(SetBlockEvent {
+                    position,
+                    block: block.map(|(block, _)| block),
+                    state: block.map_or(0, |(_, state)| state),
+                });
+            } else {
+                // the client changed the block already, tell it what the block really is
+                let (chunk_position, local_position) = to_chunk_space(position);
+                if let Some(blocks) = replicated_chunks.blocks.get(&chunk_position) {
+                    let message = Message::BlockChange {
+                        position,
+                        block: blocks[chunk_index(local_position)],
+                    };
+                    client.connection.send(&message, now);
+                }
+            }
+        }
+        Message::PlayerInput { inputs } => {
+            // applied one a tick by `move_players`, sending them faster does not move faster
+            if let Some(player) = &mut client.player {
+                player.receive_inputs(&inputs[..inputs.len().min(MAX_SENT_INPUTS)]);
+            }
+        }
+        Message::Chat { text } if text.starts_with('/') => {
+            let (Some(player_id), Some(player)) = (client.player_id(), &client.player) else {
+                return;
+            };
+            match parse_command(&text, client.permission) {
+                Ok(command) => command_events.send(CommandEvent {
+                    sender: CommandSender::Player(player_id),
+                    permission: client.permission,
+                    origin: Some(player.position),
+                    command,
+                }),
+                Err(error) => {
+                    let message = Message::Chat {
+                        text: error.to_string(),
+                    };
+                    client.connection.send(&message, now);
+                }
+            }
+        }
+        Message::Chat { text } => {
+            if let Some((_, name)) = &client.login {
+                let text = format!("<{name}> {}", truncate(&text, MAX_CHAT_LENGTH));
+                info!("{text}");
+                server.broadcast(&Message::Chat { text }, None, now);
+            }
+        }
+        Message::Disconnect { reason } => {
+            if let Some((_, name)) = &client.login {
+                info!("{name} left: {reason}");
+            }
+            server.clients.remove(&address);
+        }
+        // anything out of turn, like a second login, is ignored
+        _ => {}
+    }
+}
+
+/// Answers status queries broadcast to the discovery port by server browsers on the local network
+pub fn answer_discovery(server: Res<NetworkServer>, settings: Res<ServerSettings>) {
+    let Some(discovery_socket) = &server.discovery_socket else {
+        return;
+    };
+    let mut buffer = [0; MAX_PACKET_SIZE];
+    loop {
+        let (length, address) = match discovery_socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+            Err(_) => continue,
+        };
+        if let Some(nonce) = read_status_query(&buffer[..length]) {
+            let status = server.status(&settings);
+            let _ = discovery_socket.send_to(&status.to_bytes(nonce), address);
+        }
+    }
+}
+
+/// Finds the blocks that changed since the last run and sends them to the clients that have their chunk,
+/// then sends clients the chunks that came into their view and unloads the ones that left it
+pub fn replicate_chunks(
+    mut server: ResMut<NetworkServer>,
+    mut replicated_chunks: ResMut<ReplicatedChunks>,
+    time: Res<Time<Real>>,
+    voxel_world: Res<VoxelWorld>,
+    changed_voxel_query: Query<(&Voxel, &ChunkCoordinate, &Parent), Changed<Voxel>>,
+) {
+    let now = time.elapsed();
+    let chunk_positions: HashMap<Entity, IVec3> = voxel_world
+        .chunks
+        .iter()
+        .map(|(chunk_position, chunk)| (chunk.entity_id, *chunk_position))
+        .collect();
+
+    // light changes mark voxels as changed too, only block changes make it into a delta
+    let mut deltas: HashMap<IVec3, Vec<(u16, BlockChange)>> = HashMap::new();
+    for (voxel, chunk_coordinate, parent) in changed_voxel_query.iter() {
+        let Some(chunk_position) = chunk_positions.get(&parent.get()) else {
+            continue;
+        };
+        let index = chunk_index(chunk_coordinate.into_ivec3());
+        let blocks = replicated_chunks
+            .blocks
+            .entry(*chunk_position)
+            .or_insert_with(|| vec![None; CHUNK_VOLUME]);
+        if blocks[index] != voxel.block_change() {
+            blocks[index] = voxel.block_change();
+            deltas
+                .entry(*chunk_position)
+                .or_default()
+                .push((index as u16, voxel.block_change()));
+        }
+    }
+
+    for client in server.clients.values_mut() {
+        if client.login.is_none() || client.closing {
+            continue;
+        }
+        let mut full_chunks = vec![];
+        for (chunk_position, changes) in &deltas {
+            if !client.loaded_chunks.contains(chunk_position) {
+                continue;
+            }
+            if changes.len() > MAX_DELTA_CHANGES {
+                full_chunks.push(*chunk_position);
+            } else {
+                let mut changes = changes.clone();
+                changes.sort_by_key(|(index, _)| *index);
+                let message = Message::ChunkDelta {
+                    position: *chunk_position,
+                    changes,
+                };
+                client.connection.send(&message, now);
+            }
+        }
+
+        if let Some(position) = client.player.as_ref().map(|player| player.position) {
+            let (player_chunk, _) = to_chunk_space((position / VOXEL_SIZE).floor().as_ivec3());
+            // measured across the ground only, flying up high does not unload the world below
+            let chunk_distance = |chunk_position: IVec3| {
+                let offset = (chunk_position - player_chunk).abs();
+                offset.x.max(offset.z)
+            };
+            let mut entering: Vec<IVec3> = replicated_chunks
+                .blocks
+                .keys()
+                .copied()
+                .filter(|chunk_position| {
+                    chunk_distance(*chunk_position) <= VIEW_DISTANCE_IN_CHUNKS
+                        && !client.loaded_chunks.contains(chunk_position)
+                })
+                .collect();
+            // nearest first, so the chunks around the player show up before the far ones
+            entering.sort_by_key(|chunk_position| {
+                (chunk_distance(*chunk_position), chunk_position.to_array())
+            });
+            full_chunks.extend(entering);
+
+            // a chunk is only unloaded a chunk further out than it is loaded,
+            // so walking along a chunk border does not send it over and over
+            let mut leaving: Vec<IVec3> = client
+                .loaded_chunks
+                .iter()
+                .copied()
+                .filter(|chunk_position| {
+                    chunk_distance(*chunk_position) > VIEW_DISTANCE_IN_CHUNKS + 1
+                })
+                .collect();
+            leaving.sort_by_key(|chunk_position| chunk_position.to_array());
+            for chunk_position in leaving {
+                client.loaded_chunks.remove(&chunk_position);
+                let message = Message::UnloadChunk {
+                    position: chunk_position,
+                };
+                client.connection.send(&message, now);
+            }
+        }
+
+        for chunk_position in full_chunks {
+            let Some(blocks) = replicated_chunks.blocks.get(&chunk_position) else {
+                continue;
+            };
+            for message in chunk_messages(chunk_position, &encode_chunk(blocks)) {
+                client.connection.send(&message, now);
+            }
+            client.loaded_chunks.insert(chunk_position);
+        }
+    }
+}
+
+/// Tells clients about the items lying in the world they have not been told about yet,
+/// like the ones `/give` drops, so they show up for everyone
+pub fn replicate_dropped_items(
+    mut server: ResMut<NetworkServer>,
+    time: Res<Time<Real>>,
+    dropped_item_query: Query<(Entity, &DroppedItem, &Transform)>,
+) {
+    let now = time.elapsed();
+    for client in server.clients.values_mut() {
+        if client.login.is_none() || client.closing {
+            continue;
+        }
+        for (entity, dropped_item, transform) in dropped_item_query.iter() {
+            if client.known_items.insert(entity) {
+                let message = Message::DroppedItem {
+                    block: dropped_item.block,
+                    position: transform.translation,
+                };
+                client.connection.send(&message, now);
+            }
+        }
+    }
+}
+
+/// Moves every player by the next input its client sent
+pub fn move_players(mut server: ResMut<NetworkServer>) {
+    for client in server.clients.values_mut() {
+        if let Some(player) = &mut client.player {
+            player.apply_next_input();
+        }
+    }
+}
+
+/// Tells every client where the server has their own player, and where everyone else is
+pub fn send_player_states(
+    mut server: ResMut<NetworkServer>,
+    time: Res<Time<Real>>,
+    world_tick: Res<WorldTick>,
+) {
+    let now = time.elapsed();
+    let players: Vec<(u32, Vec3)> = server
+        .clients
+        .values()
+        .filter_map(|client| Some((client.player_id()?, client.player.as_ref()?.position)))
+        .collect();
+    for client in server.clients.values_mut() {
+        let (Some(player_id), Some(player)) = (client.player_id(), &client.player) else {
+            continue;
+        };
+        if client.closing {
+            continue;
+        }
+        let message = Message::PlayerState {
+            input_sequence: player.input_sequence,
+            position: player.position,
+        };
+        client.connection.send(&message, now);
+        for (other_id, position) in &players {
+            if *other_id != player_id {
+                let message = Message::EntityPosition {
+                    entity_id: *other_id,
+                    tick: world_tick.0,
+                    position: *position,
+                };
+                client.connection.send(&message, now);
+            }
+        }
+    }
+}
+
+/// Keeps the clients' skies in step with the server's world clock
+pub fn send_world_time(
+    mut server: ResMut<NetworkServer>,
+    time: Res<Time<Real>>,
+    world_time: Res<WorldTime>,
+) {
+    let message = Message::WorldTime {
+        day: world_time.day,
+        time_of_day: world_time.time_of_day,
+    };
+    server.broadcast(&message, None, time.elapsed());
+}
+
+/// Starts a thread reading the server's stdin, each line is a command or a message to everyone
+pub fn spawn_console_reader(mut commands: Commands) {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    commands.insert_resource(ConsoleInput(Mutex::new(receiver)));
+}
+
+/// Runs the lines typed into the console, commands with every permission and anything else as chat
+pub fn read_console(
+    console_input: Res<ConsoleInput>,
+    mut server: Option<ResMut<NetworkServer>>,
+    time: Res<Time<Real>>,
+    mut command_events: EventWriter<CommandEvent>,
+) {
+    let lines: Vec<String> = match console_input.0.lock() {
+        Ok(receiver) => receiver.try_iter().collect(),
+        Err(_) => return,
+    };
+    for line in lines {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if !line.starts_with('/') {
+            let text = format!("[Server] {}", truncate(line, MAX_CHAT_LENGTH));
+            info!("{text}");
+            if let Some(server) = &mut server {
+                server.broadcast(&Message::Chat { text }, None, time.elapsed());
+            }
+            continue;
+        }
+        match parse_command(line, PermissionLevel::Admin) {
+            Ok(command) => command_events.send(CommandEvent {
+                sender: CommandSender::Console,
+                permission: PermissionLevel::Admin,
+                origin: None,
+                command,
+            }),
+            Err(error) => println!("{error}"),
+        }
+    }
+}
+
+/// Runs the commands that change a player on the server, the rest are run by the game for everyone
+pub fn run_player_commands(
+    mut server: ResMut<NetworkServer>,
+    mut command_events: EventReader<CommandEvent>,
+    mut feedback_events: EventWriter<CommandFeedbackEvent>,
+) {
+    for event in command_events.read() {
+        let CommandSender::Player(player_id) = event.sender else {
+            continue;
+        };
+        let Some(client) = server
+            .clients
+            .values_mut()
+            .find(|client| client.player_id() == Some(player_id))
+        else {
+            continue;
+        };
+        let text = match &event.command {
+            Command::Teleport(coordinates) => {
+                let Some(player) = &mut client.player else {
+                    continue;
+                };
+                let origin = player.position / VOXEL_SIZE;
+                let target = Vec3::new(
+                    coordinates[0].resolve(origin.x),
+                    coordinates[1].resolve(origin.y),
+                    coordinates[2].resolve(origin.z),
+                );
+                // the client snaps to the new position with the next player state
+                player.position = target * VOXEL_SIZE;
+                format!("Teleported to {} {} {}", target.x, target.y, target.z)
+            }
+            Command::SetGameMode(mode) => {
+                client.game_mode = *mode;
+                format!("Set game mode to {}", mode.name())
+            }
+            _ => continue,
+        };
+        feedback_events.send(CommandFeedbackEvent {
+            recipient: event.sender,
+            text,
+        });
+    }
+}
+
+/// Runs the commands that change who may play, and saves the accounts after each of them
+pub fn run_account_commands(
+    mut server: ResMut<NetworkServer>,
+    mut accounts: ResMut<ServerAccounts>,
+    save_directory: Res<SaveDirectory>,
+    time: Res<Time<Real>>,
+    mut command_events: EventReader<CommandEvent>,
+    mut feedback_events: EventWriter<CommandFeedbackEvent>,
+) {
+    let now = time.elapsed();
+    for event in command_events.read() {
+        if event.sender == CommandSender::LocalPlayer {
+            continue;
+        }
+        // players can only act on players below them, the console on everyone
+        let outranks = |name: &str, accounts: &ServerAccounts| {
+            event.sender == CommandSender::Console
+                || accounts
+                    .players
+                    .find(name)
+                    .map(|account| account.permission)
+                    .unwrap_or_default()
+                    < event.permission
+        };
+        let text = match &event.command {
+            Command::SetPermission { name, permission } => {
+                let joined = accounts
+                    .players
+                    .find(name)
+                    .is_some_and(|account| account.key.is_some());
+                if !outranks(name, &accounts) {
+                    format!("You can not change the permissions of {name}")
+                } else if *permission > PermissionLevel::default() && !joined {
+                    // the first login claims an account, raised permissions have to wait for it
+                    format!(
+                        "{name} has to join before they can be made {}",
+                        permission.name()
+                    )
+                } else {
+                    accounts.players.find_or_create(name).permission = *permission;
+                    if let Some(client) = server.client_by_name_mut(name) {
+                        client.permission = *permission;
+                        let message = Message::PermissionChanged {
+                            permission: *permission,
+                        };
+                        client.connection.send(&message, now);
+                        let message = Message::Chat {
+                            text: format!("You are now {}", permission.name()),
+                        };
+                        client.connection.send(&message, now);
+                    }
+                    format!("{name} is now {}", permission.name())
+                }
+            }
+            Command::Kick { name, reason } | Command::Ban { name, reason } => {
+                if !outranks(name, &accounts) {
+                    format!("You can not remove {name}")
+                } else {
+                    let banning = matches!(event.command, Command::Ban { .. });
+                    if banning {
+                        accounts.bans.ban(name, reason);
+                    }
+                    let kicked = match server.client_by_name_mut(name) {
+                        Some(client) => {
+                            let reason = match (banning, reason.as_str()) {
+                                (true, "") => "you are banned from this server".to_string(),
+                                (true, reason) => {
+                                    format!("you are banned from this server: {reason}")
+                                }
+                                (false, "") => "kicked by an operator".to_string(),
+                                (false, reason) => format!("kicked: {reason}"),
+                            };
+                            client.connection.send(&Message::Disconnect { reason }, now);
+                            client.closing = true;
+                            true
+                        }
+                        None => false,
+                    };
+                    match (banning, kicked) {
+                        (true, _) => format!("Banned {name}"),
+                        (false, true) => format!("Kicked {name}"),
+                        (false, false) => format!("{name} is not playing"),
+                    }
+                }
+            }
+            Command::Pardon(name) => {
+                if accounts.bans.pardon(name) {
+                    format!("Unbanned {name}")
+                } else {
+                    format!("{name} is not banned")
+                }
+            }
+            Command::Whitelist(change) => match change {
+                WhitelistChange::Enable => {
+                    accounts.whitelist.enabled = true;
+                    "Turned the whitelist on".to_string()
+                }
+                WhitelistChange::Disable => {
+                    accounts.whitelist.enabled = false;
+                    "Turned the whitelist off".to_string()
+                }
+                WhitelistChange::Add(name) => {
+                    if accounts.whitelist.add(name) {
+                        format!("Added {name} to the whitelist")
+                    } else {
+                        format!("{name} is already on the whitelist")
+                    }
+                }
+                WhitelistChange::Remove(name) => {
+                    if accounts.whitelist.remove(name) {
+                        format!("Removed {name} from the whitelist")
+                    } else {
+                        format!("{name} is not on the whitelist")
+                    }
+                }
+            },
+            _ => continue,
+        };
+        if let Err(error) = accounts.save(&save_directory) {
+            error!("Failed to save player accounts: {error}");
+        }
+        feedback_events.send(CommandFeedbackEvent {
+            recipient: event.sender,
+            text,
+        });
+    }
+}
+
+/// Sends what commands had to say to the players that ran them, and prints it for the console
+pub fn send_command_feedback(
+    mut server: Option<ResMut<NetworkServer>>,
+    time: Res<Time<Real>>,
+    mut feedback_events: EventReader<CommandFeedbackEvent>,
+) {
+    for event in feedback_events.read() {
+        match event.recipient {
+            CommandSender::Console => println!("{}", event.text),
+            CommandSender::Player(player_id) => {
+                let Some(client) = server.as_mut().and_then(|server| {
+                    server
+                        .clients
+                        .values_mut()
+                        .find(|client| client.player_id() == Some(player_id))
+                }) else {
+                    continue;
+                };
+                let message = Message::Chat {
+                    text: event.text.clone(),
+                };
+                client.connection.send(&message, time.elapsed());
+            }
+            CommandSender::LocalPlayer => {}
+        }
+    }
+}
+
+/// Says goodbye to every client when the server shuts down
+pub fn disconnect_clients(
+    mut server: ResMut<NetworkServer>,
+    time: Res<Time<Real>>,
+    mut app_exit_events: EventReader<AppExit>,
+) {
+    if app_exit_events.read().next().is_none() {
+        return;
+    }
+    let message = Message::Disconnect {
+        reason: "server closed".to_string(),
+    };
+    for client in server.clients.values_mut() {
+        client.connection.send(&message, time.elapsed());
+    }
+}
+
+/// Puts queued, resent and ack packets on the socket, and drops clients that timed out or finished closing
+pub fn flush_connections(mut server: ResMut<NetworkServer>, time: Res<Time<Real>>) {
+    let now = time.elapsed();
+    let NetworkServer {
+        socket, clients, ..
+    } = &mut *server;
+    clients.retain(|address, client| {
+        for packet in client.connection.flush(now) {
+            // a full send buffer loses the packet, reliable ones are resent later
+            let _ = socket.send_to(&packet, address);
+        }
+        if client.connection.timed_out(now) {
+            if let Some((_, name)) = &client.login {
+                info!("{name} timed out");
+            }
+            return false;
+        }
+        !(client.closing && client.connection.is_idle())
+    });
+}